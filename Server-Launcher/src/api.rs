@@ -0,0 +1,730 @@
+use std::fs;
+use std::io::{self, Read};
+#[cfg(unix)]
+use std::mem::ManuallyDrop;
+#[cfg(unix)]
+use std::net::TcpStream;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, RawFd};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, ReadWrite, Request, Response, Server as HttpServer, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::auth::{self, Identity, Permission, RateLimiter, Source};
+use crate::classify::LogLevel;
+use crate::control::{self, ControlCommand, ControlError, ControlRequest, ErrorKind};
+use crate::db;
+use crate::hoststats::HostSampler;
+use crate::metrics;
+use crate::serverdef::ServerDef;
+use crate::stream::{StreamEvent, StreamFilter};
+use crate::web;
+
+// Local HTTP API, every route is answered by the UI loop through `control`.
+// Requests carry a token (see auth.rs), only loopback is let in before the first user exists.
+// Browsers are only listened to from the dashboard's own origin, and POST, PUT and DELETE need
+// `Content-Type: application/json`, which another site's page can't send without asking first.
+//
+//   GET  /                            web dashboard, see web.rs
+//   POST /api/login                   {"user", "password"}, sets the session cookie
+//   POST /api/logout
+//   GET  /api/me                      the caller's user and role
+//   GET  /api/host                    cpu, memory, disks, network and temperatures
+//   GET  /api/servers                 list with live state
+//   GET  /api/servers/<name>          one server
+//   GET  /api/stream                  WebSocket of log lines and lifecycle events as JSON,
+//                                     ?server=<name>&level=warn,error&replay=<lines>
+//   GET  /metrics                     Prometheus text format
+//
+// Operators and up:
+//   POST /api/servers/<name>/start
+//   POST /api/servers/<name>/stop
+//   POST /api/servers/<name>/restart
+//   POST /api/servers/<name>/command  body {"command": "..."}
+//
+// Admins:
+//   POST   /api/servers               add a server, body as in `export`
+//   PUT    /api/servers/<name>        replace its definition
+//   DELETE /api/servers/<name>
+
+// State every request thread needs
+struct Shared {
+    control: Sender<ControlRequest>,
+    db: Mutex<Connection>, // a connection of its own, the UI loop keeps the other one
+    limiter: Mutex<RateLimiter>,
+    host: Mutex<HostSampler>, // shared so rates are worked out between requests from any client
+    streams: AtomicUsize,     // WebSockets open right now
+}
+
+// Requests are answered by a fixed set of threads, so a flood of them queues instead of
+// starting a thread each. WebSockets get a thread of their own once upgraded, up to a limit.
+const WORKERS: usize = 8;
+const MAX_STREAMS: usize = 64;
+// Server definitions are the largest bodies the API takes, and nowhere near this
+const MAX_BODY: u64 = 1024 * 1024;
+
+pub fn spawn(address: &str, control: Sender<ControlRequest>, db: Connection) -> io::Result<()> {
+    let server = HttpServer::http(address).map_err(|e| io::Error::other(e.to_string()))?;
+    let shared = Arc::new(Shared {
+        control,
+        db: Mutex::new(db),
+        limiter: Mutex::new(RateLimiter::default()),
+        host: Mutex::new(HostSampler::new()),
+        streams: AtomicUsize::new(0),
+    });
+    let server = Arc::new(server);
+    for _ in 0..WORKERS {
+        let (server, shared) = (server.clone(), shared.clone());
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                // A panic is reported by the hook, the worker carries on with the next request
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| handle(request, &shared)));
+            }
+        });
+    }
+    Ok(())
+}
+
+// A panicked request thread must not take the API down with it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn handle(mut request: Request, shared: &Arc<Shared>) {
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (request.url().to_string(), String::new()),
+    };
+    // The dashboard itself is public, it asks for a login when the API says so
+    if *request.method() == Method::Get {
+        if let Some((content_type, body)) = web::asset(&path) {
+            let response = Response::from_string(body).with_header(header("Content-Type", content_type));
+            let _ = request.respond(response);
+            return;
+        }
+    }
+
+    if let Err(e) = check_origin(&request) {
+        return respond(request, Err(e));
+    }
+    if *request.method() != Method::Get && !is_json(&request) {
+        let error = ControlError::new(ErrorKind::Invalid, "expected Content-Type: application/json");
+        return respond(request, Err(error));
+    }
+
+    let segments: Vec<String> = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    if let (Method::Post, ["api", "login"]) = (request.method(), segments.as_slice()) {
+        return login(request, shared);
+    }
+    let identity = match authenticate(&request, shared) {
+        Ok(identity) => identity,
+        Err(e) => return respond(request, Err(e)),
+    };
+
+    let command = match (request.method(), segments.as_slice()) {
+        (Method::Post, ["api", "logout"]) => return logout(request, shared),
+        (Method::Get, ["api", "me"]) => {
+            let me = json!({ "user": identity.user, "role": identity.role.name(), "servers": identity.servers });
+            return respond(request, Ok(me));
+        }
+        (Method::Get, ["api", "stream"]) => return stream(request, shared, &identity, &query),
+        (Method::Get, ["api", "host"]) => {
+            let result = host_stats(shared, &identity);
+            return respond(request, result);
+        }
+        (Method::Get, ["metrics"]) => {
+            return match control::request(&shared.control, &identity, ControlCommand::Metrics) {
+                Ok(snapshot) => {
+                    let response = Response::from_string(metrics::render(&snapshot))
+                        .with_header(header("Content-Type", metrics::CONTENT_TYPE));
+                    let _ = request.respond(response);
+                }
+                Err(e) => respond(request, Err(e)),
+            };
+        }
+        (Method::Get, ["api", "servers"]) => ControlCommand::List,
+        (Method::Post, ["api", "servers"]) => match read_definition(&mut request) {
+            Ok(definition) => ControlCommand::Save { name: None, definition },
+            Err(e) => return respond(request, Err(e)),
+        },
+        (Method::Get, ["api", "servers", name]) => ControlCommand::Get(name.to_string()),
+        (Method::Put, ["api", "servers", name]) => match read_definition(&mut request) {
+            Ok(definition) => ControlCommand::Save {
+                name: Some(name.to_string()),
+                definition,
+            },
+            Err(e) => return respond(request, Err(e)),
+        },
+        (Method::Delete, ["api", "servers", name]) => ControlCommand::Delete(name.to_string()),
+        (Method::Post, ["api", "servers", name, "start"]) => ControlCommand::Start(name.to_string()),
+        (Method::Post, ["api", "servers", name, "stop"]) => ControlCommand::Stop(name.to_string()),
+        (Method::Post, ["api", "servers", name, "restart"]) => {
+            ControlCommand::Restart(name.to_string())
+        }
+        (Method::Post, ["api", "servers", name, "command"]) => match read_command(&mut request) {
+            Ok(command) => ControlCommand::SendCommand {
+                name: name.to_string(),
+                command,
+            },
+            Err(e) => return respond(request, Err(e)),
+        },
+        _ => {
+            let error = ControlError::new(ErrorKind::NotFound, format!("no route for {}", path));
+            return respond(request, Err(error));
+        }
+    };
+
+    let result = control::request(&shared.control, &identity, command);
+    respond(request, result);
+}
+
+// Dashboard logins last a week, API tokens until they are revoked
+const SESSION_LENGTH: chrono::Duration = chrono::Duration::days(7);
+const SESSION_COOKIE: &str = "session";
+
+fn client_address(request: &Request) -> IpAddr {
+    request
+        .remote_addr()
+        .map_or(IpAddr::from([0, 0, 0, 0]), |address| address.ip())
+}
+
+fn header_value(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.to_string())
+}
+
+// Any page the user has open can make the browser send requests here. One from another site
+// says so in Origin, one that got here through DNS rebinding has a name of its own in Host.
+fn check_origin(request: &Request) -> Result<(), ControlError> {
+    let Some(host) = header_value(request, "Host") else {
+        return Ok(()); // not a browser, they always send it
+    };
+    if !trusted_host(&host) {
+        return Err(ControlError::new(ErrorKind::Forbidden, format!("unknown host `{}`", host)));
+    }
+    match header_value(request, "Origin") {
+        Some(origin) if origin != format!("http://{}", host) => Err(ControlError::new(
+            ErrorKind::Forbidden,
+            "requests from other sites are not allowed",
+        )),
+        _ => Ok(()),
+    }
+}
+
+// "[::1]:8127" is ::1, "localhost:8127" is localhost
+fn host_name(host: &str) -> &str {
+    match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    }
+}
+
+// An address can't be rebound to someone else's server, a name other than this machine's can
+fn trusted_host(host: &str) -> bool {
+    let name = host_name(host);
+    if name.parse::<IpAddr>().is_ok() || name.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    let own = fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+    let own = own.trim();
+    !own.is_empty() && (name.eq_ignore_ascii_case(own) || name.eq_ignore_ascii_case(&format!("{}.local", own)))
+}
+
+fn loopback_host(request: &Request) -> bool {
+    let Some(host) = header_value(request, "Host") else {
+        return true;
+    };
+    let name = host_name(&host);
+    name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+fn is_json(request: &Request) -> bool {
+    header_value(request, "Content-Type").is_some_and(|value| {
+        let media_type = value.split(';').next().unwrap_or_default();
+        media_type.trim().eq_ignore_ascii_case("application/json")
+    })
+}
+
+// `Authorization: Bearer <token>` from scripts, the session cookie from the dashboard
+fn request_token(request: &Request) -> Option<String> {
+    if let Some(token) = header_value(request, "Authorization")
+        .and_then(|value| value.strip_prefix("Bearer ").map(|token| token.trim().to_string()))
+    {
+        return Some(token);
+    }
+    header_value(request, "Cookie")?
+        .split(';')
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, token)| token.to_string())
+}
+
+fn authenticate(request: &Request, shared: &Shared) -> Result<Identity, ControlError> {
+    let address = client_address(request);
+    if let Some(wait) = lock(&shared.limiter).blocked_for(address) {
+        return Err(rate_limited(wait));
+    }
+
+    let db = lock(&shared.db);
+    let Some(token) = request_token(request) else {
+        // Until the first user exists the API behaves as it did before there were users,
+        // but only for this machine, asked for by address and not through some other name
+        let has_users = db::has_users(&db).map_err(|e| ControlError::new(ErrorKind::Failed, e.to_string()))?;
+        if !has_users && address.is_loopback() && loopback_host(request) {
+            return Ok(Identity {
+                source: Source::Api("none".to_string()),
+                ..Identity::local()
+            });
+        }
+        return Err(ControlError::new(ErrorKind::Unauthorized, "log in or pass a token"));
+    };
+    match db::token_user(&db, &auth::hash_token(&token)) {
+        Ok(Some((user, label))) => Ok(user.identity(Source::Api(label))),
+        Ok(None) => {
+            lock(&shared.limiter).failed(address);
+            Err(ControlError::new(ErrorKind::Unauthorized, "invalid or expired token"))
+        }
+        Err(e) => Err(ControlError::new(ErrorKind::Failed, e.to_string())),
+    }
+}
+
+fn rate_limited(wait: Duration) -> ControlError {
+    ControlError::new(
+        ErrorKind::RateLimited,
+        format!("too many failed attempts, try again in {}s", wait.as_secs().max(1)),
+    )
+}
+
+// {"user": "...", "password": "..."}, answers with a session token and sets it as a cookie
+fn login(mut request: Request, shared: &Shared) {
+    let address = client_address(&request);
+    if let Some(wait) = lock(&shared.limiter).blocked_for(address) {
+        return respond(request, Err(rate_limited(wait)));
+    }
+    let credentials = match read_json(&mut request) {
+        Ok(credentials) => credentials,
+        Err(e) => return respond(request, Err(e)),
+    };
+    let name = credentials["user"].as_str().unwrap_or_default();
+    let password = credentials["password"].as_str().unwrap_or_default();
+
+    let db = lock(&shared.db);
+    let user = match db::find_user(&db, name) {
+        Ok(Some(user)) => Some(user).filter(|user| auth::verify_password(password, &user.password_hash)),
+        Ok(None) => {
+            auth::verify_password(password, auth::DUMMY_HASH);
+            None
+        }
+        Err(e) => return respond(request, Err(ControlError::new(ErrorKind::Failed, e.to_string()))),
+    };
+    let Some(user) = user else {
+        lock(&shared.limiter).failed(address);
+        let error = ControlError::new(ErrorKind::Unauthorized, "wrong user name or password");
+        return respond(request, Err(error));
+    };
+
+    let token = auth::new_token();
+    let expires_at = chrono::Local::now() + SESSION_LENGTH;
+    if let Err(e) = db::insert_token(&db, user.id, "dashboard login", &auth::hash_token(&token), Some(expires_at)) {
+        return respond(request, Err(ControlError::new(ErrorKind::Failed, e.to_string())));
+    }
+    drop(db);
+
+    let cookie = format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+        SESSION_COOKIE,
+        token,
+        SESSION_LENGTH.num_seconds()
+    );
+    let body = json!({ "token": token, "user": user.name, "role": user.role.name() });
+    respond_with(request, Ok(body), vec![header("Set-Cookie", &cookie)]);
+}
+
+fn logout(request: Request, shared: &Shared) {
+    if let Some(token) = request_token(&request) {
+        let _ = db::delete_token_hash(&lock(&shared.db), &auth::hash_token(&token));
+    }
+    let cookie = format!("{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0", SESSION_COOKIE);
+    respond_with(request, Ok(json!({ "logged_out": true })), vec![header("Set-Cookie", &cookie)]);
+}
+
+fn read_json(request: &mut Request) -> Result<Value, ControlError> {
+    let too_large = || ControlError::new(ErrorKind::TooLarge, format!("the body is over {} bytes", MAX_BODY));
+    if request.body_length().is_some_and(|length| length as u64 > MAX_BODY) {
+        return Err(too_large());
+    }
+    // One byte past the limit is enough to tell that a body without a length is too large
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| ControlError::new(ErrorKind::Invalid, e.to_string()))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(too_large());
+    }
+    serde_json::from_str(&body)
+        .map_err(|e| ControlError::new(ErrorKind::Invalid, format!("expected JSON: {}", e)))
+}
+
+// Same shape as `export` writes
+fn read_definition(request: &mut Request) -> Result<ServerDef, ControlError> {
+    serde_json::from_value(read_json(request)?)
+        .map_err(|e| ControlError::new(ErrorKind::Invalid, format!("not a server definition: {}", e)))
+}
+
+// {"command": "..."}
+fn read_command(request: &mut Request) -> Result<String, ControlError> {
+    let command = read_json(request)?["command"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| ControlError::new(ErrorKind::Invalid, "missing \"command\""))?;
+    if command.is_empty() {
+        return Err(ControlError::new(ErrorKind::Invalid, "empty command"));
+    }
+    Ok(command)
+}
+
+// Sampled at most once a second however many dashboards are open
+fn host_stats(shared: &Shared, identity: &Identity) -> Result<Value, ControlError> {
+    identity
+        .check(Permission::View, None)
+        .map_err(|e| ControlError::new(ErrorKind::Forbidden, e))?;
+    let mut sampler = lock(&shared.host);
+    if sampler
+        .last_sample()
+        .is_none_or(|last| last.elapsed() >= Duration::from_secs(1))
+    {
+        // Disks are reported for the mounts the servers live on
+        let servers = control::request(&shared.control, identity, ControlCommand::List)?;
+        let paths: Vec<(String, String)> = servers
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|server| server["definition"]["test_server"] != true)
+            .filter_map(|server| {
+                Some((
+                    server["name"].as_str()?.to_string(),
+                    server["definition"]["path"].as_str()?.to_string(),
+                ))
+            })
+            .collect();
+        sampler.sample(&paths);
+    }
+    Ok(sampler.to_json())
+}
+
+fn stream_filter(query: &str) -> Result<StreamFilter, ControlError> {
+    let mut filter = StreamFilter::default();
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        let value = percent_decode(&value.replace('+', " "));
+        match key {
+            "server" => filter.servers.extend(value.split(',').map(str::to_string)),
+            "level" => {
+                for label in value.split(',') {
+                    let level = LogLevel::parse(label).ok_or_else(|| {
+                        ControlError::new(ErrorKind::Invalid, format!("unknown level `{}`", label))
+                    })?;
+                    filter.levels.push(level);
+                }
+            }
+            "replay" => {
+                filter.replay = value
+                    .parse()
+                    .map_err(|_| ControlError::new(ErrorKind::Invalid, "replay takes a line count"))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(filter)
+}
+
+// Sent on a quiet stream so a client that went away is noticed
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+// How long a stream waits for the client before sending on, and for it to take what is sent
+#[cfg(unix)]
+const STREAM_READ_TIMEOUT: Duration = Duration::from_millis(100);
+#[cfg(unix)]
+const STREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The Sec-WebSocket-Key of a well formed upgrade request, RFC 6455 section 4.2.1
+fn handshake_key(request: &Request) -> Result<String, ControlError> {
+    let has_token = |name, token: &str| {
+        header_value(request, name)
+            .is_some_and(|value| value.split(',').any(|part| part.trim().eq_ignore_ascii_case(token)))
+    };
+    if !has_token("Upgrade", "websocket") || !has_token("Connection", "upgrade") {
+        return Err(ControlError::new(ErrorKind::Invalid, "expected a WebSocket upgrade"));
+    }
+    if header_value(request, "Sec-WebSocket-Version").as_deref().map(str::trim) != Some("13") {
+        return Err(ControlError::new(ErrorKind::Invalid, "only WebSocket version 13 is supported"));
+    }
+    header_value(request, "Sec-WebSocket-Key")
+        .ok_or_else(|| ControlError::new(ErrorKind::Invalid, "the WebSocket upgrade has no key"))
+}
+
+// Upgrades to a WebSocket and forwards events until either side goes away. The client is
+// only read from for pings and closes, anything else it sends is ignored.
+fn stream(request: Request, shared: &Arc<Shared>, identity: &Identity, query: &str) {
+    let filter = match stream_filter(query) {
+        Ok(filter) => filter,
+        Err(e) => return respond(request, Err(e)),
+    };
+    let key = match handshake_key(&request) {
+        Ok(key) => key,
+        // Tells a client asking for another version which one to use
+        Err(e) => return respond_with(request, Err(e), vec![header("Sec-WebSocket-Version", "13")]),
+    };
+
+    // Counted before subscribing, the slot is given back when the stream's thread ends
+    if shared.streams.fetch_add(1, Ordering::Relaxed) >= MAX_STREAMS {
+        shared.streams.fetch_sub(1, Ordering::Relaxed);
+        let error = ControlError::new(ErrorKind::RateLimited, "too many streams are open");
+        return respond(request, Err(error));
+    }
+    let (events, receiver) = channel();
+    if let Err(e) = control::request(&shared.control, identity, ControlCommand::Subscribe { filter, events }) {
+        shared.streams.fetch_sub(1, Ordering::Relaxed);
+        return respond(request, Err(e));
+    }
+
+    let response = Response::empty(StatusCode(101))
+        .with_header(header("Upgrade", "websocket"))
+        .with_header(header("Connection", "Upgrade"))
+        .with_header(header("Sec-WebSocket-Accept", &derive_accept_key(key.as_bytes())));
+    let client = request.remote_addr().copied();
+    let socket = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(socket, Role::Server, None);
+    // Without timeouts a read would wait for the client and hold up the events
+    let readable = client.is_some_and(|client| set_timeouts(client).is_ok());
+
+    // Streams last as long as the client wants, they would hold a worker the whole time
+    let shared = shared.clone();
+    thread::spawn(move || {
+        forward(&mut socket, &receiver, readable);
+        shared.streams.fetch_sub(1, Ordering::Relaxed);
+    });
+}
+
+// tiny_http keeps the upgraded connection's socket to itself. It is found among our fds by the
+// client's address, the timeouts set through it apply to tiny_http's copies too.
+#[cfg(unix)]
+fn set_timeouts(client: SocketAddr) -> io::Result<()> {
+    for entry in fs::read_dir("/dev/fd")? {
+        let Some(fd) = entry?.file_name().to_str().and_then(|name| name.parse::<RawFd>().ok()) else {
+            continue;
+        };
+        // Only borrowed, closing it is still up to tiny_http
+        let socket = ManuallyDrop::new(unsafe { TcpStream::from_raw_fd(fd) });
+        if socket.peer_addr().is_ok_and(|peer| peer == client) {
+            socket.set_read_timeout(Some(STREAM_READ_TIMEOUT))?;
+            return socket.set_write_timeout(Some(STREAM_WRITE_TIMEOUT));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, "the stream's socket is gone"))
+}
+
+#[cfg(not(unix))]
+fn set_timeouts(_client: SocketAddr) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the stream's socket can't be found"))
+}
+
+// Without `readable` the client is never read from, a read could block for good
+fn forward(socket: &mut WebSocket<Box<dyn ReadWrite + Send>>, receiver: &Receiver<StreamEvent>, readable: bool) {
+    let mut last_sent = Instant::now();
+    loop {
+        if readable {
+            // Pings are answered and closes confirmed by tungstenite on the next flush
+            match socket.read() {
+                Ok(Message::Close(_)) => {
+                    let _ = socket.flush();
+                    return;
+                }
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                Err(_) => return,
+            }
+        }
+        // The read timeout sets the pace, otherwise the next event does
+        let mut wait = if readable { Duration::ZERO } else { PING_INTERVAL };
+        loop {
+            match receiver.recv_timeout(wait) {
+                Ok(event) => {
+                    if socket.write(Message::Text(event.to_json().to_string())).is_err() {
+                        // Dropping the receiver unsubscribes on the next broadcast
+                        return;
+                    }
+                    last_sent = Instant::now();
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                    return;
+                }
+            }
+            wait = Duration::ZERO;
+        }
+        if last_sent.elapsed() >= PING_INTERVAL {
+            if socket.write(Message::Ping(Vec::new())).is_err() {
+                return;
+            }
+            last_sent = Instant::now();
+        }
+        if socket.flush().is_err() {
+            return;
+        }
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field, value).expect("header is valid ascii")
+}
+
+fn status_code(kind: ErrorKind) -> u16 {
+    match kind {
+        ErrorKind::NotFound => 404,
+        ErrorKind::Conflict => 409,
+        ErrorKind::Invalid => 400,
+        ErrorKind::Unauthorized => 401,
+        ErrorKind::Forbidden => 403,
+        ErrorKind::RateLimited => 429,
+        ErrorKind::TooLarge => 413,
+        ErrorKind::Failed => 500,
+    }
+}
+
+fn respond(request: Request, result: Result<Value, ControlError>) {
+    respond_with(request, result, Vec::new());
+}
+
+fn respond_with(request: Request, result: Result<Value, ControlError>, headers: Vec<Header>) {
+    let (status, body) = match result {
+        Ok(value) => (200, value),
+        Err(e) => (status_code(e.kind), json!({ "error": e.message })),
+    };
+    let mut response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"));
+    for header in headers {
+        response.add_header(header);
+    }
+    let _ = request.respond(response);
+}
+
+// Server names have spaces, so path segments arrive %-encoded. `+` is only a space in a query.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("Minecraft%20Server"), "Minecraft Server");
+        // A path keeps its `+`, only stream_filter reads it as a space
+        assert_eq!(percent_decode("C++%20Server"), "C++ Server");
+        assert_eq!(percent_decode("caf%C3%A9"), "café");
+        // Anything that isn't a valid escape stays as it is
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%2"), "%zz%2");
+    }
+
+    #[test]
+    fn stream_filters_from_the_query() {
+        let filter = stream_filter("server=Minecraft+Server,C%2B%2B&level=warn,error&replay=10").unwrap();
+        assert_eq!(filter.servers, ["Minecraft Server", "C++"]);
+        assert_eq!(filter.levels, [LogLevel::Warn, LogLevel::Error]);
+        assert_eq!(filter.replay, 10);
+        assert!(stream_filter("level=loud").is_err());
+        assert!(stream_filter("replay=all").is_err());
+    }
+
+    #[test]
+    fn host_names() {
+        assert_eq!(host_name("localhost:8127"), "localhost");
+        assert_eq!(host_name("[::1]:8127"), "::1");
+        assert_eq!(host_name("192.168.1.5"), "192.168.1.5");
+        assert!(trusted_host("127.0.0.1:8127"));
+        assert!(trusted_host("[::1]:8127"));
+        assert!(trusted_host("LocalHost:8127"));
+        assert!(trusted_host("192.168.1.5"));
+        assert!(!trusted_host("attacker.example:8127"));
+    }
+
+    #[test]
+    fn bodies_over_the_limit_are_refused() {
+        let mut request: Request = tiny_http::TestRequest::new().with_body(r#"{"command": "say hi"}"#).into();
+        assert_eq!(read_command(&mut request).unwrap(), "say hi");
+
+        let huge = format!(r#"{{"command": "{}"}}"#, "a".repeat(MAX_BODY as usize));
+        let mut request: Request = tiny_http::TestRequest::new().with_body(huge.leak()).into();
+        let error = read_command(&mut request).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::TooLarge);
+        assert_eq!(status_code(error.kind), 413);
+    }
+
+    fn upgrade(headers: &[(&str, &str)]) -> Request {
+        headers
+            .iter()
+            .fold(tiny_http::TestRequest::new(), |request, (field, value)| request.with_header(header(field, value)))
+            .into()
+    }
+
+    #[test]
+    fn websocket_handshakes_are_checked() {
+        let good = [
+            ("Upgrade", "websocket"),
+            ("Connection", "keep-alive, Upgrade"),
+            ("Sec-WebSocket-Version", "13"),
+            ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+        ];
+        assert_eq!(handshake_key(&upgrade(&good)).unwrap(), "dGhlIHNhbXBsZSBub25jZQ==");
+        assert!(handshake_key(&upgrade(&good[1..])).is_err());
+        assert!(handshake_key(&upgrade(&[good[0], ("Connection", "keep-alive"), good[2], good[3]])).is_err());
+        assert!(handshake_key(&upgrade(&[good[0], good[1], ("Sec-WebSocket-Version", "8"), good[3]])).is_err());
+        assert!(handshake_key(&upgrade(&good[..3])).is_err());
+    }
+}
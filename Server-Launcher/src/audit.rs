@@ -0,0 +1,145 @@
+use chrono::Local;
+use rusqlite::Connection;
+
+use crate::auth::Identity;
+use crate::control::{ControlError, ErrorKind};
+use crate::db::{self, AuditEntry};
+
+// Who did what, from where, and how it went. Rows go to the append-only `audit` table.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditAction {
+    Launch,
+    Kill,
+    Restart,
+    Command, // a line typed into a server's console
+    AddServer,
+    EditServer,
+    DeleteServer,
+    ImportServers,
+    AddUser,
+    EditUser,
+    RemoveUser,
+    CreateToken,
+    RevokeToken,
+    ExportLogs,
+}
+
+impl AuditAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            AuditAction::Launch => "launch",
+            AuditAction::Kill => "kill",
+            AuditAction::Restart => "restart",
+            AuditAction::Command => "command",
+            AuditAction::AddServer => "add server",
+            AuditAction::EditServer => "edit server",
+            AuditAction::DeleteServer => "delete server",
+            AuditAction::ImportServers => "import servers",
+            AuditAction::AddUser => "add user",
+            AuditAction::EditUser => "edit user",
+            AuditAction::RemoveUser => "remove user",
+            AuditAction::CreateToken => "create token",
+            AuditAction::RevokeToken => "revoke token",
+            AuditAction::ExportLogs => "export logs",
+        }
+    }
+}
+
+pub enum Outcome {
+    Ok,
+    Denied(String), // the caller was not allowed to
+    Failed(String),
+}
+
+impl Outcome {
+    pub fn of<T>(result: &Result<T, ControlError>) -> Outcome {
+        match result {
+            Ok(_) => Outcome::Ok,
+            Err(e) if matches!(e.kind, ErrorKind::Forbidden | ErrorKind::Unauthorized) => {
+                Outcome::Denied(e.message.clone())
+            }
+            Err(e) => Outcome::Failed(e.message.clone()),
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Denied(_) => "denied",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+// `target` is the server, user or token acted on, `detail` anything worth keeping besides
+pub fn record(
+    conn: &Connection,
+    who: &Identity,
+    action: AuditAction,
+    target: &str,
+    detail: &str,
+    outcome: Outcome,
+) -> rusqlite::Result<()> {
+    let message = match &outcome {
+        Outcome::Ok => String::new(),
+        Outcome::Denied(message) | Outcome::Failed(message) => message.clone(),
+    };
+    db::insert_audit(
+        conn,
+        &AuditEntry {
+            at: Local::now(),
+            user: who.user.clone(),
+            source: who.source.label(),
+            action: action.label().to_string(),
+            target: target.to_string(),
+            detail: detail.to_string(),
+            outcome: outcome.label().to_string(),
+            message,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Source;
+
+    #[test]
+    fn entries_are_recorded_newest_first() {
+        let conn = db::connect_db(":memory:").unwrap();
+        let who = Identity::local();
+        record(&conn, &who, AuditAction::Launch, "Web", "", Outcome::Ok).unwrap();
+        let result: Result<(), ControlError> =
+            Err(ControlError::new(ErrorKind::Forbidden, "operators can't edit"));
+        record(&conn, &who, AuditAction::EditServer, "Web", "", Outcome::of(&result)).unwrap();
+        record(&conn, &who, AuditAction::Kill, "Minecraft", "", Outcome::Ok).unwrap();
+
+        let entries = db::load_audit(&conn, None, Some("web"), 10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "edit server");
+        assert_eq!(entries[0].outcome, "denied");
+        assert_eq!(entries[0].message, "operators can't edit");
+        assert_eq!(entries[1].action, "launch");
+        assert_eq!(entries[1].outcome, "ok");
+        assert_eq!(entries[1].source, Source::Tui.label());
+        assert_eq!(db::load_audit(&conn, None, None, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rows_cannot_be_changed_or_removed() {
+        let conn = db::connect_db(":memory:").unwrap();
+        record(&conn, &Identity::local(), AuditAction::Restart, "Web", "", Outcome::Ok).unwrap();
+        assert!(conn.execute("UPDATE audit SET outcome = 'failed'", []).is_err());
+        assert!(conn.execute("DELETE FROM audit", []).is_err());
+        assert_eq!(db::load_audit(&conn, None, None, 10).unwrap()[0].outcome, "ok");
+    }
+
+    #[test]
+    fn failures_that_are_not_permissions_count_as_failed() {
+        let result: Result<(), ControlError> =
+            Err(ControlError::new(ErrorKind::NotFound, "no server named `Web`"));
+        assert!(matches!(Outcome::of(&result), Outcome::Failed(_)));
+        assert!(matches!(Outcome::of(&Ok::<(), ControlError>(())), Outcome::Ok));
+    }
+}
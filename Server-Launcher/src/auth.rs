@@ -0,0 +1,323 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+// Who may do what through the remote control surfaces (the HTTP API and everything on it)
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Viewer,   // status and logs
+    Operator, // start, stop and console on allowed servers
+    Admin,    // everything, including server definitions
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::Operator, Role::Admin];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Role> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Permission {
+    View,
+    Control, // launch, kill, restart, console input
+    Edit,    // add, change or delete servers
+}
+
+impl Permission {
+    fn required_role(self) -> Role {
+        match self {
+            Permission::View => Role::Viewer,
+            Permission::Control => Role::Operator,
+            Permission::Edit => Role::Admin,
+        }
+    }
+}
+
+// Where a request came in, recorded in the audit log
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Tui,
+    Cli,
+    Api(String), // label of the token used
+    Socket,
+    Ssh(String), // key comment, or its fingerprint
+    Trigger,
+}
+
+impl Source {
+    pub fn label(&self) -> String {
+        match self {
+            Source::Tui => "tui".to_string(),
+            Source::Cli => "cli".to_string(),
+            Source::Api(token) => format!("api ({})", token),
+            Source::Socket => "socket".to_string(),
+            Source::Ssh(key) => format!("ssh ({})", key),
+            Source::Trigger => "trigger".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub user: String,
+    pub role: Role,
+    pub servers: Vec<String>, // servers an operator may control, empty for all
+    pub source: Source,
+}
+
+impl Identity {
+    // Whoever sits at the TUI owns the machine anyway
+    pub fn local() -> Identity {
+        Identity {
+            user: "local".to_string(),
+            role: Role::Admin,
+            servers: Vec::new(),
+            source: Source::Tui,
+        }
+    }
+
+    // Same goes for the command line, it is recorded under the login name
+    pub fn cli() -> Identity {
+        Identity {
+            user: std::env::var("USER").unwrap_or_else(|_| "local".to_string()),
+            source: Source::Cli,
+            ..Identity::local()
+        }
+    }
+
+    // The socket is only open to the launcher's own user
+    pub fn socket() -> Identity {
+        Identity {
+            source: Source::Socket,
+            ..Identity::cli()
+        }
+    }
+
+    // Actions a server's own triggers take
+    pub fn trigger() -> Identity {
+        Identity {
+            user: "trigger".to_string(),
+            source: Source::Trigger,
+            ..Identity::local()
+        }
+    }
+
+    // `server` is the one being acted on, if any
+    pub fn check(&self, permission: Permission, server: Option<&str>) -> Result<(), String> {
+        if self.role < permission.required_role() {
+            return Err(format!(
+                "{} is a {} and may not do that",
+                self.user,
+                self.role.name()
+            ));
+        }
+        // Admins are not limited by the allow-list
+        let allowed = match server {
+            Some(server) if permission == Permission::Control && self.role != Role::Admin => {
+                self.servers.is_empty() || self.servers.iter().any(|name| name == server)
+            }
+            _ => true,
+        };
+        if !allowed {
+            return Err(format!("{} may not control {}", self.user, server.unwrap_or_default()));
+        }
+        Ok(())
+    }
+}
+
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let mut salt = [0u8; 16];
+    rand::rng().fill_bytes(&mut salt);
+    let salt = SaltString::encode_b64(&salt).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+// Checked against when the user doesn't exist, so an unknown name takes as long as a wrong password
+pub const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$nbFK95DNrXrzD+FAYp3rnw$VQsK+M4uImf3UHbnTrFIzwch+DQjCzY9b22TzQkIGxQ";
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+// Tokens are random enough that a plain sha256 is all the db needs to keep
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 24];
+    rand::rng().fill_bytes(&mut bytes);
+    format!("sl_{}", hex(&bytes))
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Failed logins and bad tokens allowed per address before it has to wait. Nothing clears them
+// early, or a valid token or one's own password could be used to reset the count between guesses.
+const MAX_FAILURES: usize = 5;
+const FAILURE_WINDOW: Duration = Duration::from_secs(300);
+
+#[derive(Default)]
+pub struct RateLimiter {
+    failures: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    // How long the address has to wait, None when it may try again
+    pub fn blocked_for(&mut self, address: IpAddr) -> Option<Duration> {
+        let failures = self.failures.get_mut(&address)?;
+        while failures.front().is_some_and(|at| at.elapsed() >= FAILURE_WINDOW) {
+            failures.pop_front();
+        }
+        if failures.len() < MAX_FAILURES {
+            return None;
+        }
+        failures
+            .front()
+            .map(|oldest| FAILURE_WINDOW.saturating_sub(oldest.elapsed()))
+    }
+
+    pub fn failed(&mut self, address: IpAddr) {
+        // Addresses that stopped trying are forgotten, or every one ever seen would be kept
+        self.failures.retain(|_, failures| {
+            while failures.front().is_some_and(|at| at.elapsed() >= FAILURE_WINDOW) {
+                failures.pop_front();
+            }
+            !failures.is_empty()
+        });
+        self.failures.entry(address).or_default().push_back(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn blocks_after_too_many_failures() {
+        let mut limiter = RateLimiter::default();
+        let guesser = address("192.168.1.20");
+        for _ in 0..MAX_FAILURES - 1 {
+            limiter.failed(guesser);
+            assert_eq!(limiter.blocked_for(guesser), None);
+        }
+        limiter.failed(guesser);
+        let wait = limiter.blocked_for(guesser).unwrap();
+        assert!(wait > Duration::ZERO && wait <= FAILURE_WINDOW);
+        // Other addresses are counted on their own
+        assert_eq!(limiter.blocked_for(address("192.168.1.21")), None);
+    }
+
+    #[test]
+    fn failures_expire_after_the_window() {
+        let mut limiter = RateLimiter::default();
+        let guesser = address("::1");
+        // Only possible once the machine has been up for longer than the window
+        let Some(expired) = Instant::now().checked_sub(FAILURE_WINDOW) else {
+            return;
+        };
+        limiter.failures.insert(guesser, VecDeque::from(vec![expired; MAX_FAILURES]));
+        assert_eq!(limiter.blocked_for(guesser), None);
+        limiter.failed(guesser);
+        assert_eq!(limiter.failures[&guesser].len(), 1);
+    }
+
+    #[test]
+    fn addresses_that_stopped_trying_are_forgotten() {
+        let mut limiter = RateLimiter::default();
+        let Some(expired) = Instant::now().checked_sub(FAILURE_WINDOW) else {
+            return;
+        };
+        limiter.failures.insert(address("10.0.0.1"), VecDeque::from(vec![expired]));
+        limiter.failed(address("10.0.0.2"));
+        assert_eq!(limiter.failures.keys().collect::<Vec<_>>(), [&address("10.0.0.2")]);
+    }
+
+    #[test]
+    fn the_dummy_hash_is_a_real_one() {
+        assert!(PasswordHash::new(DUMMY_HASH).is_ok());
+        assert!(!verify_password("", DUMMY_HASH));
+    }
+
+    fn operator(servers: &[&str]) -> Identity {
+        Identity {
+            user: "sam".to_string(),
+            role: Role::Operator,
+            servers: servers.iter().map(|name| name.to_string()).collect(),
+            source: Source::Api("test".to_string()),
+        }
+    }
+
+    #[test]
+    fn permissions_follow_the_role() {
+        let viewer = Identity { role: Role::Viewer, ..operator(&[]) };
+        assert!(viewer.check(Permission::View, Some("Web")).is_ok());
+        assert!(viewer.check(Permission::Control, Some("Web")).is_err());
+        assert!(operator(&[]).check(Permission::Control, Some("Web")).is_ok());
+        assert!(operator(&[]).check(Permission::Edit, Some("Web")).is_err());
+        assert!(Identity::local().check(Permission::Edit, None).is_ok());
+    }
+
+    #[test]
+    fn operators_only_control_their_servers() {
+        let sam = operator(&["Web"]);
+        assert!(sam.check(Permission::Control, Some("Web")).is_ok());
+        assert!(sam.check(Permission::Control, Some("Minecraft Server")).is_err());
+        // Viewing isn't limited by the list
+        assert!(sam.check(Permission::View, Some("Minecraft Server")).is_ok());
+        let admin = Identity { role: Role::Admin, ..sam };
+        assert!(admin.check(Permission::Control, Some("Minecraft Server")).is_ok());
+    }
+
+    #[test]
+    fn passwords_and_tokens() {
+        let hash = hash_password("hunter2").unwrap();
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        assert!(!verify_password("hunter2", "not a hash"));
+
+        let token = new_token();
+        assert!(token.starts_with("sl_") && token.len() == 3 + 48);
+        assert_ne!(token, new_token());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+    }
+
+    #[test]
+    fn roles_parse_back() {
+        for role in Role::ALL {
+            assert_eq!(Role::parse(role.name()), Some(role));
+        }
+        assert_eq!(Role::parse(" Admin "), Some(Role::Admin));
+        assert_eq!(Role::parse("root"), None);
+    }
+}
@@ -0,0 +1,189 @@
+use regex::Regex;
+
+// Severity or kind of a single output line, decided by the server's rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Chat,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Chat,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Chat => "CHAT",
+        }
+    }
+
+    pub fn parse(label: &str) -> Option<LogLevel> {
+        LogLevel::ALL
+            .into_iter()
+            .find(|level| level.label().eq_ignore_ascii_case(label.trim()))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LogRule {
+    pub pattern: Regex,
+    pub level: LogLevel,
+}
+
+impl LogRule {
+    pub fn new(pattern: &str, level: LogLevel) -> Result<LogRule, regex::Error> {
+        Ok(LogRule {
+            pattern: Regex::new(pattern)?,
+            level,
+        })
+    }
+}
+
+// Built in rule sets for output formats we run often
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogPreset {
+    Plain, // no built in rules, only the server's own
+    Minecraft,
+    Srcds,
+}
+
+impl LogPreset {
+    pub const ALL: [LogPreset; 3] = [LogPreset::Plain, LogPreset::Minecraft, LogPreset::Srcds];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogPreset::Plain => "plain",
+            LogPreset::Minecraft => "minecraft",
+            LogPreset::Srcds => "srcds",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<LogPreset> {
+        LogPreset::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name.trim()))
+    }
+
+    // Ordered, the first matching rule wins
+    fn patterns(self) -> &'static [(&'static str, LogLevel)] {
+        match self {
+            LogPreset::Plain => &[],
+            // [12:34:56] [Server thread/WARN]: ... and Paper's [12:34:56 WARN]: ...
+            LogPreset::Minecraft => &[
+                (r"^\[\d{2}:\d{2}:\d{2}(\] \[[^\]]*/| )(ERROR|FATAL|SEVERE)\]", LogLevel::Error),
+                (r"^\[\d{2}:\d{2}:\d{2}(\] \[[^\]]*/| )WARN(ING)?\]", LogLevel::Warn),
+                (r"^\[\d{2}:\d{2}:\d{2}(\] \[[^\]]*/| )(DEBUG|TRACE)\]", LogLevel::Debug),
+                (r"^\[\d{2}:\d{2}:\d{2}(\] \[[^\]]*/| )INFO\]: (\[Not Secure\] )?<[^>]+> ", LogLevel::Chat),
+                (r"^\[\d{2}:\d{2}:\d{2}(\] \[[^\]]*/| )INFO\]", LogLevel::Info),
+            ],
+            // L 10/18/2026 - 12:34:56: "Player<2><STEAM_1:0:123><CT>" say "gg"
+            LogPreset::Srcds => &[
+                (r#""[^"]*<\d+><[^>]*><[^>]*>" say(_team)? ""#, LogLevel::Chat),
+                (r"(?i)\b(error|fatal|host_error|failed)\b", LogLevel::Error),
+                (r"(?i)\bwarn(ing)?\b", LogLevel::Warn),
+                (r"(?i)^\s*\[?debug\]?", LogLevel::Debug),
+                (r"^L \d{2}/\d{2}/\d{4} - \d{2}:\d{2}:\d{2}:", LogLevel::Info),
+            ],
+        }
+    }
+
+    pub fn rules(self) -> Vec<LogRule> {
+        self.patterns()
+            .iter()
+            .map(|(pattern, level)| LogRule::new(pattern, *level).expect("preset regex is valid"))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Classifier {
+    rules: Vec<LogRule>,
+}
+
+impl Classifier {
+    // The server's own rules are tried before the preset so they can override it
+    pub fn new(preset: LogPreset, custom_rules: &[LogRule]) -> Classifier {
+        let mut rules = custom_rules.to_vec();
+        rules.extend(preset.rules());
+        Classifier { rules }
+    }
+
+    pub fn classify(&self, line: &str) -> Option<LogLevel> {
+        self.rules
+            .iter()
+            .find(|rule| rule.pattern.is_match(line))
+            .map(|rule| rule.level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minecraft_preset() {
+        let classifier = Classifier::new(LogPreset::Minecraft, &[]);
+        let cases = [
+            ("[12:34:56] [Server thread/ERROR]: Crashed", Some(LogLevel::Error)),
+            ("[12:34:56] [Server thread/WARN]: Can't keep up!", Some(LogLevel::Warn)),
+            ("[12:34:56 WARN]: Paper style", Some(LogLevel::Warn)),
+            ("[12:34:56] [Server thread/INFO]: <Steve> hello", Some(LogLevel::Chat)),
+            ("[12:34:56] [Server thread/INFO]: [Not Secure] <Steve> hi", Some(LogLevel::Chat)),
+            ("[12:34:56] [Server thread/INFO]: Done (3.2s)!", Some(LogLevel::Info)),
+            ("[12:34:56] [Worker/DEBUG]: tick", Some(LogLevel::Debug)),
+            ("Loading libraries, please wait...", None),
+        ];
+        for (line, level) in cases {
+            assert_eq!(classifier.classify(line), level, "{}", line);
+        }
+    }
+
+    #[test]
+    fn srcds_preset() {
+        let classifier = Classifier::new(LogPreset::Srcds, &[]);
+        let chat = r#"L 10/18/2026 - 12:34:56: "Player<2><STEAM_1:0:123><CT>" say "gg""#;
+        assert_eq!(classifier.classify(chat), Some(LogLevel::Chat));
+        assert_eq!(classifier.classify("Host_Error: bad map"), Some(LogLevel::Error));
+        assert_eq!(classifier.classify("Warning: no map cycle"), Some(LogLevel::Warn));
+        assert_eq!(classifier.classify("L 10/18/2026 - 12:34:56: World triggered"), Some(LogLevel::Info));
+    }
+
+    #[test]
+    fn plain_preset_has_no_rules() {
+        let classifier = Classifier::new(LogPreset::Plain, &[]);
+        assert_eq!(classifier.classify("[12:34:56] [Server thread/ERROR]: x"), None);
+    }
+
+    #[test]
+    fn custom_rules_come_first() {
+        let rules = [LogRule::new("Can't keep up", LogLevel::Debug).unwrap()];
+        let classifier = Classifier::new(LogPreset::Minecraft, &rules);
+        let line = "[12:34:56] [Server thread/WARN]: Can't keep up!";
+        assert_eq!(classifier.classify(line), Some(LogLevel::Debug));
+    }
+
+    #[test]
+    fn labels_and_names_parse_back() {
+        for level in LogLevel::ALL {
+            assert_eq!(LogLevel::parse(level.label()), Some(level));
+        }
+        assert_eq!(LogLevel::parse(" warn "), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::parse("loud"), None);
+        for preset in LogPreset::ALL {
+            assert_eq!(LogPreset::parse(preset.name()), Some(preset));
+        }
+        assert_eq!(LogPreset::parse("Minecraft"), Some(LogPreset::Minecraft));
+    }
+}
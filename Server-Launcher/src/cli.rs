@@ -0,0 +1,1207 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+#[cfg(unix)]
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+#[cfg(unix)]
+use crossterm::terminal;
+use ratatui::style::Color;
+use rusqlite::Connection;
+use serde_json::json;
+
+use crate::audit::{self, AuditAction, Outcome};
+use crate::auth::{self, Identity, Role};
+use crate::classify::LogPreset;
+use crate::control::{ControlError, ErrorKind};
+use crate::db::{self, Server};
+use crate::detached;
+use crate::keymap::{Action, Keymap};
+use crate::logfile;
+use crate::serverdef::{self, ServerDef};
+use crate::settings::Settings;
+use crate::socket::Client;
+
+// Exit codes, scripts can tell failures apart without parsing the output
+const EXIT_OK: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NOT_FOUND: i32 = 3;
+const EXIT_STATE: i32 = 4; // already running, or not running
+
+const USAGE: &str = "Usage: Server-Launcher [command] [--json]
+
+Without a command the TUI is opened. While it is open, start, stop, restart and send go
+through its control socket and the servers run inside it.
+
+Commands:
+  list                          Configured servers
+  status [name]                 Whether servers are running, with pid and uptime
+  start <name>                  Start a server in the background
+  stop <name>                   Stop a server
+  restart <name>                Stop then start a server
+  send <name> <input>           Type a line into the console of a server the TUI runs
+  logs <name> [-f] [-n lines]   Print the server's log file, -f keeps following it
+  add --name N --path P --exec E [--args A] [--autostart] [--test] [--color #rrggbb] [--preset P]
+  edit <name> [same options as add, plus --no-autostart and --no-test]
+  remove <name>                 Delete a stopped server
+  import <file> [--replace]     Add servers from a JSON export
+  export [file]                 Write all servers as JSON, to stdout without a file
+  config [key] [value]          Show the settings, or change one
+  keys                          The key bindings in use and any problems with the keymap file
+  user list                     API users with their role and allowed servers
+  user add <user> --role R [--servers a,b]
+                                Roles are viewer, operator and admin. Without --servers an
+                                operator may control every server. The password is asked for,
+                                or read from stdin when that is not a terminal.
+  user edit <user> [--role R] [--servers a,b] [--new-password]
+  user remove <user>            Delete the user and their tokens
+  token create <user> [--label L]
+                                Print a new API token for the user, it is only shown once
+  token list
+  token revoke <id>
+  audit [-n count] [--user U] [--server S]
+                                Who started, stopped or changed what, newest first
+  tui                           Open the TUI
+  attach                        Show the open launcher's TUI in this terminal, next to its own
+
+Exit codes: 0 ok, 1 error, 2 bad usage, 3 no such server, user or token, 4 server in the wrong state";
+
+// Options that take a value, everything else starting with - is a flag
+const VALUE_OPTIONS: [&str; 13] = [
+    "--name", "--path", "--exec", "--args", "--color", "--preset", "-n", "--lines", "--role",
+    "--servers", "--label", "--user", "--server",
+];
+
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: HashSet<String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, Failure> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: HashMap::new(),
+            flags: HashSet::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if VALUE_OPTIONS.contains(&arg.as_str()) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| Failure::usage(format!("{} needs a value", arg)))?;
+                parsed.options.insert(arg.clone(), value.clone());
+            } else if arg.starts_with('-') && arg.len() > 1 {
+                parsed.flags.insert(arg.clone());
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, names: &[&str]) -> bool {
+        names.iter().any(|name| self.flags.contains(*name))
+    }
+
+    fn option(&self, names: &[&str]) -> Option<&str> {
+        names
+            .iter()
+            .find_map(|name| self.options.get(*name))
+            .map(String::as_str)
+    }
+
+    // The positional argument after the command
+    fn name(&self) -> Result<&str, Failure> {
+        self.positional
+            .get(1)
+            .map(String::as_str)
+            .ok_or_else(|| Failure::usage(format!("{} needs a server name", self.positional[0])))
+    }
+}
+
+struct Failure {
+    code: i32,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: i32, message: impl Into<String>) -> Failure {
+        Failure {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn usage(message: impl Into<String>) -> Failure {
+        Failure::new(EXIT_USAGE, message)
+    }
+}
+
+impl From<rusqlite::Error> for Failure {
+    fn from(e: rusqlite::Error) -> Failure {
+        Failure::new(EXIT_ERROR, format!("database error: {}", e))
+    }
+}
+
+impl From<ControlError> for Failure {
+    fn from(e: ControlError) -> Failure {
+        let code = match e.kind {
+            ErrorKind::NotFound => EXIT_NOT_FOUND,
+            ErrorKind::Conflict => EXIT_STATE,
+            _ => EXIT_ERROR,
+        };
+        Failure::new(code, e.message)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Failure {
+        Failure::new(EXIT_ERROR, e.to_string())
+    }
+}
+
+// Runs one command line invocation and returns the process exit code
+pub fn run(conn: &Connection, args: &[String]) -> i32 {
+    let result = Args::parse(args).and_then(|args| {
+        let json = args.flag(&["--json"]);
+        let result = match args.positional.first().map(String::as_str) {
+            None if args.flag(&["-h", "--help"]) => {
+                println!("{}", USAGE);
+                Ok(())
+            }
+            Some("help") => {
+                println!("{}", USAGE);
+                Ok(())
+            }
+            Some("list") => list(conn, json),
+            Some("status") => status(conn, &args, json),
+            Some("start") => start(conn, args.name()?, json),
+            Some("stop") => stop(conn, args.name()?, json),
+            Some("restart") => restart(conn, args.name()?, json),
+            Some("send") => send(conn, &args, json),
+            Some("logs") => logs(conn, &args, json),
+            Some("add") => add(conn, &args, json),
+            Some("edit") => edit(conn, &args, json),
+            Some("remove") => remove(conn, args.name()?, json),
+            Some("import") => import(conn, &args, json),
+            Some("export") => export(conn, &args),
+            Some("config") => config(conn, &args, json),
+            Some("keys") => keys(conn, json),
+            Some("user") => user(conn, &args, json),
+            Some("token") => token(conn, &args, json),
+            Some("audit") => audit_log(conn, &args, json),
+            Some("attach") => attach(),
+            Some(other) => Err(Failure::usage(format!("unknown command `{}`", other))),
+            None => Err(Failure::usage("no command given")),
+        };
+        record(conn, &args, &result);
+        result
+    });
+
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(failure) => {
+            eprintln!("error: {}", failure.message);
+            if failure.code == EXIT_USAGE {
+                eprintln!("\n{}", USAGE);
+            }
+            failure.code
+        }
+    }
+}
+
+// The action a command line takes and what it acts on, None for the ones that only read
+fn audited(args: &Args) -> Option<(AuditAction, String)> {
+    let word = |index: usize| args.positional.get(index).cloned().unwrap_or_default();
+    let action = match (word(0).as_str(), word(1).as_str()) {
+        ("start", _) => AuditAction::Launch,
+        ("stop", _) => AuditAction::Kill,
+        ("restart", _) => AuditAction::Restart,
+        ("send", _) => return Some((AuditAction::Command, word(1))),
+        ("add", _) => return Some((AuditAction::AddServer, args.option(&["--name"]).unwrap_or_default().trim().to_string())),
+        ("edit", _) => AuditAction::EditServer,
+        ("remove", _) => AuditAction::DeleteServer,
+        ("import", _) => AuditAction::ImportServers,
+        ("user", "add") => return Some((AuditAction::AddUser, word(2))),
+        ("user", "edit") => return Some((AuditAction::EditUser, word(2))),
+        ("user", "remove") => return Some((AuditAction::RemoveUser, word(2))),
+        ("token", "create") => return Some((AuditAction::CreateToken, word(2))),
+        ("token", "revoke") => return Some((AuditAction::RevokeToken, word(2))),
+        _ => return None,
+    };
+    Some((action, word(1)))
+}
+
+// Mistyped command lines never got to do anything and are left out
+fn record(conn: &Connection, args: &Args, result: &Result<(), Failure>) {
+    let Some((action, target)) = audited(args) else {
+        return;
+    };
+    // The open launcher did the work and logged it itself
+    if FORWARDED.load(Ordering::Relaxed) {
+        return;
+    }
+    let outcome = match result {
+        Ok(()) => Outcome::Ok,
+        Err(failure) if failure.code == EXIT_USAGE => return,
+        Err(failure) => Outcome::Failed(failure.message.clone()),
+    };
+    if let Err(e) = audit::record(conn, &Identity::cli(), action, &target, "", outcome) {
+        eprintln!("warning: could not write the audit log: {}", e);
+    }
+}
+
+// Set once a command was handed to the open launcher over its socket
+static FORWARDED: AtomicBool = AtomicBool::new(false);
+
+fn forward(client: &mut Client, request: serde_json::Value) -> Result<serde_json::Value, Failure> {
+    FORWARDED.store(true, Ordering::Relaxed);
+    Ok(client.request(request)?)
+}
+
+// Servers the open launcher runs itself, by name, for list and status
+fn launcher_servers() -> HashMap<String, serde_json::Value> {
+    let Some(mut client) = Client::connect() else {
+        return HashMap::new();
+    };
+    let Ok(serde_json::Value::Array(servers)) = client.request(json!({ "op": "list" })) else {
+        return HashMap::new();
+    };
+    servers
+        .into_iter()
+        .filter(|server| server["state"] != "stopped" && server["background"] == false)
+        .filter_map(|server| Some((server["name"].as_str()?.to_string(), server)))
+        .collect()
+}
+
+fn print_json(value: &serde_json::Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+}
+
+// Server names are matched without caring about case
+fn find(conn: &Connection, name: &str) -> Result<Server, Failure> {
+    db::load_servers(conn)?
+        .into_iter()
+        .find(|server| server.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Failure::new(EXIT_NOT_FOUND, format!("no server named `{}`", name)))
+}
+
+fn list(conn: &Connection, json: bool) -> Result<(), Failure> {
+    let servers = db::load_servers(conn)?;
+    let in_launcher = launcher_servers();
+    let state = |server: &Server| {
+        let running = detached::read(&server.name).is_some() || in_launcher.contains_key(&server.name);
+        if running { "running" } else { "stopped" }
+    };
+    if json {
+        // The definition as `export` writes it, plus whether it is running
+        let servers: Vec<serde_json::Value> = servers
+            .iter()
+            .map(|server| {
+                let mut value = json!(ServerDef::from_server(server));
+                value["state"] = json!(state(server));
+                value
+            })
+            .collect();
+        print_json(&json!(servers));
+        return Ok(());
+    }
+    println!("{:<24} {:<8} COMMAND", "NAME", "STATE");
+    for server in &servers {
+        let state = state(server);
+        let command = if server.test_server {
+            "(test server)".to_string()
+        } else {
+            format!("{} {}", server.executable, server.args.join(" "))
+        };
+        println!("{:<24} {:<8} {}", server.name, state, command.trim_end());
+    }
+    Ok(())
+}
+
+fn status(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let servers = match args.positional.get(1) {
+        Some(name) => vec![find(conn, name)?],
+        None => db::load_servers(conn)?,
+    };
+
+    let in_launcher = launcher_servers();
+    let mut statuses = Vec::new();
+    for server in &servers {
+        let running = detached::read(&server.name);
+        let last_exit = db::last_run(conn, server.id)?.and_then(|run| run.exit_code);
+        let mut status = json!({
+            "name": server.name,
+            "running": running.is_some(),
+            "pid": running.as_ref().and_then(|r| r.server_pid),
+            "supervisor_pid": running.as_ref().map(|r| r.supervisor_pid),
+            "started_at": running.as_ref().map(|r| r.started_at.to_rfc3339()),
+            "uptime_secs": running.as_ref().map(|r| (Local::now() - r.started_at).num_seconds()),
+            "last_exit_code": last_exit,
+        });
+        // Run by the open TUI, there is no supervisor
+        if let Some(live) = in_launcher.get(&server.name) {
+            let started_at = live["started_at"]
+                .as_str()
+                .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok());
+            status["running"] = json!(true);
+            status["pid"] = live["pid"].clone();
+            status["started_at"] = live["started_at"].clone();
+            status["uptime_secs"] = json!(started_at.map(|at| (Local::now() - at.with_timezone(&Local)).num_seconds()));
+        }
+        statuses.push(status);
+    }
+
+    if json {
+        print_json(&json!(statuses));
+    } else {
+        println!("{:<24} {:<8} {:<8} {:<10} LAST EXIT", "NAME", "STATE", "PID", "UPTIME");
+        for status in &statuses {
+            let text = |key: &str| match &status[key] {
+                serde_json::Value::Null => "-".to_string(),
+                value => value.to_string(),
+            };
+            let uptime = status["uptime_secs"].as_i64().map_or("-".to_string(), |secs| {
+                format!("{}h{:02}m{:02}s", secs / 3600, secs % 3600 / 60, secs % 60)
+            });
+            let state = if status["running"] == true { "running" } else { "stopped" };
+            println!(
+                "{:<24} {:<8} {:<8} {:<10} {}",
+                status["name"].as_str().unwrap_or_default(),
+                state,
+                text("pid"),
+                uptime,
+                text("last_exit_code")
+            );
+        }
+    }
+
+    // Asking about one server doubles as a check that it is up
+    if args.positional.len() > 1 && statuses.iter().any(|status| status["running"] == false) {
+        return Err(Failure::new(EXIT_STATE, format!("{} is not running", servers[0].name)));
+    }
+    Ok(())
+}
+
+// Prints the result of a start or restart the open launcher did
+fn print_launched(server: &serde_json::Value, verb: &str, json: bool) {
+    if json {
+        print_json(&json!({
+            "name": server["name"],
+            "pid": server["pid"],
+            "supervisor_pid": null,
+        }));
+        return;
+    }
+    let name = server["name"].as_str().unwrap_or_default();
+    match server["pid"].as_u64() {
+        Some(pid) => println!("{} {} in the open launcher (pid {})", verb, name, pid),
+        None => println!("{} {} in the open launcher", verb, name),
+    }
+}
+
+fn start(conn: &Connection, name: &str, json: bool) -> Result<(), Failure> {
+    let server = find(conn, name)?;
+    if let Some(mut client) = Client::connect() {
+        let started = forward(&mut client, json!({ "op": "start", "server": server.name }))?;
+        print_launched(&started, "Started", json);
+        return Ok(());
+    }
+    start_background(&server, json)
+}
+
+fn start_background(server: &Server, json: bool) -> Result<(), Failure> {
+    if let Some(running) = detached::read(&server.name) {
+        return Err(Failure::new(
+            EXIT_STATE,
+            format!("{} is already running (supervisor pid {})", server.name, running.supervisor_pid),
+        ));
+    }
+
+    let mut supervisor = detached::spawn_supervisor(&server.name)?;
+    // Wait for the pid file so a failed launch is reported here and not only in the log
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let running = loop {
+        if let Some(running) = detached::read(&server.name) {
+            break running;
+        }
+        if let Some(status) = supervisor.try_wait()? {
+            return Err(Failure::new(
+                EXIT_ERROR,
+                format!(
+                    "{} failed to start ({}), see {}",
+                    server.name,
+                    status,
+                    logfile::log_path(&server.name).display()
+                ),
+            ));
+        }
+        if Instant::now() > deadline {
+            return Err(Failure::new(EXIT_ERROR, format!("{} did not come up in time", server.name)));
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    if json {
+        print_json(&json!({
+            "name": server.name,
+            "pid": running.server_pid,
+            "supervisor_pid": running.supervisor_pid,
+        }));
+    } else {
+        match running.server_pid {
+            Some(pid) => println!("Started {} (pid {})", server.name, pid),
+            None => println!("Started {}", server.name),
+        }
+    }
+    Ok(())
+}
+
+fn stop(conn: &Connection, name: &str, json: bool) -> Result<(), Failure> {
+    let server = find(conn, name)?;
+    match (detached::read(&server.name), Client::connect()) {
+        (Some(running), _) => detached::stop(&running, Duration::from_secs(10)),
+        (None, Some(mut client)) => {
+            forward(&mut client, json!({ "op": "stop", "server": server.name }))?;
+        }
+        (None, None) => return Err(Failure::new(EXIT_STATE, format!("{} is not running", server.name))),
+    }
+
+    if json {
+        print_json(&json!({ "name": server.name, "stopped": true }));
+    } else {
+        println!("Stopped {}", server.name);
+    }
+    Ok(())
+}
+
+// A background server stays in the background, anything else is left to the open launcher
+fn restart(conn: &Connection, name: &str, json: bool) -> Result<(), Failure> {
+    let server = find(conn, name)?;
+    if let Some(running) = detached::read(&server.name) {
+        detached::stop(&running, Duration::from_secs(10));
+        return start_background(&server, json);
+    }
+    match Client::connect() {
+        Some(mut client) => {
+            let restarted = forward(&mut client, json!({ "op": "restart", "server": server.name }))?;
+            print_launched(&restarted, "Restarted", json);
+            Ok(())
+        }
+        None => start_background(&server, json),
+    }
+}
+
+fn send(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let server = find(conn, args.name()?)?;
+    let input = args.positional[2..].join(" ");
+    if input.is_empty() {
+        return Err(Failure::usage("send needs the line to type"));
+    }
+    let Some(mut client) = Client::connect() else {
+        return Err(Failure::new(
+            EXIT_STATE,
+            "no launcher is open, only servers running in the TUI take console input",
+        ));
+    };
+    forward(&mut client, json!({ "op": "send", "server": server.name, "input": input }))?;
+    if json {
+        print_json(&json!({ "name": server.name, "sent": input }));
+    } else {
+        println!("Sent `{}` to {}", input, server.name);
+    }
+    Ok(())
+}
+
+fn logs(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let server = find(conn, args.name()?)?;
+    let lines: usize = match args.option(&["-n", "--lines"]) {
+        Some(lines) => lines
+            .parse()
+            .map_err(|_| Failure::usage(format!("`{}` is not a line count", lines)))?,
+        None => 50,
+    };
+    let path = logfile::log_path(&server.name);
+    let print = |line: &str| {
+        if json {
+            println!("{}", json!({ "name": server.name, "line": line }));
+        } else {
+            println!("{}", line);
+        }
+    };
+
+    let mut file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !args.flag(&["-f", "--follow"]) => {
+            return Err(Failure::new(EXIT_ERROR, format!("no log file at {}", path.display())));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::create_dir_all(logfile::LOG_DIR)?;
+            File::options().create(true).append(true).open(&path)?;
+            File::open(&path)?
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    let all: Vec<&str> = text.lines().collect();
+    for line in &all[all.len().saturating_sub(lines)..] {
+        print(line);
+    }
+    if !args.flag(&["-f", "--follow"]) {
+        return Ok(());
+    }
+
+    // Poll for appended lines until interrupted, starting over if the file is truncated
+    let mut position = file.stream_position()?;
+    let mut partial = String::new();
+    loop {
+        thread::sleep(Duration::from_millis(250));
+        let length = fs::metadata(&path)?.len();
+        if length < position {
+            position = 0;
+        }
+        if length == position {
+            continue;
+        }
+        file.seek(SeekFrom::Start(position))?;
+        let mut added = String::new();
+        file.read_to_string(&mut added)?;
+        position = file.stream_position()?;
+
+        partial.push_str(&added);
+        while let Some(end) = partial.find('\n') {
+            print(&partial[..end]);
+            partial.drain(..=end);
+        }
+    }
+}
+
+fn parse_color(text: &str) -> Result<Color, Failure> {
+    serverdef::parse_color(text).ok_or_else(|| Failure::usage(format!("`{}` is not a #rrggbb color", text)))
+}
+
+fn to_server(definition: &ServerDef) -> Result<Server, Failure> {
+    definition
+        .to_server()
+        .map_err(|e| Failure::new(EXIT_ERROR, format!("server {}: {}", definition.name, e)))
+}
+
+// Applies the add/edit options on top of `server`
+fn apply_options(mut server: Server, args: &Args) -> Result<Server, Failure> {
+    if let Some(name) = args.option(&["--name"]) {
+        server = server.name(name.trim());
+    }
+    if let Some(path) = args.option(&["--path"]) {
+        server = server.path(path.trim());
+    }
+    if let Some(executable) = args.option(&["--exec"]) {
+        server = server.executable(executable.trim());
+    }
+    if let Some(arguments) = args.option(&["--args"]) {
+        server = server.args(arguments.split_whitespace().map(String::from).collect());
+    }
+    if args.flag(&["--autostart"]) {
+        server = server.autostart(true);
+    }
+    if args.flag(&["--no-autostart"]) {
+        server = server.autostart(false);
+    }
+    if args.flag(&["--test"]) {
+        server = server.test_server(true);
+    }
+    if args.flag(&["--no-test"]) {
+        server = server.test_server(false);
+    }
+    if let Some(color) = args.option(&["--color"]) {
+        server = server.display_color(parse_color(color)?);
+    }
+    if let Some(preset) = args.option(&["--preset"]) {
+        let preset = LogPreset::parse(preset)
+            .ok_or_else(|| Failure::usage(format!("unknown log preset `{}`", preset)))?;
+        server = server.log_preset(preset);
+    }
+    Ok(server)
+}
+
+// The same checks the add/edit form makes
+fn validate(server: &Server, existing: &[Server]) -> Result<(), Failure> {
+    if server.name.is_empty() {
+        return Err(Failure::usage("--name is required"));
+    }
+    if !server.test_server && server.executable.is_empty() {
+        return Err(Failure::usage("--exec is required"));
+    }
+    serverdef::validate(server, existing).map_err(|e| Failure::new(EXIT_ERROR, e))
+}
+
+fn add(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let server = apply_options(Server::default(), args)?;
+    validate(&server, &db::load_servers(conn)?)?;
+    let id = save(conn, &server, None)?;
+
+    if json {
+        print_json(&json!({ "id": id, "name": server.name }));
+    } else {
+        println!("Added server {}", server.name);
+    }
+    Ok(())
+}
+
+fn edit(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let current = find(conn, args.name()?)?;
+    let server = apply_options(current.clone(), args)?;
+    validate(&server, &db::load_servers(conn)?)?;
+    if server.name != current.name && is_running(&current.name) {
+        return Err(Failure::new(EXIT_STATE, format!("stop {} before renaming it", current.name)));
+    }
+    save(conn, &server, Some(&current.name))?;
+
+    if json {
+        print_json(&json!(ServerDef::from_server(&server)));
+    } else {
+        println!("Updated server {}", server.name);
+    }
+    Ok(())
+}
+
+fn remove(conn: &Connection, name: &str, json: bool) -> Result<(), Failure> {
+    let server = find(conn, name)?;
+    if is_running(&server.name) {
+        return Err(Failure::new(EXIT_STATE, format!("stop {} before removing it", server.name)));
+    }
+    match Client::connect() {
+        Some(mut client) => {
+            forward(&mut client, json!({ "op": "delete", "server": server.name }))?;
+        }
+        None => db::delete_server(conn, server.id)?,
+    }
+
+    if json {
+        print_json(&json!({ "name": server.name, "removed": true }));
+    } else {
+        println!("Removed server {}", server.name);
+    }
+    Ok(())
+}
+
+// Run by the open launcher or in the background
+fn is_running(name: &str) -> bool {
+    detached::read(name).is_some() || launcher_servers().contains_key(name)
+}
+
+// Adds the server, or replaces `current`. While the launcher is open it does the saving, so its
+// list stays current and it can turn down renaming a server it runs.
+fn save(conn: &Connection, server: &Server, current: Option<&str>) -> Result<i32, Failure> {
+    let Some(mut client) = Client::connect() else {
+        return match current {
+            Some(_) => {
+                db::update_server(conn, server)?;
+                Ok(server.id)
+            }
+            None => Ok(db::insert_server(conn, server)?),
+        };
+    };
+    let request = json!({ "op": "save", "server": current, "definition": ServerDef::from_server(server) });
+    let saved = forward(&mut client, request)?;
+    Ok(saved["id"].as_i64().unwrap_or_default() as i32)
+}
+
+fn import(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let path = args.positional.get(1).ok_or_else(|| Failure::usage("import needs a file"))?;
+    let text = fs::read_to_string(path)?;
+    let definitions: Vec<ServerDef> = serde_json::from_str(&text)
+        .map_err(|e| Failure::new(EXIT_ERROR, format!("{} is not a server export: {}", path, e)))?;
+    let replace = args.flag(&["--replace"]);
+
+    let mut added = Vec::new();
+    let mut skipped = Vec::new();
+    for definition in definitions {
+        let existing = db::load_servers(conn)?;
+        let current = existing
+            .iter()
+            .find(|server| server.name.eq_ignore_ascii_case(&definition.name));
+        match current {
+            Some(current) if replace => {
+                let server = to_server(&definition)?.id(current.id);
+                save(conn, &server, Some(&current.name))?;
+                added.push(server.name);
+            }
+            Some(_) => skipped.push(definition.name),
+            None => {
+                let server = to_server(&definition)?;
+                save(conn, &server, None)?;
+                added.push(server.name);
+            }
+        }
+    }
+
+    if json {
+        print_json(&json!({ "imported": added, "skipped": skipped }));
+    } else {
+        println!("Imported {} server(s)", added.len());
+        if !skipped.is_empty() {
+            println!("Skipped existing (use --replace to overwrite): {}", skipped.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn export(conn: &Connection, args: &Args) -> Result<(), Failure> {
+    let servers: Vec<ServerDef> = db::load_servers(conn)?
+        .iter()
+        .map(ServerDef::from_server)
+        .collect();
+    let text = serde_json::to_string_pretty(&servers)
+        .map_err(|e| Failure::new(EXIT_ERROR, e.to_string()))?;
+    match args.positional.get(1) {
+        Some(path) => {
+            fs::write(path, text + "\n")?;
+            eprintln!("Exported {} server(s) to {}", servers.len(), path);
+        }
+        None => println!("{}", text),
+    }
+    Ok(())
+}
+
+fn config(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let mut settings = Settings::load(conn);
+    if let Some(key) = args.positional.get(1) {
+        // An empty value is allowed, it turns the API off
+        let value = args
+            .positional
+            .get(2)
+            .ok_or_else(|| Failure::usage(format!("config {} needs a value", key)))?;
+        settings.set(key, value).map_err(Failure::usage)?;
+        settings.save(conn)?;
+    }
+
+    if json {
+        let entries: serde_json::Map<String, serde_json::Value> = settings
+            .entries()
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), json!(value)))
+            .collect();
+        print_json(&json!(entries));
+    } else {
+        for (key, value) in settings.entries() {
+            println!("{:<20} {}", key, value);
+        }
+    }
+    Ok(())
+}
+
+// Checks the keymap file without opening the TUI, problems make it fail
+fn keys(conn: &Connection, json: bool) -> Result<(), Failure> {
+    let settings = Settings::load(conn);
+    let (keymap, problems) = Keymap::load(settings.key_preset, Path::new(&settings.keymap_file));
+    let bindings = Action::ALL.iter().map(|&action| {
+        let keys: Vec<String> = keymap.keys(action).iter().map(ToString::to_string).collect();
+        (action.name(), keys)
+    });
+
+    if json {
+        let bindings: serde_json::Map<String, serde_json::Value> =
+            bindings.map(|(name, keys)| (name.to_string(), json!(keys))).collect();
+        print_json(&json!({
+            "preset": settings.key_preset.name(),
+            "file": settings.keymap_file,
+            "bindings": bindings,
+            "problems": problems,
+        }));
+    } else {
+        println!("preset {}, file {}", settings.key_preset.name(), settings.keymap_file);
+        for (name, keys) in bindings {
+            println!("{:<20} {}", name, if keys.is_empty() { "-".to_string() } else { keys.join(", ") });
+        }
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Failure::new(EXIT_ERROR, problems.join("\n")))
+    }
+}
+
+// The word after `user`/`token` and the argument after that
+fn subcommand<'a>(args: &'a Args, what: &str) -> Result<(&'a str, Option<&'a str>), Failure> {
+    let sub = args
+        .positional
+        .get(1)
+        .ok_or_else(|| Failure::usage(format!("{} needs a subcommand", what)))?;
+    Ok((sub.as_str(), args.positional.get(2).map(String::as_str)))
+}
+
+fn find_user(conn: &Connection, name: Option<&str>) -> Result<db::User, Failure> {
+    let name = name.ok_or_else(|| Failure::usage("a user name is needed"))?;
+    db::find_user(conn, name)?
+        .ok_or_else(|| Failure::new(EXIT_NOT_FOUND, format!("no user named `{}`", name)))
+}
+
+// Names as stored, so the allow-list matches however they were typed
+fn allowed_servers(conn: &Connection, list: &str) -> Result<Vec<String>, Failure> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| find(conn, name).map(|server| server.name))
+        .collect()
+}
+
+// Prompts on the terminal with echo off, or reads a line from piped input
+fn read_password() -> Result<String, Failure> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let stdin = io::stdin();
+    let terminal = stdin.is_terminal();
+    #[cfg(unix)]
+    let mut saved: Option<libc::termios> = None;
+    if terminal {
+        eprint!("Password: ");
+        io::stderr().flush()?;
+        // Echo is only switched off where termios exists
+        #[cfg(unix)]
+        {
+            let mut attributes: libc::termios = unsafe { std::mem::zeroed() };
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut attributes) } == 0 {
+                saved = Some(attributes);
+                attributes.c_lflag &= !libc::ECHO;
+                unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &attributes) };
+            }
+        }
+    }
+    let mut password = String::new();
+    let read = stdin.lock().read_line(&mut password);
+    #[cfg(unix)]
+    if let Some(attributes) = saved {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &attributes) };
+    }
+    if terminal {
+        eprintln!();
+    }
+    read?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn password_hash() -> Result<String, Failure> {
+    let password = read_password()?;
+    if password.is_empty() {
+        return Err(Failure::usage("the password can not be empty"));
+    }
+    auth::hash_password(&password).map_err(|e| Failure::new(EXIT_ERROR, e))
+}
+
+fn parse_role(args: &Args) -> Result<Option<Role>, Failure> {
+    args.option(&["--role"])
+        .map(|role| {
+            Role::parse(role).ok_or_else(|| Failure::usage(format!("unknown role `{}`, use viewer, operator or admin", role)))
+        })
+        .transpose()
+}
+
+fn user_json(user: &db::User) -> serde_json::Value {
+    json!({ "name": user.name, "role": user.role.name(), "servers": user.servers })
+}
+
+fn user(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    // Anything on the command line shows up in ps and the shell history
+    if args.flag(&["--password"]) {
+        return Err(Failure::usage("passwords are not taken on the command line, type it when asked or pipe it to stdin"));
+    }
+    match subcommand(args, "user")? {
+        ("list", _) => {
+            let users = db::load_users(conn)?;
+            if json {
+                print_json(&json!(users.iter().map(user_json).collect::<Vec<_>>()));
+                return Ok(());
+            }
+            println!("{:<20} {:<9} SERVERS", "USER", "ROLE");
+            for user in users {
+                let servers = if user.servers.is_empty() { "all".to_string() } else { user.servers.join(", ") };
+                println!("{:<20} {:<9} {}", user.name, user.role.name(), servers);
+            }
+        }
+        ("add", name) => {
+            let name = name.ok_or_else(|| Failure::usage("user add needs a user name"))?.trim();
+            if db::find_user(conn, name)?.is_some() {
+                return Err(Failure::new(EXIT_ERROR, format!("a user named `{}` already exists", name)));
+            }
+            let role = parse_role(args)?.ok_or_else(|| Failure::usage("--role is required"))?;
+            let servers = allowed_servers(conn, args.option(&["--servers"]).unwrap_or_default())?;
+            let user = db::User {
+                id: -1,
+                name: name.to_string(),
+                password_hash: password_hash()?,
+                role,
+                servers,
+            };
+            db::insert_user(conn, &user)?;
+            if json {
+                print_json(&user_json(&user));
+            } else {
+                println!("Added {} {}", user.role.name(), user.name);
+            }
+        }
+        ("edit", name) => {
+            let mut user = find_user(conn, name)?;
+            if let Some(role) = parse_role(args)? {
+                user.role = role;
+            }
+            if let Some(list) = args.option(&["--servers"]) {
+                user.servers = allowed_servers(conn, list)?;
+            }
+            if args.flag(&["--new-password"]) {
+                user.password_hash = password_hash()?;
+            }
+            db::update_user(conn, &user)?;
+            if json {
+                print_json(&user_json(&user));
+            } else {
+                println!("Updated user {}", user.name);
+            }
+        }
+        ("remove", name) => {
+            let user = find_user(conn, name)?;
+            db::delete_user(conn, user.id)?;
+            if json {
+                print_json(&json!({ "name": user.name, "removed": true }));
+            } else {
+                println!("Removed user {}", user.name);
+            }
+        }
+        (other, _) => return Err(Failure::usage(format!("unknown user command `{}`", other))),
+    }
+    Ok(())
+}
+
+fn token(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    match subcommand(args, "token")? {
+        ("create", name) => {
+            let user = find_user(conn, name)?;
+            let label = args.option(&["--label"]).unwrap_or("api");
+            let token = auth::new_token();
+            let id = db::insert_token(conn, user.id, label, &auth::hash_token(&token), None)?;
+            if json {
+                print_json(&json!({ "id": id, "user": user.name, "label": label, "token": token }));
+            } else {
+                println!("{}", token);
+                eprintln!("Token {} for {}, it will not be shown again", id, user.name);
+            }
+        }
+        ("list", _) => {
+            let tokens = db::load_tokens(conn)?;
+            if json {
+                let tokens: Vec<serde_json::Value> = tokens
+                    .iter()
+                    .map(|token| {
+                        json!({
+                            "id": token.id,
+                            "user": token.user_name,
+                            "label": token.label,
+                            "created_at": token.created_at.to_rfc3339(),
+                            "expires_at": token.expires_at.map(|at| at.to_rfc3339()),
+                            "last_used_at": token.last_used_at.map(|at| at.to_rfc3339()),
+                        })
+                    })
+                    .collect();
+                print_json(&json!(tokens));
+                return Ok(());
+            }
+            println!("{:<5} {:<20} {:<18} {:<17} LAST USED", "ID", "USER", "LABEL", "EXPIRES");
+            let format = |at: Option<chrono::DateTime<Local>>, none: &str| {
+                at.map_or(none.to_string(), |at| at.format("%Y-%m-%d %H:%M").to_string())
+            };
+            for token in tokens {
+                println!(
+                    "{:<5} {:<20} {:<18} {:<17} {}",
+                    token.id,
+                    token.user_name,
+                    token.label,
+                    format(token.expires_at, "never"),
+                    format(token.last_used_at, "-"),
+                );
+            }
+        }
+        ("revoke", id) => {
+            let id: i64 = id
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| Failure::usage("token revoke needs a token id, see `token list`"))?;
+            if !db::delete_token(conn, id)? {
+                return Err(Failure::new(EXIT_NOT_FOUND, format!("no token with id {}", id)));
+            }
+            if json {
+                print_json(&json!({ "id": id, "revoked": true }));
+            } else {
+                println!("Revoked token {}", id);
+            }
+        }
+        (other, _) => return Err(Failure::usage(format!("unknown token command `{}`", other))),
+    }
+    Ok(())
+}
+
+fn audit_log(conn: &Connection, args: &Args, json: bool) -> Result<(), Failure> {
+    let count: usize = match args.option(&["-n", "--lines"]) {
+        Some(count) => count
+            .parse()
+            .map_err(|_| Failure::usage(format!("`{}` is not an entry count", count)))?,
+        None => 50,
+    };
+    let entries = db::load_audit(conn, args.option(&["--user"]), args.option(&["--server"]), count)?;
+    if json {
+        let entries: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "at": entry.at.to_rfc3339(),
+                    "user": entry.user,
+                    "source": entry.source,
+                    "action": entry.action,
+                    "target": entry.target,
+                    "detail": entry.detail,
+                    "outcome": entry.outcome,
+                    "message": entry.message,
+                })
+            })
+            .collect();
+        print_json(&json!(entries));
+        return Ok(());
+    }
+    println!("{:<19} {:<12} {:<16} {:<14} {:<16} {:<7} DETAIL", "TIME", "USER", "SOURCE", "ACTION", "TARGET", "OUTCOME");
+    for entry in entries {
+        let detail = if entry.message.is_empty() { entry.detail } else { entry.message };
+        println!(
+            "{:<19} {:<12} {:<16} {:<14} {:<16} {:<7} {}",
+            entry.at.format("%Y-%m-%d %H:%M:%S"),
+            entry.user,
+            entry.source,
+            entry.action,
+            entry.target,
+            entry.outcome,
+            detail,
+        );
+    }
+    Ok(())
+}
+
+// Another terminal on the open launcher, with its own pages, selection and filters
+#[cfg(unix)]
+fn attach() -> Result<(), Failure> {
+    let Some(client) = Client::connect() else {
+        return Err(Failure::new(EXIT_STATE, "no launcher is open to attach to"));
+    };
+    let (cols, rows) = terminal::size()?;
+    let (mut output, writer) = client.attach(cols, rows)?;
+    let writer = Arc::new(Mutex::new(writer));
+    let send = |writer: &Mutex<std::os::unix::net::UnixStream>, line: serde_json::Value| {
+        let mut writer = writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        writeln!(writer, "{}", line)
+    };
+
+    terminal::enable_raw_mode()?;
+    // Keys as typed, the launcher makes sense of them
+    {
+        let writer = writer.clone();
+        thread::spawn(move || {
+            let mut stdin = io::stdin();
+            let mut buffer = [0u8; 1024];
+            let mut pending = Vec::new();
+            while let Ok(read @ 1..) = stdin.read(&mut buffer) {
+                pending.extend_from_slice(&buffer[..read]);
+                let keys = complete_utf8(&mut pending);
+                if keys.is_empty() {
+                    continue;
+                }
+                if send(&writer, json!({ "keys": keys })).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+    // There is no resize event without an event loop, so the size is checked now and then
+    thread::spawn(move || {
+        let mut size = (cols, rows);
+        loop {
+            thread::sleep(Duration::from_millis(250));
+            match terminal::size() {
+                Ok(now) if now != size => {
+                    size = now;
+                    if send(&writer, json!({ "resize": [now.0, now.1] })).is_err() {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    // The launcher closes the connection when the dashboard quits
+    let mut stdout = io::stdout();
+    let mut buffer = [0u8; 16 * 1024];
+    let copied = loop {
+        match output.read(&mut buffer) {
+            Ok(0) => break Ok(()),
+            Ok(read) => {
+                if let Err(e) = stdout.write_all(&buffer[..read]).and_then(|()| stdout.flush()) {
+                    break Err(e);
+                }
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    Ok(copied?)
+}
+
+// Takes the text out of `pending`, leaving a character a read split in half for the next one
+#[cfg(unix)]
+fn complete_utf8(pending: &mut Vec<u8>) -> String {
+    let mut checked = 0;
+    let complete = loop {
+        match std::str::from_utf8(&pending[checked..]) {
+            Ok(_) => break pending.len(),
+            Err(e) => match e.error_len() {
+                // Really invalid, it gets replaced and the rest is looked at
+                Some(invalid) => checked += e.valid_up_to() + invalid,
+                // Cut off at the end, the rest of it is still to come
+                None => break checked + e.valid_up_to(),
+            },
+        }
+    };
+    let text = String::from_utf8_lossy(&pending[..complete]).into_owned();
+    pending.drain(..complete);
+    text
+}
+
+#[cfg(not(unix))]
+fn attach() -> Result<(), Failure> {
+    Err(Failure::new(EXIT_ERROR, "attach needs the control socket, which is Unix only"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_character_split_between_reads_is_kept_whole() {
+        let mut pending = "aé".as_bytes()[..2].to_vec();
+        assert_eq!(complete_utf8(&mut pending), "a");
+        assert_eq!(pending, [0xc3]);
+        pending.extend_from_slice(&"é".as_bytes()[1..]);
+        assert_eq!(complete_utf8(&mut pending), "é");
+        assert!(pending.is_empty());
+
+        let mut pending = vec![0xff, b'b', 0xc3];
+        assert_eq!(complete_utf8(&mut pending), "\u{fffd}b");
+        assert_eq!(pending, [0xc3]);
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::auth::Identity;
+use crate::serverdef::ServerDef;
+use crate::stream::{StreamEvent, StreamFilter};
+
+// Requests from outside the UI loop (the HTTP API, the Unix socket) to act on the running servers.
+// The UI loop owns the servers, so it answers them between frames.
+
+pub enum ControlCommand {
+    List,
+    Get(String),
+    Start(String),
+    Stop(String),
+    Restart(String),
+    SendCommand { name: String, command: String },
+    // Adds the server, or replaces the one called `name`
+    Save { name: Option<String>, definition: ServerDef },
+    Delete(String),
+    // Log lines and lifecycle events go to `events` until the receiver hangs up
+    Subscribe { filter: StreamFilter, events: Sender<StreamEvent> },
+    // Counters and gauges for /metrics, see metrics.rs
+    Metrics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    NotFound,
+    Conflict, // the server is in the wrong state for the request
+    Invalid,
+    Unauthorized, // not logged in
+    Forbidden,    // logged in, but the role or allow-list says no
+    RateLimited,
+    TooLarge, // the request body is over the limit
+    Failed,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 8] = [
+        ErrorKind::NotFound,
+        ErrorKind::Conflict,
+        ErrorKind::Invalid,
+        ErrorKind::Unauthorized,
+        ErrorKind::Forbidden,
+        ErrorKind::RateLimited,
+        ErrorKind::TooLarge,
+        ErrorKind::Failed,
+    ];
+
+    // As sent over the Unix socket
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::Conflict => "conflict",
+            ErrorKind::Invalid => "invalid",
+            ErrorKind::Unauthorized => "unauthorized",
+            ErrorKind::Forbidden => "forbidden",
+            ErrorKind::RateLimited => "rate_limited",
+            ErrorKind::TooLarge => "too_large",
+            ErrorKind::Failed => "failed",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<ErrorKind> {
+        ErrorKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+#[derive(Debug)]
+pub struct ControlError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ControlError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> ControlError {
+        ControlError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+pub type ControlResult = Result<Value, ControlError>;
+
+pub struct ControlRequest {
+    pub identity: Identity, // checked by the UI loop before acting
+    pub command: ControlCommand,
+    pub reply: Sender<ControlResult>,
+}
+
+// How long a caller waits for the UI loop before giving up
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Sends the command to the UI loop and blocks until it answers
+pub fn request(
+    sender: &Sender<ControlRequest>,
+    identity: &Identity,
+    command: ControlCommand,
+) -> ControlResult {
+    let (reply, answer) = channel();
+    let identity = identity.clone();
+    sender
+        .send(ControlRequest { identity, command, reply })
+        .map_err(|_| ControlError::new(ErrorKind::Failed, "launcher is shutting down"))?;
+    answer
+        .recv_timeout(REPLY_TIMEOUT)
+        .map_err(|_| ControlError::new(ErrorKind::Failed, "launcher did not answer"))?
+}
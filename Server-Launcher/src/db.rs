@@ -1,7 +1,15 @@
-use colored_text::Colorize;
 use ratatui::style::Color;
 use rusqlite::{params, Connection, Result};
 
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::auth::{Identity, Role, Source};
+use crate::classify::{LogLevel, LogPreset, LogRule};
+use crate::triggers::{Trigger, TriggerAction};
+
 
 #[derive(Debug, Clone)]
 pub struct Server {
@@ -12,12 +20,15 @@ pub struct Server {
     pub args: Vec<String>,
     pub autostart: bool, // If True will laucnh the server when the program starts
     pub test_server: bool, // If true it uses a Dummy Server Thread
-    pub display_color: ratatui::style::Color // Try to store this in the db as a bitshifted u24 to u32. then decode to use rgb on read
+    pub display_color: ratatui::style::Color, // Try to store this in the db as a bitshifted u24 to u32. then decode to use rgb on read
+    pub log_preset: LogPreset, // Built in level rules for the server's output format
+    pub log_rules: Vec<LogRule>, // Extra level rules, tried before the preset
+    pub triggers: Vec<Trigger>, // Actions run when an output line matches
 }
 
 impl Server {
     pub fn default() -> Server {
-        Server { id: -1, name: "".to_string(), path: "~/Users/student/bin".to_string(), executable: "script.sh".to_string(), args: vec![], autostart: false, test_server: false, display_color: Color::White }
+        Server { id: -1, name: "".to_string(), path: "~/Users/student/bin".to_string(), executable: "script.sh".to_string(), args: vec![], autostart: false, test_server: false, display_color: Color::White, log_preset: LogPreset::Plain, log_rules: vec![], triggers: vec![] }
     }
     pub fn id(mut self , new_id:i32)->Server{
         self.id = new_id;
@@ -51,8 +62,23 @@ impl Server {
         self.display_color = new_color;
         self
     }
+    pub fn log_preset(mut self , new_preset:LogPreset)->Server{
+        self.log_preset = new_preset;
+        self
+    }
+    pub fn log_rules(mut self , new_rules:Vec<LogRule>)->Server{
+        self.log_rules = new_rules;
+        self
+    }
+    pub fn triggers(mut self , new_triggers:Vec<Trigger>)->Server{
+        self.triggers = new_triggers;
+        self
+    }
 }
 
+// Relative to the working directory, like logs/ and run/
+pub const DB_PATH: &str = "path_to_db";
+
 pub fn connect_db(path: &str) -> Result<Connection> {
     let conn = Connection::open(path)?;
 
@@ -67,13 +93,133 @@ pub fn connect_db(path: &str) -> Result<Connection> {
         )",
         [],
     )?;
+    add_column(&conn, "servers", "log_preset TEXT NOT NULL DEFAULT 'plain'")?;
+    add_column(&conn, "servers", "test_server INTEGER NOT NULL DEFAULT 0")?;
+    add_column(&conn, "servers", "display_color INTEGER")?; // 0xRRGGBB, NULL for the default
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS log_rules (
+            id INTEGER PRIMARY KEY,
+            server_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            pattern TEXT NOT NULL,
+            level TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS triggers (
+            id INTEGER PRIMARY KEY,
+            server_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            pattern TEXT NOT NULL,
+            action TEXT NOT NULL,
+            argument TEXT NOT NULL,
+            cooldown_secs INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // One row per launch, ended_at stays NULL until the process is gone
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS runs (
+            id INTEGER PRIMARY KEY,
+            server_id INTEGER NOT NULL,
+            server_name TEXT NOT NULL,
+            started_at TEXT NOT NULL,
+            ended_at TEXT,
+            exit_code INTEGER
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Accounts for the HTTP API, servers is a JSON list of names an operator may control
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL,
+            servers TEXT NOT NULL DEFAULT '[]'
+        )",
+        [],
+    )?;
+
+    // Who did what to which server, rows are never changed or removed
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit (
+            id INTEGER PRIMARY KEY,
+            at TEXT NOT NULL,
+            user TEXT NOT NULL,
+            source TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT NOT NULL,
+            detail TEXT NOT NULL,
+            outcome TEXT NOT NULL,
+            message TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS audit_no_update BEFORE UPDATE ON audit
+            BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;
+         CREATE TRIGGER IF NOT EXISTS audit_no_delete BEFORE DELETE ON audit
+            BEGIN SELECT RAISE(ABORT, 'the audit log is append-only'); END;",
+    )?;
+
+    // Only the sha256 of a token is kept, the token itself is shown once
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tokens (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            label TEXT NOT NULL,
+            token_hash TEXT NOT NULL UNIQUE,
+            created_at TEXT NOT NULL,
+            expires_at TEXT,
+            last_used_at TEXT
+        )",
+        [],
+    )?;
 
     Ok(conn)
 }
 
+// Only rgb colors are stored, anything else falls back to the default on load
+fn color_to_u32(color: Color) -> Option<u32> {
+    match color {
+        Color::Rgb(r, g, b) => Some(((r as u32) << 16) | ((g as u32) << 8) | b as u32),
+        _ => None,
+    }
+}
+
+fn color_from_u32(value: u32) -> Color {
+    Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)
+}
+
+// Databases created by older versions are missing newer columns
+fn add_column(conn: &Connection, table: &str, column: &str) -> Result<()> {
+    match conn.execute(&format!("ALTER TABLE {} ADD COLUMN {}", table, column), []) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(_, Some(msg))) if msg.contains("duplicate column") => {
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
 pub fn load_servers(conn: &Connection) -> Result<Vec<Server>> {
-    let mut stmt =
-        conn.prepare("SELECT id, name, path, executable, args, autostart FROM servers")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, path, executable, args, autostart, log_preset, test_server, display_color FROM servers ORDER BY id",
+    )?;
     let rows = stmt.query_map([], |row| {
         Ok(Server {
             id: row.get(0)?,
@@ -86,14 +232,93 @@ pub fn load_servers(conn: &Connection) -> Result<Vec<Server>> {
                 .map(String::from)
                 .collect(),
             autostart: row.get::<_, i32>(5)? != 0,
-            test_server: row.get::<_, bool>(6)?,
-            display_color: Color::White, //TODO convert the u32 into 3 bytes of rgb
+            test_server: row.get::<_, i32>(7)? != 0,
+            display_color: row
+                .get::<_, Option<u32>>(8)?
+                .map_or(Color::White, color_from_u32),
+            log_preset: LogPreset::parse(&row.get::<_, String>(6)?).unwrap_or(LogPreset::Plain),
+            log_rules: vec![],
+            triggers: vec![],
         })
     })?;
 
-    Ok(rows.filter_map(Result::ok).collect())
+    let mut servers: Vec<Server> = rows.filter_map(Result::ok).collect();
+    for server in servers.iter_mut() {
+        server.log_rules = load_log_rules(conn, server.id)?;
+        server.triggers = load_triggers(conn, server.id)?;
+    }
+    Ok(servers)
+}
+
+// Rules with a pattern that no longer compiles or an unknown level are skipped
+pub fn load_log_rules(conn: &Connection, server_id: i32) -> Result<Vec<LogRule>> {
+    let mut stmt = conn.prepare(
+        "SELECT pattern, level FROM log_rules WHERE server_id = ?1 ORDER BY position",
+    )?;
+    let rows = stmt.query_map(params![server_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|(pattern, level)| LogRule::new(&pattern, LogLevel::parse(&level)?).ok())
+        .collect())
+}
+
+pub fn save_log_rules(conn: &Connection, server_id: i32, rules: &[LogRule]) -> Result<()> {
+    conn.execute("DELETE FROM log_rules WHERE server_id = ?1", params![server_id])?;
+    for (position, rule) in rules.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO log_rules (server_id, position, pattern, level) VALUES (?1, ?2, ?3, ?4)",
+            params![server_id, position as i32, rule.pattern.as_str(), rule.level.label()],
+        )?;
+    }
+    Ok(())
+}
+
+// Same as log rules, broken rows are skipped rather than failing the whole load
+pub fn load_triggers(conn: &Connection, server_id: i32) -> Result<Vec<Trigger>> {
+    let mut stmt = conn.prepare(
+        "SELECT pattern, action, argument, cooldown_secs FROM triggers WHERE server_id = ?1 ORDER BY position",
+    )?;
+    let rows = stmt.query_map(params![server_id], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+
+    Ok(rows
+        .filter_map(Result::ok)
+        .filter_map(|(pattern, action, argument, cooldown)| {
+            let action = TriggerAction::parse(&action, &argument)?;
+            let cooldown = Duration::from_secs(cooldown.max(0) as u64);
+            Trigger::new(&pattern, action, cooldown).ok()
+        })
+        .collect())
+}
+
+pub fn save_triggers(conn: &Connection, server_id: i32, triggers: &[Trigger]) -> Result<()> {
+    conn.execute("DELETE FROM triggers WHERE server_id = ?1", params![server_id])?;
+    for (position, trigger) in triggers.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO triggers (server_id, position, pattern, action, argument, cooldown_secs) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                server_id,
+                position as i32,
+                trigger.pattern.as_str(),
+                trigger.action.kind(),
+                trigger.action.argument(),
+                trigger.cooldown.as_secs() as i64,
+            ],
+        )?;
+    }
+    Ok(())
 }
 
+#[allow(dead_code)]
 pub fn update_server_args(conn: &Connection, id: i32, new_args: &[String]) -> Result<()> {
     let joined = new_args.join(" ");
     conn.execute(
@@ -103,17 +328,373 @@ pub fn update_server_args(conn: &Connection, id: i32, new_args: &[String]) -> Re
     Ok(())
 }
 
-pub fn insert_server(conn: &Connection, server: &Server) -> Result<()> {
+// Returns the id the new row was given. The server and its rules are written all or nothing,
+// as are the changes in update_server and delete_server.
+pub fn insert_server(conn: &Connection, server: &Server) -> Result<i32> {
     let joined = server.args.join(" ");
-    conn.execute(
-        "INSERT INTO servers (name, path, executable, args, autostart) VALUES (?1, ?2, ?3, ?4, ?5)",
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO servers (name, path, executable, args, autostart, log_preset, test_server, display_color) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
         params![
             server.name,
             server.path,
             server.executable,
             joined,
             server.autostart as i32,
+            server.log_preset.name(),
+            server.test_server as i32,
+            color_to_u32(server.display_color),
+        ],
+    )?;
+    let id = tx.last_insert_rowid() as i32;
+    save_log_rules(&tx, id, &server.log_rules)?;
+    save_triggers(&tx, id, &server.triggers)?;
+    tx.commit()?;
+    Ok(id)
+}
+
+// Overwrites every stored attribute of the server with the same id
+pub fn update_server(conn: &Connection, server: &Server) -> Result<()> {
+    let joined = server.args.join(" ");
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE servers SET name = ?1, path = ?2, executable = ?3, args = ?4, autostart = ?5, log_preset = ?6, test_server = ?7, display_color = ?8 WHERE id = ?9",
+        params![
+            server.name,
+            server.path,
+            server.executable,
+            joined,
+            server.autostart as i32,
+            server.log_preset.name(),
+            server.test_server as i32,
+            color_to_u32(server.display_color),
+            server.id,
+        ],
+    )?;
+    save_log_rules(&tx, server.id, &server.log_rules)?;
+    save_triggers(&tx, server.id, &server.triggers)?;
+    tx.commit()
+}
+
+pub fn delete_server(conn: &Connection, id: i32) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM log_rules WHERE server_id = ?1", params![id])?;
+    tx.execute("DELETE FROM triggers WHERE server_id = ?1", params![id])?;
+    tx.execute("DELETE FROM servers WHERE id = ?1", params![id])?;
+    tx.commit()
+}
+
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub server_name: String, // kept as it was at launch, the server may be renamed or deleted since
+    pub started_at: DateTime<Local>,
+    pub ended_at: Option<DateTime<Local>>,
+    pub exit_code: Option<i32>, // negative for the signal that killed it
+}
+
+// Returns the id of the new run row
+pub fn start_run(conn: &Connection, server: &Server, started_at: DateTime<Local>) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO runs (server_id, server_name, started_at) VALUES (?1, ?2, ?3)",
+        params![server.id, server.name, started_at.to_rfc3339()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn finish_run(conn: &Connection, run: i64, exit_code: Option<i32>) -> Result<()> {
+    conn.execute(
+        "UPDATE runs SET ended_at = ?1, exit_code = ?2 WHERE id = ?3",
+        params![Local::now().to_rfc3339(), exit_code, run],
+    )?;
+    Ok(())
+}
+
+// Newest first
+pub fn load_runs(conn: &Connection, limit: usize) -> Result<Vec<Run>> {
+    let mut stmt = conn.prepare(
+        "SELECT server_name, started_at, ended_at, exit_code FROM runs ORDER BY id DESC LIMIT ?1",
+    )?;
+    let runs = stmt.query_map(params![limit as i64], run_from_row)?;
+    runs.collect()
+}
+
+fn run_from_row(row: &rusqlite::Row) -> Result<Run> {
+    let parse = |text: String| {
+        DateTime::parse_from_rfc3339(&text)
+            .map(|time| time.with_timezone(&Local))
+            .ok()
+    };
+    let ended_at: Option<String> = row.get(2)?;
+    Ok(Run {
+        server_name: row.get(0)?,
+        started_at: parse(row.get(1)?).unwrap_or_else(Local::now),
+        ended_at: ended_at.and_then(parse),
+        exit_code: row.get(3)?,
+    })
+}
+
+pub fn last_run(conn: &Connection, server_id: i32) -> Result<Option<Run>> {
+    let mut stmt = conn.prepare(
+        "SELECT server_name, started_at, ended_at, exit_code FROM runs WHERE server_id = ?1 ORDER BY id DESC LIMIT 1",
+    )?;
+    let mut runs = stmt.query_map(params![server_id], run_from_row)?;
+    runs.next().transpose()
+}
+
+pub fn load_settings(conn: &Connection) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
+    let settings = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    settings.collect()
+}
+
+pub fn save_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub id: i64,
+    pub name: String,
+    pub password_hash: String,
+    pub role: Role,
+    pub servers: Vec<String>, // empty allows every server
+}
+
+impl User {
+    pub fn identity(&self, source: Source) -> Identity {
+        Identity {
+            user: self.name.clone(),
+            role: self.role,
+            servers: self.servers.clone(),
+            source,
+        }
+    }
+}
+
+// An unreadable role falls back to the least it could be
+fn user_from_row(row: &rusqlite::Row) -> Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        password_hash: row.get(2)?,
+        role: Role::parse(&row.get::<_, String>(3)?).unwrap_or(Role::Viewer),
+        servers: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
+    })
+}
+
+pub fn load_users(conn: &Connection) -> Result<Vec<User>> {
+    let mut stmt = conn.prepare("SELECT id, name, password_hash, role, servers FROM users ORDER BY name")?;
+    let users = stmt.query_map([], user_from_row)?;
+    users.collect()
+}
+
+pub fn find_user(conn: &Connection, name: &str) -> Result<Option<User>> {
+    let mut stmt =
+        conn.prepare("SELECT id, name, password_hash, role, servers FROM users WHERE name = ?1")?;
+    let mut users = stmt.query_map(params![name], user_from_row)?;
+    users.next().transpose()
+}
+
+pub fn has_users(conn: &Connection) -> Result<bool> {
+    conn.query_row("SELECT EXISTS (SELECT 1 FROM users)", [], |row| row.get(0))
+}
+
+pub fn insert_user(conn: &Connection, user: &User) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO users (name, password_hash, role, servers) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            user.name,
+            user.password_hash,
+            user.role.name(),
+            serde_json::to_string(&user.servers).unwrap_or_default(),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_user(conn: &Connection, user: &User) -> Result<()> {
+    conn.execute(
+        "UPDATE users SET name = ?1, password_hash = ?2, role = ?3, servers = ?4 WHERE id = ?5",
+        params![
+            user.name,
+            user.password_hash,
+            user.role.name(),
+            serde_json::to_string(&user.servers).unwrap_or_default(),
+            user.id,
         ],
     )?;
     Ok(())
 }
+
+// Their tokens go with them
+pub fn delete_user(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("DELETE FROM tokens WHERE user_id = ?1", params![id])?;
+    conn.execute("DELETE FROM users WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub id: i64,
+    pub user_name: String,
+    pub label: String,
+    pub created_at: DateTime<Local>,
+    pub expires_at: Option<DateTime<Local>>, // set for dashboard logins, API tokens never expire
+    pub last_used_at: Option<DateTime<Local>>,
+}
+
+pub fn insert_token(
+    conn: &Connection,
+    user_id: i64,
+    label: &str,
+    token_hash: &str,
+    expires_at: Option<DateTime<Local>>,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO tokens (user_id, label, token_hash, created_at, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            user_id,
+            label,
+            token_hash,
+            Local::now().to_rfc3339(),
+            expires_at.map(|at| at.to_rfc3339()),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// The owner of an unexpired token and the token's label, marking it used on the way
+pub fn token_user(conn: &Connection, token_hash: &str) -> Result<Option<(User, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT users.id, users.name, users.password_hash, users.role, users.servers, tokens.expires_at, tokens.label
+         FROM tokens JOIN users ON users.id = tokens.user_id WHERE tokens.token_hash = ?1",
+    )?;
+    let mut rows = stmt.query_map(params![token_hash], |row| {
+        Ok((user_from_row(row)?, row.get::<_, Option<String>>(5)?, row.get::<_, String>(6)?))
+    })?;
+    let Some((user, expires_at, label)) = rows.next().transpose()? else {
+        return Ok(None);
+    };
+    let expired = expires_at
+        .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+        .is_some_and(|at| at < Local::now());
+    if expired {
+        conn.execute("DELETE FROM tokens WHERE token_hash = ?1", params![token_hash])?;
+        return Ok(None);
+    }
+    conn.execute(
+        "UPDATE tokens SET last_used_at = ?1 WHERE token_hash = ?2",
+        params![Local::now().to_rfc3339(), token_hash],
+    )?;
+    Ok(Some((user, label)))
+}
+
+pub fn load_tokens(conn: &Connection) -> Result<Vec<Token>> {
+    let mut stmt = conn.prepare(
+        "SELECT tokens.id, users.name, tokens.label, tokens.created_at, tokens.expires_at, tokens.last_used_at
+         FROM tokens JOIN users ON users.id = tokens.user_id ORDER BY tokens.id",
+    )?;
+    let parse = |text: Option<String>| {
+        text.and_then(|text| DateTime::parse_from_rfc3339(&text).ok())
+            .map(|time| time.with_timezone(&Local))
+    };
+    let tokens = stmt.query_map([], |row| {
+        Ok(Token {
+            id: row.get(0)?,
+            user_name: row.get(1)?,
+            label: row.get(2)?,
+            created_at: parse(row.get(3)?).unwrap_or_else(Local::now),
+            expires_at: parse(row.get(4)?),
+            last_used_at: parse(row.get(5)?),
+        })
+    })?;
+    tokens.collect()
+}
+
+// Returns whether there was such a token
+pub fn delete_token(conn: &Connection, id: i64) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM tokens WHERE id = ?1", params![id])? > 0)
+}
+
+pub fn delete_token_hash(conn: &Connection, token_hash: &str) -> Result<()> {
+    conn.execute("DELETE FROM tokens WHERE token_hash = ?1", params![token_hash])?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub at: DateTime<Local>,
+    pub user: String,
+    pub source: String,
+    pub action: String,
+    pub target: String,  // server, user or token acted on
+    pub detail: String,  // e.g. the console command
+    pub outcome: String, // ok, denied or failed
+    pub message: String, // why it was denied or failed
+}
+
+pub fn insert_audit(conn: &Connection, entry: &AuditEntry) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit (at, user, source, action, target, detail, outcome, message) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            entry.at.to_rfc3339(),
+            entry.user,
+            entry.source,
+            entry.action,
+            entry.target,
+            entry.detail,
+            entry.outcome,
+            entry.message,
+        ],
+    )?;
+    Ok(())
+}
+
+// Newest first, None matches everything
+pub fn load_audit(
+    conn: &Connection,
+    user: Option<&str>,
+    target: Option<&str>,
+    limit: usize,
+) -> Result<Vec<AuditEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT at, user, source, action, target, detail, outcome, message FROM audit
+         WHERE (?1 IS NULL OR user = ?1 COLLATE NOCASE) AND (?2 IS NULL OR target = ?2 COLLATE NOCASE)
+         ORDER BY id DESC LIMIT ?3",
+    )?;
+    let entries = stmt.query_map(params![user, target, limit as i64], |row| {
+        Ok(AuditEntry {
+            at: DateTime::parse_from_rfc3339(&row.get::<_, String>(0)?)
+                .map(|at| at.with_timezone(&Local))
+                .unwrap_or_else(|_| Local::now()),
+            user: row.get(1)?,
+            source: row.get(2)?,
+            action: row.get(3)?,
+            target: row.get(4)?,
+            detail: row.get(5)?,
+            outcome: row.get(6)?,
+            message: row.get(7)?,
+        })
+    })?;
+    entries.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_server_is_saved_whole_or_not_at_all() {
+        let conn = connect_db(":memory:").unwrap();
+        let before = load_servers(&conn).unwrap().len();
+        // The rules can't be written, so the server row must not stay behind either
+        conn.execute_batch("DROP TABLE triggers").unwrap();
+        assert!(insert_server(&conn, &Server::default().name("Half")).is_err());
+        assert_eq!(load_servers(&conn).unwrap().len(), before);
+    }
+}
@@ -0,0 +1,466 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(unix)]
+use std::{
+    collections::HashMap,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    sync::mpsc::{channel, RecvTimeoutError},
+    thread,
+    time::Instant,
+};
+
+use chrono::{DateTime, Local};
+#[cfg(unix)]
+use rusqlite::Connection;
+
+#[cfg(unix)]
+use crate::audit::{self, AuditAction, Outcome};
+#[cfg(unix)]
+use crate::auth::Identity;
+#[cfg(unix)]
+use crate::db::{self, Server};
+use crate::logfile;
+#[cfg(unix)]
+use crate::logfile::LogWriter;
+use crate::servers::ServerHandle;
+#[cfg(unix)]
+use crate::servers::{self, Handover, MessageType, ServerLifecycleEvent, ServerMessage};
+#[cfg(unix)]
+use crate::triggers::TriggerAction;
+
+// Servers started from the command line run under a small supervisor process of their own.
+// It writes a pid file in here so other commands can find them. The supervisor needs Unix
+// sessions, signals and inherited pipes, elsewhere nothing runs in the background.
+pub const RUN_DIR: &str = "run";
+
+#[cfg_attr(not(unix), allow(dead_code))] // the start times are only checked before signalling
+pub struct Detached {
+    pub server_pid: Option<u32>, // None for test servers
+    pub supervisor_pid: u32,
+    pub started_at: DateTime<Local>,
+    // Start times of the two, so a pid that was reused since is not mistaken for them
+    server_start: Option<u64>,
+    supervisor_start: Option<u64>,
+}
+
+fn pid_path(name: &str) -> PathBuf {
+    PathBuf::from(RUN_DIR).join(format!("{}.pid", logfile::escape_name(name)))
+}
+
+#[cfg(unix)]
+pub fn is_alive(pid: u32) -> bool {
+    // Signal 0 only checks the process exists, EPERM means it does but is not ours
+    let found = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    found || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// When the process started, in clock ticks since boot. Only Linux has /proc to read it from.
+#[cfg(unix)]
+pub fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name can hold spaces and parentheses, the fields after it are plain numbers
+    let fields = &stat[stat.rfind(')')? + 1..];
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+// Whether `pid` is still the process that was written down. Without a start time to compare
+// it has to at least still lead its own process group, as servers and supervisors do.
+#[cfg(unix)]
+pub fn is_same(pid: u32, start: Option<u64>) -> bool {
+    if !is_alive(pid) {
+        return false;
+    }
+    match start {
+        Some(start) => start_time(pid) == Some(start),
+        None => (unsafe { libc::getpgid(pid as libc::pid_t) }) == pid as libc::pid_t,
+    }
+}
+
+// No supervisor is ever started here, so any pid file is left over from somewhere else
+#[cfg(not(unix))]
+fn is_same(_pid: u32, _start: Option<u64>) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+pub fn spawn_supervisor(_name: &str) -> io::Result<std::process::Child> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+pub fn hand_over(_handle: &mut ServerHandle, _run: Option<i64>) -> io::Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(unix))]
+pub fn stop(_detached: &Detached, _timeout: Duration) {}
+
+#[cfg(not(unix))]
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "servers only run in the background on Unix")
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, signal: libc::c_int) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, signal);
+    }
+}
+
+// The running detached instance of the server, stale pid files are cleaned up on the way
+pub fn read(name: &str) -> Option<Detached> {
+    let path = pid_path(name);
+    let text = fs::read_to_string(&path).ok()?;
+    let mut fields = text.split_whitespace();
+    let server_pid = fields.next()?.parse().ok();
+    let supervisor_pid = fields.next()?.parse().ok()?;
+    let started_at = DateTime::parse_from_rfc3339(fields.next()?)
+        .ok()?
+        .with_timezone(&Local);
+    // Missing from pid files written by older versions, and where there is no /proc
+    let server_start = fields.next().and_then(|start| start.parse().ok());
+    let supervisor_start = fields.next().and_then(|start| start.parse().ok());
+
+    if !is_same(supervisor_pid, supervisor_start) {
+        let _ = fs::remove_file(path);
+        return None;
+    }
+    Some(Detached {
+        server_pid,
+        supervisor_pid,
+        started_at,
+        server_start,
+        supervisor_start,
+    })
+}
+
+#[cfg(unix)]
+fn write(name: &str, handle: &ServerHandle) -> io::Result<()> {
+    fs::create_dir_all(RUN_DIR)?;
+    let number = |value: Option<u64>| value.map_or("-".to_string(), |value| value.to_string());
+    let server_pid = handle.pid().map(u64::from);
+    let server_start = handle.adopted_start.or_else(|| handle.pid().and_then(start_time));
+    fs::write(
+        pid_path(name),
+        format!(
+            "{} {} {} {} {}\n",
+            number(server_pid),
+            std::process::id(),
+            handle.started_at.to_rfc3339(),
+            number(server_start),
+            number(start_time(std::process::id())),
+        ),
+    )
+}
+
+// `<this binary> supervise <name>`, run in a session of its own so it outlives the shell
+#[cfg(unix)]
+fn supervisor_command(name: &str) -> io::Result<Command> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("supervise")
+        .arg(name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+    Ok(command)
+}
+
+#[cfg(unix)]
+pub fn spawn_supervisor(name: &str) -> io::Result<std::process::Child> {
+    supervisor_command(name)?.spawn()
+}
+
+// Gives a server the launcher started to a supervisor without stopping it. The supervisor
+// inherits the pipes under the same numbers: `supervise <name> --adopt <pid> <start> <run>
+// <started_at> <stdout> <stderr> <stdin>`, `-` for a missing start time, run or stdin.
+#[cfg(unix)]
+pub fn hand_over(handle: &mut ServerHandle, run: Option<i64>) -> io::Result<()> {
+    let (Some(pid), Some((stdout, stderr))) = (handle.pid(), handle.output.take()) else {
+        return Err(io::Error::other("test servers have no process to hand over"));
+    };
+    let stdin = handle.stdin.take().map(OwnedFd::from);
+    let fds: Vec<RawFd> = [Some(&stdout), Some(&stderr), stdin.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(AsRawFd::as_raw_fd)
+        .collect();
+
+    let mut command = supervisor_command(&handle.name)?;
+    command
+        .arg("--adopt")
+        .arg(pid.to_string())
+        // Read while it is still our child, its pid can't have been reused yet
+        .arg(start_time(pid).map_or("-".to_string(), |start| start.to_string()))
+        .arg(run.map_or("-".to_string(), |run| run.to_string()))
+        .arg(handle.started_at.to_rfc3339())
+        .arg(stdout.as_raw_fd().to_string())
+        .arg(stderr.as_raw_fd().to_string())
+        .arg(stdin.as_ref().map_or("-".to_string(), |fd| fd.as_raw_fd().to_string()));
+    unsafe {
+        // Only in the child, the launcher's own copies stay close-on-exec
+        command.pre_exec(move || {
+            for &fd in &fds {
+                if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    command.spawn().map(|_| ())
+}
+
+// Takes the fds `hand_over` passed down back into ownership
+#[cfg(unix)]
+fn parse_handover(args: &[String]) -> Option<(Handover, Option<i64>)> {
+    let [flag, pid, start, run, started_at, stdout, stderr, stdin] = args else {
+        return None;
+    };
+    if flag != "--adopt" {
+        return None;
+    }
+    let fd = |arg: &str| -> Option<OwnedFd> {
+        let fd: RawFd = arg.parse().ok()?;
+        // Make sure it really was inherited, and don't pass it on to hooks and restarts
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return None;
+        }
+        Some(unsafe { OwnedFd::from_raw_fd(fd) })
+    };
+    let handover = Handover {
+        pid: pid.parse().ok()?,
+        start: if start == "-" { None } else { Some(start.parse().ok()?) },
+        stdout: fd(stdout)?,
+        stderr: fd(stderr)?,
+        stdin: if stdin == "-" { None } else { Some(fd(stdin)?) },
+        started_at: DateTime::parse_from_rfc3339(started_at).ok()?.with_timezone(&Local),
+    };
+    let run = if run == "-" { None } else { Some(run.parse().ok()?) };
+    Some((handover, run))
+}
+
+// Asks the server to exit, killing it if it is still around after `timeout`
+#[cfg(unix)]
+pub fn stop(detached: &Detached, timeout: Duration) {
+    let supervisor_running = || is_same(detached.supervisor_pid, detached.supervisor_start);
+    // Test servers have no process, their supervisor is the whole run
+    let (pid, start) = match detached.server_pid {
+        Some(pid) => (pid, detached.server_start),
+        None => (detached.supervisor_pid, detached.supervisor_start),
+    };
+    // Checked before every signal, the pids only come from a file
+    if is_same(pid, start) {
+        signal(pid, libc::SIGTERM);
+    }
+
+    let deadline = Instant::now() + timeout;
+    while supervisor_running() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+    if supervisor_running() {
+        if is_same(pid, start) {
+            signal(pid, libc::SIGKILL);
+        }
+        signal(detached.supervisor_pid, libc::SIGKILL);
+    }
+}
+
+#[cfg(unix)]
+enum RunEnd {
+    Exited,
+    Restart,
+}
+
+// Body of the supervisor process: run the server, write its output to the log files and
+// apply its triggers until it exits. With the arguments of `hand_over` it starts out with
+// the server a quitting launcher left running. Returns the process exit code.
+#[cfg(unix)]
+pub fn supervise(conn: &Connection, name: &str, args: &[String]) -> i32 {
+    let mut handover = match args {
+        [] => None,
+        args => match parse_handover(args) {
+            Some(handover) => Some(handover),
+            None => return 2,
+        },
+    };
+    let mut writer = LogWriter::new(logfile::LOG_DIR);
+    let server = match db::load_servers(conn) {
+        Ok(servers) => servers.into_iter().find(|server| server.name == name),
+        Err(_) => None,
+    };
+    let Some(server) = server else {
+        return 1;
+    };
+
+    // Kept across restarts, or a restart trigger that matches during startup would never cool down
+    let mut fired: HashMap<usize, Instant> = HashMap::new();
+    loop {
+        let (log_sender, log_receiver) = channel();
+        let (event_sender, event_receiver) = channel();
+        let (mut handle, run, note) = match handover.take() {
+            // The launcher already opened a run for it
+            Some((handover, run)) => (
+                servers::adopt(&server, handover, log_sender.clone(), event_sender),
+                run,
+                format!("Server {} was left running by the launcher, it runs in the background now.", server.name),
+            ),
+            None => {
+                let handle = match servers::launch(&server, log_sender.clone(), event_sender) {
+                    Ok(handle) => handle,
+                    Err(e) => {
+                        let _ = writer.write(&ServerMessage::new(
+                            &server.name,
+                            format!("Failed to launch server {}: {}", server.name, e),
+                            MessageType::Err,
+                        ));
+                        return 1;
+                    }
+                };
+                let run = db::start_run(conn, &server, handle.started_at).ok();
+                (handle, run, format!("Server {} launched in the background.", server.name))
+            }
+        };
+        let _ = write(&server.name, &handle);
+        let _ = writer.write(&ServerMessage::new(&server.name, note, MessageType::Main));
+
+        let mut exited = false;
+        let end = loop {
+            match log_receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok(message) => {
+                    let _ = writer.write(&message);
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break RunEnd::Exited,
+            }
+            let mut end = None;
+            while let Ok(event) = event_receiver.try_recv() {
+                match event {
+                    // Only test servers send it, a process is watched below
+                    ServerLifecycleEvent::Exited { .. } => {
+                        exited = true;
+                        end = Some(RunEnd::Exited);
+                    }
+                    ServerLifecycleEvent::Triggered { rule, action, cooldown, line, .. } => {
+                        if fired.get(&rule).is_some_and(|at| at.elapsed() < cooldown) {
+                            continue;
+                        }
+                        fired.insert(rule, Instant::now());
+                        let (message, result) =
+                            apply_trigger(conn, &server, &mut handle, action, &line, &log_sender);
+                        if let Some(message) = message {
+                            let _ = writer.write(&message);
+                        }
+                        end = end.or(result);
+                    }
+                }
+            }
+            if end.is_none() && handle.process_gone() {
+                exited = true;
+                end = Some(RunEnd::Exited);
+            }
+            if let Some(end) = end {
+                break end;
+            }
+        };
+
+        // An adopted server is not our child, once it is gone its pid may be someone else's
+        if !(exited && handle.adopted.is_some()) {
+            let _ = handle.kill_process();
+        }
+        // Whatever was printed before the exit still belongs in the log, the readers may
+        // not have got to the last lines yet
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while let Ok(message) =
+            log_receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            let _ = writer.write(&message);
+        }
+        if let Some(run) = run {
+            let _ = db::finish_run(conn, run, handle.exit_code());
+        }
+        let _ = writer.write(&ServerMessage::new(
+            &server.name,
+            format!("Server {} has Exited", server.name),
+            MessageType::Main,
+        ));
+
+        if let RunEnd::Exited = end {
+            let _ = fs::remove_file(pid_path(&server.name));
+            return 0;
+        }
+    }
+}
+
+// Same actions the TUI takes for a trigger, minus the ones that only make sense on screen
+#[cfg(unix)]
+fn apply_trigger(
+    conn: &Connection,
+    server: &Server,
+    handle: &mut ServerHandle,
+    action: TriggerAction,
+    line: &str,
+    log_sender: &std::sync::mpsc::Sender<ServerMessage>,
+) -> (Option<ServerMessage>, Option<RunEnd>) {
+    let note = |text: String| Some(ServerMessage::new(&server.name, text, MessageType::Main));
+    // Nothing to show a failed write on here, the supervisor has no terminal
+    let record = |action, detail: &str, outcome| {
+        let _ = audit::record(conn, &Identity::trigger(), action, &server.name, detail, outcome);
+    };
+    match action {
+        TriggerAction::Restart => {
+            record(AuditAction::Restart, "", Outcome::Ok);
+            (note(format!("Trigger: restarting {}", server.name)), Some(RunEnd::Restart))
+        }
+        TriggerAction::Stop => {
+            record(AuditAction::Kill, "", Outcome::Ok);
+            (note(format!("Trigger: stopping {}", server.name)), Some(RunEnd::Exited))
+        }
+        TriggerAction::SendCommand(command) => match handle.send_input(&command) {
+            Ok(_) => {
+                record(AuditAction::Command, &command, Outcome::Ok);
+                (note(format!("Trigger: sent `{}` to {}", command, server.name)), None)
+            }
+            Err(e) => {
+                record(AuditAction::Command, &command, Outcome::Failed(e.clone()));
+                (Some(ServerMessage::new(&server.name, e, MessageType::Err)), None)
+            }
+        },
+        TriggerAction::RunHook(hook) => {
+            servers::run_hook(server, &hook, line, log_sender.clone());
+            (None, None)
+        }
+        TriggerAction::Notify(text) => {
+            let text = if text.is_empty() { line.trim().to_string() } else { text };
+            (note(format!("[notify] {}: {}", server.name, text)), None)
+        }
+        TriggerAction::MarkReady => {
+            handle.ready = true;
+            (note(format!("Server {} is ready", server.name)), None)
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_reused_pid_is_not_the_recorded_process() {
+        let pid = std::process::id();
+        let start = start_time(pid);
+        assert!(start.is_some());
+        assert!(is_same(pid, start));
+        assert!(!is_same(pid, start.map(|start| start + 1)));
+    }
+}
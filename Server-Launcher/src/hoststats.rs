@@ -0,0 +1,419 @@
+use std::collections::{HashMap, VecDeque};
+#[cfg(unix)]
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+// Host wide numbers for the host stats page and the dashboard, read from procfs and sysfs
+
+// How many samples the network chart keeps
+const NET_HISTORY_LEN: usize = 120;
+
+pub struct Memory {
+    pub total_kb: u64,
+    pub available_kb: u64,
+    pub swap_total_kb: u64,
+    pub swap_free_kb: u64,
+}
+
+pub struct Disk {
+    pub mount: String,
+    pub servers: Vec<String>, // servers whose path lives on this mount
+    pub total: u64,           // bytes
+    pub available: u64,
+}
+
+pub struct Interface {
+    pub name: String,
+    pub rx_rate: f64, // bytes per second
+    pub tx_rate: f64,
+}
+
+pub struct Temperature {
+    pub label: String,
+    pub celsius: f64,
+}
+
+// Keeps the previous counters around so rates can be worked out between samples
+pub struct HostSampler {
+    last_cores: Vec<(u64, u64)>, // busy and total ticks of each core
+    last_net: HashMap<String, (u64, u64)>,
+    last_sample: Option<Instant>,
+    pub cores: Vec<u64>, // busy percent of each core
+    pub memory: Option<Memory>,
+    pub disks: Vec<Disk>,
+    pub interfaces: Vec<Interface>,
+    pub temperatures: Vec<Temperature>,
+    pub rx_history: VecDeque<(f64, f64)>, // seconds since start, total bytes per second
+    pub tx_history: VecDeque<(f64, f64)>,
+    started: Instant,
+}
+
+impl HostSampler {
+    pub fn new() -> HostSampler {
+        HostSampler {
+            last_cores: Vec::new(),
+            last_net: HashMap::new(),
+            last_sample: None,
+            cores: Vec::new(),
+            memory: None,
+            disks: Vec::new(),
+            interfaces: Vec::new(),
+            temperatures: Vec::new(),
+            rx_history: VecDeque::new(),
+            tx_history: VecDeque::new(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn last_sample(&self) -> Option<Instant> {
+        self.last_sample
+    }
+
+    // `paths` are the server directories, keyed by server name
+    pub fn sample(&mut self, paths: &[(String, String)]) {
+        let elapsed = self
+            .last_sample
+            .map_or(0.0, |last| last.elapsed().as_secs_f64());
+        self.last_sample = Some(Instant::now());
+
+        self.sample_cores();
+        self.memory = read_memory();
+        self.disks = read_disks(paths);
+        self.sample_net(elapsed);
+        self.temperatures = read_temperatures();
+    }
+
+    // Latest sample for the web dashboard
+    pub fn to_json(&self) -> Value {
+        let cpu = match self.cores.len() {
+            0 => 0.0,
+            n => self.cores.iter().sum::<u64>() as f64 / n as f64,
+        };
+        json!({
+            "cpu_percent": cpu,
+            "cores": self.cores,
+            "memory": self.memory.as_ref().map(|memory| json!({
+                "total_bytes": memory.total_kb * 1024,
+                "available_bytes": memory.available_kb * 1024,
+            })),
+            "disks": self.disks.iter().map(|disk| json!({
+                "mount": disk.mount,
+                "servers": disk.servers,
+                "total_bytes": disk.total,
+                "available_bytes": disk.available,
+            })).collect::<Vec<_>>(),
+            "network": {
+                "rx_rate": self.interfaces.iter().map(|interface| interface.rx_rate).sum::<f64>(),
+                "tx_rate": self.interfaces.iter().map(|interface| interface.tx_rate).sum::<f64>(),
+            },
+            "temperatures": self.temperatures.iter().map(|temperature| json!({
+                "label": temperature.label,
+                "celsius": temperature.celsius,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    fn sample_cores(&mut self) {
+        if let Ok(stat) = fs::read_to_string("/proc/stat") {
+            self.record_cores(&stat);
+        }
+    }
+
+    fn record_cores(&mut self, stat: &str) {
+        // cpu0 user nice system idle iowait irq softirq steal ...
+        let ticks: Vec<(u64, u64)> = stat
+            .lines()
+            .filter(|line| line.starts_with("cpu") && !line.starts_with("cpu "))
+            .map(|line| {
+                let values: Vec<u64> = line
+                    .split_whitespace()
+                    .skip(1)
+                    .filter_map(|v| v.parse().ok())
+                    .collect();
+                let total: u64 = values.iter().take(8).sum();
+                let idle = values.get(3).unwrap_or(&0) + values.get(4).unwrap_or(&0);
+                (total - idle, total)
+            })
+            .collect();
+
+        self.cores = ticks
+            .iter()
+            .enumerate()
+            .map(|(i, &(busy, total))| match self.last_cores.get(i) {
+                Some(&(last_busy, last_total)) if total > last_total => {
+                    busy.saturating_sub(last_busy) * 100 / (total - last_total)
+                }
+                _ => 0,
+            })
+            .collect();
+        self.last_cores = ticks;
+    }
+
+    fn sample_net(&mut self, elapsed: f64) {
+        if let Ok(dev) = fs::read_to_string("/proc/net/dev") {
+            self.record_net(&dev, elapsed);
+        }
+    }
+
+    fn record_net(&mut self, dev: &str, elapsed: f64) {
+        let mut interfaces = Vec::new();
+        let mut counters = HashMap::new();
+        for line in dev.lines().skip(2) {
+            let Some((name, values)) = line.split_once(':') else {
+                continue;
+            };
+            let name = name.trim().to_string();
+            let values: Vec<u64> = values
+                .split_whitespace()
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            if name == "lo" || values.len() < 9 {
+                continue;
+            }
+            let (rx, tx) = (values[0], values[8]);
+            let (rx_rate, tx_rate) = match self.last_net.get(&name) {
+                Some(&(last_rx, last_tx)) if elapsed > 0.0 => (
+                    rx.saturating_sub(last_rx) as f64 / elapsed,
+                    tx.saturating_sub(last_tx) as f64 / elapsed,
+                ),
+                _ => (0.0, 0.0),
+            };
+            counters.insert(name.clone(), (rx, tx));
+            interfaces.push(Interface { name, rx_rate, tx_rate });
+        }
+        self.last_net = counters;
+
+        let now = self.started.elapsed().as_secs_f64();
+        let rx: f64 = interfaces.iter().map(|i| i.rx_rate).sum();
+        let tx: f64 = interfaces.iter().map(|i| i.tx_rate).sum();
+        for (history, value) in [(&mut self.rx_history, rx), (&mut self.tx_history, tx)] {
+            history.push_back((now, value));
+            if history.len() > NET_HISTORY_LEN {
+                history.pop_front();
+            }
+        }
+        self.interfaces = interfaces;
+    }
+}
+
+fn read_memory() -> Option<Memory> {
+    Some(parse_memory(&fs::read_to_string("/proc/meminfo").ok()?))
+}
+
+fn parse_memory(meminfo: &str) -> Memory {
+    let value = |key: &str| -> u64 {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|rest| rest.trim_start_matches(':').split_whitespace().next())
+            .and_then(|kb| kb.parse().ok())
+            .unwrap_or(0)
+    };
+    Memory {
+        total_kb: value("MemTotal"),
+        available_kb: value("MemAvailable"),
+        swap_total_kb: value("SwapTotal"),
+        swap_free_kb: value("SwapFree"),
+    }
+}
+
+// The mount point the path lives on, the longest mounted prefix of it
+fn mount_of(path: &Path, mounts: &[PathBuf]) -> Option<PathBuf> {
+    let path = path.canonicalize().ok()?;
+    mounts
+        .iter()
+        .filter(|mount| path.starts_with(mount))
+        .max_by_key(|mount| mount.as_os_str().len())
+        .cloned()
+}
+
+fn read_disks(paths: &[(String, String)]) -> Vec<Disk> {
+    let mounts = parse_mounts(&fs::read_to_string("/proc/self/mounts").unwrap_or_default());
+
+    let mut disks: Vec<Disk> = Vec::new();
+    for (name, path) in paths {
+        let Some(mount) = mount_of(Path::new(path), &mounts) else {
+            continue;
+        };
+        let mount = mount.display().to_string();
+        if let Some(disk) = disks.iter_mut().find(|disk| disk.mount == mount) {
+            disk.servers.push(name.clone());
+            continue;
+        }
+        if let Some((total, available)) = disk_space(&mount) {
+            disks.push(Disk {
+                mount,
+                servers: vec![name.clone()],
+                total,
+                available,
+            });
+        }
+    }
+    disks
+}
+
+fn parse_mounts(mounts: &str) -> Vec<PathBuf> {
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        // Spaces in mount points are escaped as \040
+        .map(|mount| PathBuf::from(mount.replace("\\040", " ")))
+        .collect()
+}
+
+#[cfg(unix)]
+fn disk_space(mount: &str) -> Option<(u64, u64)> {
+    let path = CString::new(mount).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid C string and `stat` is a properly sized out parameter
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    Some((stat.f_blocks as u64 * block, stat.f_bavail as u64 * block))
+}
+
+// Mounts come from /proc/mounts, which only Linux has
+#[cfg(not(unix))]
+fn disk_space(_mount: &str) -> Option<(u64, u64)> {
+    None
+}
+
+fn read_millidegrees(path: &Path) -> Option<f64> {
+    let text = fs::read_to_string(path).ok()?;
+    Some(text.trim().parse::<f64>().ok()? / 1000.0)
+}
+
+fn read_temperatures() -> Vec<Temperature> {
+    let mut temperatures = Vec::new();
+
+    // /sys/class/thermal/thermal_zoneN/{type,temp}
+    if let Ok(zones) = fs::read_dir("/sys/class/thermal") {
+        for zone in zones.flatten() {
+            let path = zone.path();
+            if !zone.file_name().to_string_lossy().starts_with("thermal_zone") {
+                continue;
+            }
+            if let Some(celsius) = read_millidegrees(&path.join("temp")) {
+                let label = fs::read_to_string(path.join("type"))
+                    .map(|kind| kind.trim().to_string())
+                    .unwrap_or_else(|_| zone.file_name().to_string_lossy().to_string());
+                temperatures.push(Temperature { label, celsius });
+            }
+        }
+    }
+
+    // /sys/class/hwmon/hwmonN/{name,tempM_input,tempM_label}
+    if let Ok(monitors) = fs::read_dir("/sys/class/hwmon") {
+        for monitor in monitors.flatten() {
+            let path = monitor.path();
+            let chip = fs::read_to_string(path.join("name"))
+                .map(|name| name.trim().to_string())
+                .unwrap_or_default();
+            let Ok(files) = fs::read_dir(&path) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let file_name = file.file_name().to_string_lossy().to_string();
+                let Some(sensor) = file_name
+                    .strip_prefix("temp")
+                    .and_then(|rest| rest.strip_suffix("_input"))
+                else {
+                    continue;
+                };
+                let Some(celsius) = read_millidegrees(&file.path()) else {
+                    continue;
+                };
+                let label = fs::read_to_string(path.join(format!("temp{}_label", sensor)))
+                    .map(|label| format!("{} {}", chip, label.trim()))
+                    .unwrap_or_else(|_| format!("{} temp{}", chip, sensor));
+                temperatures.push(Temperature { label, celsius });
+            }
+        }
+    }
+
+    temperatures.sort_by(|a, b| a.label.cmp(&b.label));
+    temperatures
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_usage_is_the_busy_share_between_samples() {
+        let mut sampler = HostSampler::new();
+        sampler.record_cores("cpu  0 0 0 0 0\ncpu0 100 0 100 800 0 0 0 0\ncpu1 0 0 0 1000 0 0 0 0\n");
+        // Nothing to compare against on the first sample
+        assert_eq!(sampler.cores, [0, 0]);
+        sampler.record_cores("cpu  0 0 0 0 0\ncpu0 150 0 150 900 0 0 0 0\ncpu1 0 0 0 1200 0 0 0 0\n");
+        assert_eq!(sampler.cores, [50, 0]);
+    }
+
+    #[test]
+    fn network_rates_skip_loopback() {
+        let dev = |rx: u64, tx: u64| {
+            format!(
+                "Inter-|   Receive |  Transmit\n face |bytes packets errs drop fifo frame compressed multicast|bytes\n    lo: 999 1 0 0 0 0 0 0 999 1 0 0 0 0 0 0\n  eth0: {} 10 0 0 0 0 0 0 {} 5 0 0 0 0 0 0\n",
+                rx, tx
+            )
+        };
+        let mut sampler = HostSampler::new();
+        sampler.record_net(&dev(1000, 500), 0.0);
+        sampler.record_net(&dev(3000, 1500), 2.0);
+        assert_eq!(sampler.interfaces.len(), 1);
+        assert_eq!(sampler.interfaces[0].name, "eth0");
+        assert_eq!(sampler.interfaces[0].rx_rate, 1000.0);
+        assert_eq!(sampler.interfaces[0].tx_rate, 500.0);
+        assert_eq!(sampler.rx_history.back().map(|&(_, rate)| rate), Some(1000.0));
+    }
+
+    #[test]
+    fn memory_reads_meminfo_fields() {
+        let memory = parse_memory(
+            "MemTotal:       16000000 kB\nMemFree:         1000000 kB\nMemAvailable:    8000000 kB\nSwapTotal:       2000000 kB\nSwapFree:        1500000 kB\n",
+        );
+        assert_eq!(memory.total_kb, 16000000);
+        assert_eq!(memory.available_kb, 8000000);
+        assert_eq!(memory.swap_total_kb, 2000000);
+        assert_eq!(memory.swap_free_kb, 1500000);
+        // Missing fields read as zero
+        assert_eq!(parse_memory("").total_kb, 0);
+    }
+
+    #[test]
+    fn mount_points_unescape_spaces() {
+        let mounts = parse_mounts("/dev/sda1 / ext4 rw 0 0\n/dev/sdb1 /srv/game\\040servers ext4 rw 0 0\n");
+        assert_eq!(mounts, [PathBuf::from("/"), PathBuf::from("/srv/game servers")]);
+    }
+
+    #[test]
+    fn paths_belong_to_the_longest_mount() {
+        let tmp = std::env::temp_dir().canonicalize().unwrap();
+        let mounts = [PathBuf::from("/"), tmp.clone()];
+        assert_eq!(mount_of(&tmp.join("."), &mounts), Some(tmp));
+        assert_eq!(mount_of(Path::new("/does/not/exist"), &mounts), None);
+    }
+
+    #[test]
+    fn bytes_use_the_largest_fitting_unit() {
+        assert_eq!(format_bytes(512.0), "512.0 B");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(3.0 * 1024.0 * 1024.0 * 1024.0), "3.0 GiB");
+    }
+}
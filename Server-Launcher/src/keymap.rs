@@ -0,0 +1,600 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde_json::Value;
+
+// Which keys do what in the TUI pages. A preset gives every action its keys and the keymap
+// file (keys.json next to the database by default) can rebind any of them, e.g.
+//   { "launch": ["Enter", "Ctrl+l"], "kill": "Alt+x", "export_logs": [] }
+// Dialogs, the server form and the search prompt keep their own keys, and F1-F6 always
+// switch pages.
+
+// Every action the pages and the global keys know about
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Quit,
+    NextPage,
+    PrevPage,
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    Launch,
+    Kill,
+    Restart,
+    AddServer,
+    EditServer,
+    DeleteServer,
+    ClearLogs,
+    Search,
+    NextMatch,
+    PrevMatch,
+    FilterMatches,
+    CaseSensitive,
+    SelectedOnly,
+    Timestamps,
+    ExportLogs,
+    ToggleError,
+    ToggleWarn,
+    ToggleInfo,
+    ToggleDebug,
+    ToggleChat,
+    ToggleStdout,
+    ToggleStderr,
+    ToggleLauncher,
+    ShowAllLogs,
+}
+
+impl Action {
+    pub const ALL: [Action; 35] = [
+        Action::Quit,
+        Action::NextPage,
+        Action::PrevPage,
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Top,
+        Action::Bottom,
+        Action::Launch,
+        Action::Kill,
+        Action::Restart,
+        Action::AddServer,
+        Action::EditServer,
+        Action::DeleteServer,
+        Action::ClearLogs,
+        Action::Search,
+        Action::NextMatch,
+        Action::PrevMatch,
+        Action::FilterMatches,
+        Action::CaseSensitive,
+        Action::SelectedOnly,
+        Action::Timestamps,
+        Action::ExportLogs,
+        Action::ToggleError,
+        Action::ToggleWarn,
+        Action::ToggleInfo,
+        Action::ToggleDebug,
+        Action::ToggleChat,
+        Action::ToggleStdout,
+        Action::ToggleStderr,
+        Action::ToggleLauncher,
+        Action::ShowAllLogs,
+    ];
+
+    // As written in the keymap file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::NextPage => "next_page",
+            Action::PrevPage => "prev_page",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::Launch => "launch",
+            Action::Kill => "kill",
+            Action::Restart => "restart",
+            Action::AddServer => "add_server",
+            Action::EditServer => "edit_server",
+            Action::DeleteServer => "delete_server",
+            Action::ClearLogs => "clear_logs",
+            Action::Search => "search",
+            Action::NextMatch => "next_match",
+            Action::PrevMatch => "prev_match",
+            Action::FilterMatches => "filter_matches",
+            Action::CaseSensitive => "case_sensitive",
+            Action::SelectedOnly => "selected_only",
+            Action::Timestamps => "timestamps",
+            Action::ExportLogs => "export_logs",
+            Action::ToggleError => "toggle_error",
+            Action::ToggleWarn => "toggle_warn",
+            Action::ToggleInfo => "toggle_info",
+            Action::ToggleDebug => "toggle_debug",
+            Action::ToggleChat => "toggle_chat",
+            Action::ToggleStdout => "toggle_stdout",
+            Action::ToggleStderr => "toggle_stderr",
+            Action::ToggleLauncher => "toggle_launcher",
+            Action::ShowAllLogs => "show_all_logs",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name.trim())
+    }
+
+    // Keys every preset agrees on
+    fn common_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q", "Esc"],
+            Action::NextPage => &["Tab"],
+            Action::PrevPage => &["Shift+Tab"],
+            Action::Up => &["Up"],
+            Action::Down => &["Down"],
+            Action::Left => &["Left"],
+            Action::Right => &["Right"],
+            Action::PageUp => &["PgUp"],
+            Action::PageDown => &["PgDn"],
+            Action::Top => &["Home"],
+            Action::Bottom => &["End"],
+            Action::Launch => &["Enter"],
+            Action::Kill => &["x"],
+            Action::Restart => &["r"],
+            Action::AddServer => &["Space"],
+            Action::EditServer => &["e"],
+            Action::DeleteServer => &["d", "Delete"],
+            Action::ClearLogs => &["c"],
+            Action::Search => &["/"],
+            Action::NextMatch => &["n"],
+            Action::PrevMatch => &["N"],
+            Action::FilterMatches => &["f"],
+            Action::CaseSensitive => &["i"],
+            Action::SelectedOnly => &["s"],
+            Action::Timestamps => &["t"],
+            Action::ExportLogs => &["w"],
+            Action::ToggleError => &["1"],
+            Action::ToggleWarn => &["2"],
+            Action::ToggleInfo => &["3"],
+            Action::ToggleDebug => &["4"],
+            Action::ToggleChat => &["5"],
+            Action::ToggleStdout => &["6"],
+            Action::ToggleStderr => &["7"],
+            Action::ToggleLauncher => &["8"],
+            Action::ShowAllLogs => &["0"],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum KeyPreset {
+    Vim,    // hjkl, g/G and Ctrl+u/d on top of the arrow keys
+    Arrows, // arrow keys, PgUp/PgDn and Home/End only
+}
+
+impl KeyPreset {
+    pub const ALL: [KeyPreset; 2] = [KeyPreset::Vim, KeyPreset::Arrows];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyPreset::Vim => "vim",
+            KeyPreset::Arrows => "arrows",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<KeyPreset> {
+        KeyPreset::ALL.into_iter().find(|preset| preset.name() == name.trim())
+    }
+
+    // Goes before the common keys, so these are the ones the controls panel shows first
+    fn extra_keys(self, action: Action) -> &'static [&'static str] {
+        match (self, action) {
+            (KeyPreset::Vim, Action::Up) => &["k"],
+            (KeyPreset::Vim, Action::Down) => &["j"],
+            (KeyPreset::Vim, Action::Left) => &["h"],
+            (KeyPreset::Vim, Action::Right) => &["l"],
+            (KeyPreset::Vim, Action::PageUp) => &["Ctrl+u"],
+            (KeyPreset::Vim, Action::PageDown) => &["Ctrl+d"],
+            (KeyPreset::Vim, Action::Top) => &["g"],
+            (KeyPreset::Vim, Action::Bottom) => &["G"],
+            _ => &[],
+        }
+    }
+
+    fn keys(self, action: Action) -> Vec<Key> {
+        self.extra_keys(action)
+            .iter()
+            .chain(action.common_keys())
+            .filter_map(|name| Key::parse(name))
+            .collect()
+    }
+}
+
+// A key with the modifiers held down. Shift is part of the character for letters, so `J` is
+// shift+j and `Shift+Tab` is BackTab.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const MODIFIERS: [(&str, KeyModifiers); 3] = [
+    ("Ctrl", KeyModifiers::CONTROL),
+    ("Alt", KeyModifiers::ALT),
+    ("Shift", KeyModifiers::SHIFT),
+];
+
+const NAMED_KEYS: [(&str, KeyCode); 14] = [
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Space", KeyCode::Char(' ')),
+    ("Tab", KeyCode::Tab),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PgUp", KeyCode::PageUp),
+    ("PgDn", KeyCode::PageDown),
+];
+
+impl Key {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::Char(c.to_ascii_uppercase()),
+            code => code,
+        };
+        if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Key { code, modifiers }
+    }
+
+    // What a terminal sent, the local one reports shift on capitals and BackTab, remote ones don't
+    pub fn from_event(key: &KeyEvent) -> Key {
+        Key::new(key.code, key.modifiers)
+    }
+
+    pub fn event(self) -> KeyEvent {
+        KeyEvent::new(self.code, self.modifiers)
+    }
+
+    // "x", "N", "Enter", "Ctrl+d", "Alt+Shift+Up", "F12". Case matters for single characters
+    // only.
+    pub fn parse(text: &str) -> Option<Key> {
+        let text = text.trim();
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // A lone "+" is the plus key, "Ctrl++" is ctrl and plus
+        while let Some((prefix, after)) = rest.split_once('+').filter(|(_, after)| !after.is_empty()) {
+            let (_, modifier) = MODIFIERS.iter().find(|(name, _)| name.eq_ignore_ascii_case(prefix))?;
+            modifiers |= *modifier;
+            rest = after;
+        }
+        let code = if rest.chars().count() == 1 {
+            KeyCode::Char(rest.chars().next()?)
+        } else if let Some((_, code)) = NAMED_KEYS.iter().find(|(name, _)| name.eq_ignore_ascii_case(rest)) {
+            *code
+        } else {
+            KeyCode::F(rest.strip_prefix(['F', 'f'])?.parse().ok()?)
+        };
+        Some(Key::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, modifier) in MODIFIERS {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::F(number) => write!(f, "F{}", number),
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            code => match NAMED_KEYS.iter().find(|(_, named)| *named == code) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "?"),
+            },
+        }
+    }
+}
+
+// One "(keys) Label" part of a line in the controls panel
+pub enum Hint {
+    Actions(&'static [Action], &'static str),
+    Fixed(&'static str, &'static str), // keys the keymap doesn't cover, like F1-F6 or clicks
+}
+
+pub struct Keymap {
+    pub preset: KeyPreset,
+    actions: HashMap<Key, Action>,
+    keys: HashMap<Action, Vec<Key>>, // in the order they were given, for the controls panel
+}
+
+impl Keymap {
+    pub fn preset(preset: KeyPreset) -> Keymap {
+        Keymap::build(preset, HashMap::new()).0
+    }
+
+    // The preset with the file's bindings on top. A missing file is fine, anything wrong in
+    // it comes back as one problem per line and the rest still applies.
+    pub fn load(preset: KeyPreset, path: &Path) -> (Keymap, Vec<String>) {
+        let mut problems = Vec::new();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return (Keymap::preset(preset), problems),
+            Err(e) => {
+                problems.push(format!("could not read {}: {}", path.display(), e));
+                return (Keymap::preset(preset), problems);
+            }
+        };
+        let bindings = match serde_json::from_str::<Value>(&text) {
+            Ok(Value::Object(bindings)) => bindings,
+            Ok(_) => {
+                problems.push(format!("{} should be an object of action names", path.display()));
+                return (Keymap::preset(preset), problems);
+            }
+            Err(e) => {
+                problems.push(format!("{} is not valid JSON: {}", path.display(), e));
+                return (Keymap::preset(preset), problems);
+            }
+        };
+
+        let mut overrides = HashMap::new();
+        for (name, value) in bindings {
+            let Some(action) = Action::parse(&name) else {
+                problems.push(format!("unknown action `{}`", name));
+                continue;
+            };
+            let names: Vec<&str> = match &value {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => {
+                    problems.push(format!("{} should be a key or a list of keys", name));
+                    continue;
+                }
+            };
+            let mut keys = Vec::new();
+            for key_name in names {
+                match Key::parse(key_name) {
+                    Some(Key { code: KeyCode::F(_), .. }) => {
+                        problems.push(format!("{} for {}: function keys switch pages", key_name, name))
+                    }
+                    Some(key) => keys.push(key),
+                    None => problems.push(format!("{} for {} is not a key", key_name, name)),
+                }
+            }
+            overrides.insert(action, keys);
+        }
+
+        let (keymap, conflicts) = Keymap::build(preset, overrides);
+        problems.extend(conflicts);
+        (keymap, problems)
+    }
+
+    // Rebound actions claim their keys first, so a key taken from a preset action goes to the
+    // one the file gave it. Every key bound twice is a conflict, the first claim keeps it.
+    fn build(preset: KeyPreset, overrides: HashMap<Action, Vec<Key>>) -> (Keymap, Vec<String>) {
+        let mut keymap = Keymap {
+            preset,
+            actions: HashMap::new(),
+            keys: HashMap::new(),
+        };
+        let mut conflicts = Vec::new();
+        let (rebound, from_preset): (Vec<Action>, Vec<Action>) =
+            Action::ALL.into_iter().partition(|action| overrides.contains_key(action));
+        for action in rebound.into_iter().chain(from_preset) {
+            let wanted = overrides.get(&action).cloned().unwrap_or_else(|| preset.keys(action));
+            let mut keys = Vec::new();
+            for key in wanted {
+                match keymap.actions.get(&key) {
+                    Some(&other) if other != action => conflicts.push(format!(
+                        "{} is bound to both {} and {}, {} keeps it",
+                        key,
+                        other.name(),
+                        action.name(),
+                        other.name()
+                    )),
+                    Some(_) => {}
+                    None => {
+                        keymap.actions.insert(key, action);
+                        keys.push(key);
+                    }
+                }
+            }
+            keymap.keys.insert(action, keys);
+        }
+        (keymap, conflicts)
+    }
+
+    // Capitals that aren't bound themselves do what their lowercase letter does, so caps
+    // lock doesn't get in the way
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        let key = Key::from_event(key);
+        self.actions.get(&key).copied().or_else(|| match key.code {
+            KeyCode::Char(c) if c.is_ascii_uppercase() => self
+                .actions
+                .get(&Key::new(KeyCode::Char(c.to_ascii_lowercase()), key.modifiers))
+                .copied(),
+            _ => None,
+        })
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    // A controls line as the keys are bound right now. Unbound actions are left out.
+    pub fn describe(&self, hints: &[Hint]) -> String {
+        let mut parts = Vec::new();
+        for hint in hints {
+            match hint {
+                Hint::Actions(actions, label) => {
+                    let keys: Vec<String> = actions
+                        .iter()
+                        .map(|&action| {
+                            let names: Vec<String> = self.keys(action).iter().map(Key::to_string).collect();
+                            names.join("/")
+                        })
+                        .filter(|names| !names.is_empty())
+                        .collect();
+                    if !keys.is_empty() {
+                        parts.push(format!("({}) {}", keys.join(", "), label));
+                    }
+                }
+                Hint::Fixed(keys, label) => parts.push(format!("({}) {}", keys, label)),
+            }
+        }
+        parts.join(" | ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(text: &str) -> Key {
+        Key::parse(text).unwrap()
+    }
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(key("x"), Key::new(KeyCode::Char('x'), KeyModifiers::NONE));
+        assert_eq!(key("ctrl+d"), Key::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert_eq!(key("Alt+Shift+Up"), Key::new(KeyCode::Up, KeyModifiers::ALT | KeyModifiers::SHIFT));
+        assert_eq!(key("pgdn"), Key::new(KeyCode::PageDown, KeyModifiers::NONE));
+        assert_eq!(key("F12"), Key::new(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(key("+"), Key::new(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(key("Ctrl++"), Key::new(KeyCode::Char('+'), KeyModifiers::CONTROL));
+        // Shift is folded into capitals and BackTab
+        assert_eq!(key("Shift+j"), key("J"));
+        assert_eq!(key("Shift+Tab"), Key::new(KeyCode::BackTab, KeyModifiers::NONE));
+        assert_ne!(key("n"), key("N"));
+        for bad in ["", "Hyper+x", "Ctrl+", "Foo", "Fx", "xy"] {
+            assert_eq!(Key::parse(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn names_parse_back() {
+        for text in ["x", "N", "Enter", "Space", "Ctrl+u", "Alt+Shift+Up", "Shift+Tab", "F5", "Ctrl++"] {
+            assert_eq!(key(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn events_from_any_terminal() {
+        // The local terminal reports shift with capitals and BackTab, SSH clients don't
+        for modifiers in [KeyModifiers::NONE, KeyModifiers::SHIFT] {
+            assert_eq!(Key::from_event(&event(KeyCode::Char('G'), modifiers)), key("G"));
+            assert_eq!(Key::from_event(&event(KeyCode::BackTab, modifiers)), key("Shift+Tab"));
+        }
+        assert_eq!(Key::from_event(&key("Ctrl+d").event()), key("Ctrl+d"));
+    }
+
+    #[test]
+    fn presets_bind_every_action_once() {
+        for preset in KeyPreset::ALL {
+            let (keymap, conflicts) = Keymap::build(preset, HashMap::new());
+            assert!(conflicts.is_empty(), "{}: {:?}", preset.name(), conflicts);
+            for action in Action::ALL {
+                assert!(!keymap.keys(action).is_empty(), "{} has no keys", action.name());
+                assert_eq!(Action::parse(action.name()), Some(action));
+            }
+        }
+        let vim = Keymap::preset(KeyPreset::Vim);
+        assert_eq!(vim.action(&key("j").event()), Some(Action::Down));
+        assert_eq!(Keymap::preset(KeyPreset::Arrows).action(&key("j").event()), None);
+    }
+
+    #[test]
+    fn rebinding_takes_the_key_from_the_preset() {
+        let overrides = HashMap::from([(Action::Kill, vec![key("r")])]);
+        let (keymap, conflicts) = Keymap::build(KeyPreset::Vim, overrides);
+        assert_eq!(conflicts, ["r is bound to both kill and restart, kill keeps it"]);
+        assert_eq!(keymap.action(&key("r").event()), Some(Action::Kill));
+        assert_eq!(keymap.action(&key("x").event()), None);
+        assert!(keymap.keys(Action::Restart).is_empty());
+    }
+
+    #[test]
+    fn two_rebinds_of_one_key_conflict() {
+        let overrides = HashMap::from([(Action::Launch, vec![key("z")]), (Action::Kill, vec![key("z")])]);
+        let (keymap, conflicts) = Keymap::build(KeyPreset::Vim, overrides);
+        assert_eq!(conflicts, ["z is bound to both launch and kill, launch keeps it"]);
+        assert_eq!(keymap.action(&key("z").event()), Some(Action::Launch));
+    }
+
+    #[test]
+    fn capitals_fall_back_to_lowercase() {
+        let keymap = Keymap::preset(KeyPreset::Vim);
+        assert_eq!(keymap.action(&key("X").event()), Some(Action::Kill));
+        // Unless the capital is bound itself
+        assert_eq!(keymap.action(&key("G").event()), Some(Action::Bottom));
+        assert_eq!(keymap.action(&key("N").event()), Some(Action::PrevMatch));
+    }
+
+    #[test]
+    fn keymap_file() {
+        let path = std::env::temp_dir().join(format!("keymap-test-{}.json", std::process::id()));
+        let (_, problems) = Keymap::load(KeyPreset::Vim, &path);
+        assert!(problems.is_empty());
+
+        let bindings = r#"{"kill": "Ctrl+k", "down": ["j", "Down", "F3", "Hyper+j"], "jump": "J", "up": 5}"#;
+        fs::write(&path, bindings).unwrap();
+        let (keymap, mut problems) = Keymap::load(KeyPreset::Vim, &path);
+        fs::remove_file(&path).unwrap();
+        problems.sort();
+        assert_eq!(
+            problems,
+            [
+                "F3 for down: function keys switch pages",
+                "Hyper+j for down is not a key",
+                "unknown action `jump`",
+                "up should be a key or a list of keys",
+            ]
+        );
+        assert_eq!(keymap.keys(Action::Kill), [key("Ctrl+k")]);
+        assert_eq!(keymap.keys(Action::Down), [key("j"), key("Down")]);
+        assert_eq!(keymap.action(&key("x").event()), None);
+        // What the file got wrong keeps the preset's keys
+        assert_eq!(keymap.keys(Action::Up), [key("k"), key("Up")]);
+    }
+
+    #[test]
+    fn controls_line() {
+        let overrides = HashMap::from([(Action::ExportLogs, Vec::new())]);
+        let (keymap, _) = Keymap::build(KeyPreset::Arrows, overrides);
+        let hints = [
+            Hint::Actions(&[Action::Launch, Action::Kill], "Launch/Kill"),
+            Hint::Actions(&[Action::ExportLogs], "Export"),
+            Hint::Fixed("F1-F6", "Go to Page"),
+        ];
+        assert_eq!(keymap.describe(&hints), "(Enter, x) Launch/Kill | (F1-F6) Go to Page");
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, SecondsFormat};
+
+use crate::servers::{LogStream, ServerMessage};
+
+// Every server gets its own file in here, launcher messages go to launcher.log
+pub const LOG_DIR: &str = "logs";
+
+// One line per message, the same format for persisted logs and exports
+pub fn format_line(message: &ServerMessage) -> String {
+    let mut tags = String::new();
+    if let Some(level) = message.level {
+        tags.push_str(&format!(" {}", level.label()));
+    }
+    if message.stream == LogStream::Stderr {
+        tags.push_str(&format!(" {}", message.stream.label()));
+    }
+    format!(
+        "{} [{}]{} {}",
+        message
+            .timestamp
+            .to_rfc3339_opts(SecondsFormat::Millis, false),
+        source_name(message),
+        tags,
+        message.contents.trim_start()
+    )
+}
+
+fn source_name(message: &ServerMessage) -> &str {
+    if message.name.is_empty() {
+        "launcher"
+    } else {
+        &message.name
+    }
+}
+
+// Server names are free text. Anything but letters, digits, - and _ is written as %XX, % too,
+// so no two names share a file.
+pub fn escape_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+fn file_name(name: &str) -> String {
+    format!("{}.log", escape_name(name))
+}
+
+// Where the messages of the server (or "" for the launcher) are persisted
+pub fn log_path(name: &str) -> PathBuf {
+    let name = if name.is_empty() { "launcher" } else { name };
+    Path::new(LOG_DIR).join(file_name(name))
+}
+
+// Appends messages to per server files, keeping them open between writes
+pub struct LogWriter {
+    dir: PathBuf,
+    files: HashMap<String, File>,
+}
+
+impl LogWriter {
+    pub fn new(dir: &str) -> LogWriter {
+        LogWriter {
+            dir: PathBuf::from(dir),
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn write(&mut self, message: &ServerMessage) -> io::Result<()> {
+        let name = source_name(message).to_string();
+        if !self.files.contains_key(&name) {
+            fs::create_dir_all(&self.dir)?;
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.dir.join(file_name(&name)))?;
+            self.files.insert(name.clone(), file);
+        }
+
+        let file = self.files.get_mut(&name).expect("log file was just opened");
+        writeln!(file, "{}", format_line(message))
+    }
+}
+
+// Writes the given messages to a new timestamped file and returns its path
+pub fn export<'a>(messages: impl Iterator<Item = &'a ServerMessage>) -> io::Result<PathBuf> {
+    fs::create_dir_all(LOG_DIR)?;
+    let path = Path::new(LOG_DIR).join(format!(
+        "export-{}.log",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    let mut file = File::create(&path)?;
+    for message in messages {
+        writeln!(file, "{}", format_line(message))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_that_differ_get_different_files() {
+        assert_eq!(escape_name("web-1_a"), "web-1_a");
+        assert_eq!(escape_name("Minecraft Server"), "Minecraft%20Server");
+        assert_ne!(file_name("a b"), file_name("a_b"));
+        assert_ne!(file_name("a%20b"), file_name("a b"));
+        assert_eq!(file_name("../etc"), "%2E%2E%2Fetc.log");
+    }
+}
@@ -1,20 +1,52 @@
+mod api;
+mod audit;
+mod auth;
+mod classify;
+mod cli;
+mod control;
 mod db;
+mod detached;
+mod hoststats;
+mod keymap;
+mod logfile;
+mod metrics;
+mod procinfo;
+mod serverdef;
 mod servers;
+mod settings;
+#[cfg(unix)]
+mod socket;
+#[cfg(not(unix))]
+#[path = "socket_unsupported.rs"]
+mod socket;
+mod stream;
+mod triggers;
 mod tui;
+mod usage;
+mod web;
 
 fn main() -> std::io::Result<()> {
     // get server states from local db
-    let conn = db::connect_db("path_to_db");
-    match conn {
-        Ok(connection) => {
-            let available_Servers = db::load_servers(&connection);
+    let conn = match db::connect_db(db::DB_PATH) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("Error geting servers: {}", e);
+            std::process::exit(1);
         }
-        _ => {
-            println!("Error geting servers")
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None | Some("tui") => {}
+        // Internal, runs a server started with `start` or left running on quit in the background
+        #[cfg(unix)]
+        Some("supervise") if args.len() >= 2 => {
+            std::process::exit(detached::supervise(&conn, &args[1], &args[2..]))
         }
+        Some(_) => std::process::exit(cli::run(&conn, &args)),
     }
 
-    if let Err(e) = tui::init_tui() {
+    if let Err(e) = tui::init_tui(conn) {
         eprintln!("Application error: {}", e);
         // Optionally, perform any other cleanup before exiting
         std::process::exit(1);
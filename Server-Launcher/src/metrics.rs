@@ -0,0 +1,101 @@
+use std::fmt::Write;
+
+use serde_json::Value;
+
+// Prometheus text format for /metrics, built from the UI loop's answer to ControlCommand::Metrics
+
+const PREFIX: &str = "server_launcher";
+const STATES: [&str; 3] = ["stopped", "starting", "running"];
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// One metric with its help line, samples without a value are left out
+fn family(out: &mut String, name: &str, kind: &str, help: &str, samples: Vec<(String, Option<f64>)>) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+    for (labels, value) in samples {
+        if let Some(value) = value {
+            let _ = writeln!(out, "{}_{}{} {}", PREFIX, name, labels, value);
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub fn render(snapshot: &Value) -> String {
+    let mut out = String::new();
+    let servers = snapshot["servers"].as_array().cloned().unwrap_or_default();
+    let launcher = |key: &str| vec![(String::new(), snapshot[key].as_f64())];
+
+    family(&mut out, "uptime_seconds", "gauge", "Seconds since the launcher started", launcher("uptime_secs"));
+    family(&mut out, "servers", "gauge", "Configured servers", vec![(String::new(), Some(servers.len() as f64))]);
+    family(&mut out, "servers_running", "gauge", "Servers running or starting", launcher("servers_running"));
+    family(&mut out, "stream_clients", "gauge", "Open log stream connections", launcher("stream_clients"));
+    family(&mut out, "log_lines_total", "counter", "Lines logged by all servers and the launcher", launcher("log_lines"));
+
+    let per_server = |key: &str| -> Vec<(String, Option<f64>)> {
+        servers
+            .iter()
+            .map(|server| {
+                let name = escape(server["name"].as_str().unwrap_or_default());
+                (format!("{{server=\"{}\"}}", name), server[key].as_f64())
+            })
+            .collect()
+    };
+    let states = servers
+        .iter()
+        .flat_map(|server| {
+            let name = escape(server["name"].as_str().unwrap_or_default());
+            STATES.into_iter().map(move |state| {
+                let value = if server["state"] == state { 1.0 } else { 0.0 };
+                (format!("{{server=\"{}\",state=\"{}\"}}", name, state), Some(value))
+            })
+        })
+        .collect();
+
+    family(&mut out, "server_state", "gauge", "1 for the state the server is in", states);
+    family(&mut out, "server_uptime_seconds", "gauge", "Seconds since the server was started", per_server("uptime_secs"));
+    family(&mut out, "server_restarts_total", "counter", "Restarts since the launcher started", per_server("restarts"));
+    family(&mut out, "server_cpu_percent", "gauge", "CPU use in percent of one core, child processes included", per_server("cpu_percent"));
+    family(&mut out, "server_memory_rss_bytes", "gauge", "Resident memory, child processes included", per_server("rss_bytes"));
+    family(&mut out, "server_log_lines_total", "counter", "Lines logged since the launcher started", per_server("log_lines"));
+    family(&mut out, "server_log_lines_per_second", "gauge", "Lines logged over the last second", per_server("log_lines_per_second"));
+    family(&mut out, "server_last_exit_code", "gauge", "Exit code of the previous run, negative for a signal", per_server("last_exit_code"));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape(r#"say "hi" \o/"#), r#"say \"hi\" \\o/"#);
+        assert_eq!(escape("two\nlines"), "two\\nlines");
+
+        let out = render(&json!({"servers": [{"name": "The \"Best\" Server", "state": "running"}]}));
+        assert!(out.contains(r#"server_launcher_server_state{server="The \"Best\" Server",state="running"} 1"#));
+        assert!(out.contains(r#"server_launcher_server_state{server="The \"Best\" Server",state="stopped"} 0"#));
+    }
+
+    #[test]
+    fn missing_values_leave_out_the_sample() {
+        let out = render(&json!({
+            "uptime_secs": 90,
+            "servers": [
+                {"name": "Web", "state": "running", "cpu_percent": 12.5},
+                {"name": "Minecraft", "state": "stopped"},
+            ],
+        }));
+        assert!(out.contains("server_launcher_uptime_seconds 90\n"));
+        assert!(out.contains("server_launcher_servers 2\n"));
+        assert!(out.contains("server_launcher_server_cpu_percent{server=\"Web\"} 12.5\n"));
+        assert!(!out.contains("server_cpu_percent{server=\"Minecraft\"}"));
+        // Every family keeps its help and type lines even with no samples
+        assert!(out.contains("# TYPE server_launcher_stream_clients gauge\n"));
+        assert!(!out.contains("\nserver_launcher_stream_clients "));
+    }
+}
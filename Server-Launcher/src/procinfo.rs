@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+// Reads process information from /proc, everything here comes back empty on other systems
+
+// The process and everything it started, a wrapper script's children included
+pub fn process_tree(pid: u32) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    if let Ok(entries) = fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let Some(child) = entry.file_name().to_str().and_then(|name| name.parse().ok()) else {
+                continue;
+            };
+            if let Some(parent) = parent_pid(child) {
+                children.entry(parent).or_default().push(child);
+            }
+        }
+    }
+
+    let mut tree = vec![pid];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(found) = children.get(&tree[i]) {
+            tree.extend(found);
+        }
+        i += 1;
+    }
+    tree
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    stat_parent(&stat)
+}
+
+fn stat_parent(stat: &str) -> Option<u32> {
+    // The command name in parentheses can contain spaces, fields start after it
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 2..].split_whitespace().collect();
+    fields.get(1)?.parse().ok()
+}
+
+pub fn cwd(pid: u32) -> Option<PathBuf> {
+    fs::read_link(format!("/proc/{}/cwd", pid)).ok()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ListeningPort {
+    pub protocol: &'static str,
+    pub port: u16,
+}
+
+// Ports the processes have bound, tcp sockets in LISTEN and every bound udp socket
+pub fn listening_ports(pids: &[u32]) -> Vec<ListeningPort> {
+    let mut inodes = HashSet::new();
+    for pid in pids {
+        let Ok(fds) = fs::read_dir(format!("/proc/{}/fd", pid)) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let target = target.to_string_lossy();
+            if let Some(inode) = target
+                .strip_prefix("socket:[")
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                inodes.insert(inode.to_string());
+            }
+        }
+    }
+    if inodes.is_empty() {
+        return Vec::new();
+    }
+
+    let tables = [
+        ("tcp", "/proc/net/tcp", Some("0A")),
+        ("tcp", "/proc/net/tcp6", Some("0A")),
+        ("udp", "/proc/net/udp", None),
+        ("udp", "/proc/net/udp6", None),
+    ];
+    let mut ports = HashSet::new();
+    for (protocol, path, listen_state) in tables {
+        let Ok(table) = fs::read_to_string(path) else {
+            continue;
+        };
+        ports.extend(table_ports(&table, protocol, listen_state, &inodes));
+    }
+
+    let mut ports: Vec<ListeningPort> = ports.into_iter().collect();
+    ports.sort();
+    ports
+}
+
+// Sockets from one /proc/net table that belong to `inodes`
+fn table_ports(
+    table: &str,
+    protocol: &'static str,
+    listen_state: Option<&str>,
+    inodes: &HashSet<String>,
+) -> Vec<ListeningPort> {
+    let mut ports = Vec::new();
+    for line in table.lines().skip(1) {
+        // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 || !inodes.contains(fields[9]) {
+            continue;
+        }
+        if listen_state.is_some_and(|state| fields[3] != state) {
+            continue;
+        }
+        let port = fields[1]
+            .rsplit(':')
+            .next()
+            .and_then(|hex| u16::from_str_radix(hex, 16).ok());
+        if let Some(port) = port {
+            ports.push(ListeningPort { protocol, port });
+        }
+    }
+    ports
+}
+
+// Resource use summed over a set of processes
+#[derive(Clone, Copy, Default)]
+pub struct ProcessUsage {
+    pub cpu_ticks: u64, // user + system time
+    pub rss_kb: u64,
+    pub threads: u64,
+}
+
+pub fn process_usage(pids: &[u32]) -> Option<ProcessUsage> {
+    let mut usage = ProcessUsage::default();
+    let mut found = false;
+    for pid in pids {
+        let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+            continue;
+        };
+        let Some((cpu_ticks, threads)) = stat_usage(&stat) else {
+            continue;
+        };
+        usage.cpu_ticks += cpu_ticks;
+        usage.threads += threads;
+
+        if let Ok(status) = fs::read_to_string(format!("/proc/{}/status", pid)) {
+            usage.rss_kb += status_rss_kb(&status).unwrap_or(0);
+        }
+        found = true;
+    }
+    found.then_some(usage)
+}
+
+// Cpu ticks and thread count from a /proc/<pid>/stat line
+fn stat_usage(stat: &str) -> Option<(u64, u64)> {
+    let end = stat.rfind(')')?;
+    // Fields after the command name, utime and stime are the 12th and 13th of these
+    let fields: Vec<&str> = stat.get(end + 2..)?.split_whitespace().collect();
+    let ticks = |i: usize| fields.get(i).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+    Some((ticks(11) + ticks(12), ticks(17)))
+}
+
+fn status_rss_kb(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.split_whitespace().next())
+        .and_then(|kb| kb.parse::<u64>().ok())
+}
+
+// Ticks every cpu has spent in any state, the clock cpu percentages are measured against
+pub fn total_cpu_ticks() -> Option<u64> {
+    stat_total_ticks(&fs::read_to_string("/proc/stat").ok()?)
+}
+
+fn stat_total_ticks(stat: &str) -> Option<u64> {
+    let line = stat.lines().next()?.strip_prefix("cpu ")?;
+    Some(line.split_whitespace().filter_map(|v| v.parse::<u64>().ok()).sum())
+}
+
+pub fn cpu_count() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:0CEA 0100007F:9C40 01 00000000:00000000 00:00000000 00000000  1000        0 4243 1 0000000000000000 20 4 30 10 -1
+   2: 00000000:0016 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 999 1 0000000000000000 100 0 0 10 0
+";
+
+    fn inodes(list: &[&str]) -> HashSet<String> {
+        list.iter().map(|inode| inode.to_string()).collect()
+    }
+
+    #[test]
+    fn only_listening_sockets_we_own_count() {
+        let ports = table_ports(TCP, "tcp", Some("0A"), &inodes(&["4242", "4243"]));
+        assert!(ports == vec![ListeningPort { protocol: "tcp", port: 8080 }]);
+    }
+
+    #[test]
+    fn udp_sockets_count_in_any_state() {
+        let ports = table_ports(TCP, "udp", None, &inodes(&["4243"]));
+        assert!(ports == vec![ListeningPort { protocol: "udp", port: 3306 }]);
+        assert!(table_ports("header only\n", "udp", None, &inodes(&["4243"])).is_empty());
+    }
+
+    #[test]
+    fn parent_is_read_after_the_command_name() {
+        assert_eq!(stat_parent("1234 (java) S 1 1234 1234 0 -1"), Some(1));
+        assert_eq!(stat_parent("77 (my server (v2)) R 42 77 77"), Some(42));
+        assert_eq!(stat_parent("garbage"), None);
+    }
+
+    #[test]
+    fn finds_our_own_listening_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pid = std::process::id();
+        if fs::metadata("/proc/self/fd").is_err() {
+            return;
+        }
+        assert!(process_tree(pid).contains(&pid));
+        assert!(listening_ports(&[pid]).contains(&ListeningPort { protocol: "tcp", port }));
+    }
+    #[test]
+    fn cpu_ticks_and_threads_come_from_stat() {
+        let stat = "4321 (java -jar) S 1 4321 4321 0 -1 4194560 9000 0 0 0 150 50 0 0 20 0 37 0 100 0 0";
+        assert_eq!(stat_usage(stat), Some((200, 37)));
+        assert_eq!(stat_usage("no command name"), None);
+    }
+
+    #[test]
+    fn rss_comes_from_status() {
+        let status = "Name:\tjava\nVmPeak:\t  900000 kB\nVmRSS:\t  524288 kB\nThreads:\t37\n";
+        assert_eq!(status_rss_kb(status), Some(524288));
+        // Kernel threads have no memory lines at all
+        assert_eq!(status_rss_kb("Name:\tkworker\n"), None);
+    }
+
+    #[test]
+    fn total_ticks_sum_the_cpu_line() {
+        let stat = "cpu  100 5 50 1000 20 0 3 0 0 0\ncpu0 50 2 25 500 10 0 1 0 0 0\n";
+        assert_eq!(stat_total_ticks(stat), Some(1178));
+        assert_eq!(stat_total_ticks("intr 1 2 3\n"), None);
+    }
+}
@@ -0,0 +1,239 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::classify::{LogLevel, LogPreset, LogRule};
+use crate::db::Server;
+use crate::triggers::{Trigger, TriggerAction};
+
+// Only rgb colors can be written down as #rrggbb
+pub fn parse_color(text: &str) -> Option<Color> {
+    match Color::from_str(text.trim()) {
+        Ok(color @ Color::Rgb(..)) => Some(color),
+        _ => None,
+    }
+}
+
+// Server definition as it appears in exports and --json output
+#[derive(Serialize, Deserialize)]
+pub struct ServerDef {
+    pub name: String,
+    path: String,
+    executable: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    autostart: bool,
+    #[serde(default)]
+    test_server: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(default = "default_preset")]
+    log_preset: String,
+    #[serde(default)]
+    log_rules: Vec<RuleDef>,
+    #[serde(default)]
+    triggers: Vec<TriggerDef>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RuleDef {
+    pattern: String,
+    level: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TriggerDef {
+    pattern: String,
+    action: String,
+    #[serde(default)]
+    argument: String,
+    #[serde(default)]
+    cooldown_secs: u64,
+}
+
+fn default_preset() -> String {
+    LogPreset::Plain.name().to_string()
+}
+
+impl ServerDef {
+    pub fn from_server(server: &Server) -> ServerDef {
+        ServerDef {
+            name: server.name.clone(),
+            path: server.path.clone(),
+            executable: server.executable.clone(),
+            args: server.args.clone(),
+            autostart: server.autostart,
+            test_server: server.test_server,
+            color: match server.display_color {
+                Color::Rgb(r, g, b) => Some(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+                _ => None,
+            },
+            log_preset: server.log_preset.name().to_string(),
+            log_rules: server
+                .log_rules
+                .iter()
+                .map(|rule| RuleDef {
+                    pattern: rule.pattern.as_str().to_string(),
+                    level: rule.level.label().to_string(),
+                })
+                .collect(),
+            triggers: server
+                .triggers
+                .iter()
+                .map(|trigger| TriggerDef {
+                    pattern: trigger.pattern.as_str().to_string(),
+                    action: trigger.action.kind().to_string(),
+                    argument: trigger.action.argument().to_string(),
+                    cooldown_secs: trigger.cooldown.as_secs(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_server(&self) -> Result<Server, String> {
+        let preset = LogPreset::parse(&self.log_preset)
+            .ok_or_else(|| format!("unknown log preset `{}`", self.log_preset))?;
+        let color = match &self.color {
+            Some(color) => parse_color(color).ok_or_else(|| format!("`{}` is not a #rrggbb color", color))?,
+            None => Color::White,
+        };
+        let rules = self
+            .log_rules
+            .iter()
+            .map(|rule| {
+                let level = LogLevel::parse(&rule.level)
+                    .ok_or_else(|| format!("unknown level `{}`", rule.level))?;
+                LogRule::new(&rule.pattern, level).map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let triggers = self
+            .triggers
+            .iter()
+            .map(|trigger| {
+                let action = TriggerAction::parse(&trigger.action, &trigger.argument)
+                    .ok_or_else(|| format!("unknown trigger action `{}`", trigger.action))?;
+                Trigger::new(&trigger.pattern, action, Duration::from_secs(trigger.cooldown_secs))
+                    .map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Server::default()
+            .name(&self.name)
+            .path(&self.path)
+            .executable(&self.executable)
+            .args(self.args.clone())
+            .autostart(self.autostart)
+            .test_server(self.test_server)
+            .display_color(color)
+            .log_preset(preset)
+            .log_rules(rules)
+            .triggers(triggers))
+    }
+}
+
+// The checks the add/edit form makes, for servers coming from anywhere else
+pub fn validate(server: &Server, existing: &[Server]) -> Result<(), String> {
+    if server.name.is_empty() {
+        return Err("a name is required".to_string());
+    }
+    // The launcher's own messages go by this name in the log panel and in logs/
+    if server.name.eq_ignore_ascii_case("launcher") {
+        return Err("`launcher` is the name of the launcher's own log".to_string());
+    }
+    if existing
+        .iter()
+        .any(|other| other.id != server.id && other.name.eq_ignore_ascii_case(&server.name))
+    {
+        return Err(format!("a server named `{}` already exists", server.name));
+    }
+    if !server.test_server {
+        if !Path::new(&server.path).is_dir() {
+            return Err(format!("`{}` is not a directory", server.path));
+        }
+        if server.executable.is_empty() {
+            return Err("an executable is required".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(json: &str) -> Result<Server, String> {
+        serde_json::from_str::<ServerDef>(json).map_err(|e| e.to_string())?.to_server()
+    }
+
+    #[test]
+    fn round_trip_through_json() {
+        let json = r##"{
+            "name": "Minecraft Server",
+            "path": "/srv/minecraft",
+            "executable": "java",
+            "args": ["-jar", "server.jar", "nogui"],
+            "autostart": true,
+            "color": "#ff8800",
+            "log_preset": "minecraft",
+            "log_rules": [{"pattern": "lag", "level": "warn"}],
+            "triggers": [{"pattern": "Done", "action": "ready", "cooldown_secs": 5}]
+        }"##;
+        let server = definition(json).unwrap();
+        assert_eq!(server.args, ["-jar", "server.jar", "nogui"]);
+        assert_eq!(server.display_color, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(server.log_preset, LogPreset::Minecraft);
+        assert_eq!(server.log_rules[0].level, LogLevel::Warn);
+        assert_eq!(server.triggers[0].action, TriggerAction::MarkReady);
+        assert_eq!(server.triggers[0].cooldown, Duration::from_secs(5));
+
+        let exported = serde_json::to_value(ServerDef::from_server(&server)).unwrap();
+        let again = serde_json::from_value::<ServerDef>(exported.clone()).unwrap().to_server().unwrap();
+        assert_eq!(serde_json::to_value(ServerDef::from_server(&again)).unwrap(), exported);
+    }
+
+    #[test]
+    fn defaults_for_missing_fields() {
+        let server = definition(r#"{"name": "Web", "path": "/srv/web", "executable": "python3"}"#).unwrap();
+        assert!(server.args.is_empty() && !server.autostart && !server.test_server);
+        assert_eq!(server.log_preset, LogPreset::Plain);
+        assert_eq!(server.display_color, Color::White);
+    }
+
+    #[test]
+    fn bad_definitions() {
+        let base = r#""name": "Web", "path": "/srv/web", "executable": "python3""#;
+        for (extra, error) in [
+            (r#""log_preset": "apache""#, "unknown log preset"),
+            (r#""color": "red""#, "not a #rrggbb color"),
+            (r#""log_rules": [{"pattern": "x", "level": "loud"}]"#, "unknown level"),
+            (r#""log_rules": [{"pattern": "(", "level": "warn"}]"#, "regex parse error"),
+            (r#""triggers": [{"pattern": "x", "action": "reboot"}]"#, "unknown trigger action"),
+        ] {
+            let result = definition(&format!("{{{}, {}}}", base, extra));
+            assert!(result.as_ref().is_err_and(|e| e.contains(error)), "{}: {:?}", extra, result.err());
+        }
+        assert!(definition(r#"{"name": "Web"}"#).is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let test = |name: &str| Server::default().name(name).test_server(true);
+        let existing = [test("Web").id(1)];
+        assert!(validate(&test("Minecraft"), &existing).is_ok());
+        // Renaming a server to its own name is fine, taking another's isn't
+        assert!(validate(&test("web").id(1), &existing).is_ok());
+        assert!(validate(&test("WEB").id(2), &existing).is_err());
+        assert!(validate(&test(""), &existing).is_err());
+        assert!(validate(&test("Launcher"), &existing).is_err());
+
+        let missing = Server::default().name("Real").path("/nonexistent/launcher-test");
+        assert!(validate(&missing, &[]).unwrap_err().contains("not a directory"));
+        let no_executable = Server::default().name("Real").path("/").executable("");
+        assert!(validate(&no_executable, &[]).is_err());
+        assert!(validate(&Server::default().name("Real").path("/"), &[]).is_ok());
+    }
+}
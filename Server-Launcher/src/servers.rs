@@ -1,35 +1,140 @@
-use std::io::{self, Stdin};
-use std::io::{BufRead, BufReader, Result, Read};
-use std::process::{Child, Command, Stdio};
+#[cfg(unix)]
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, Read, Result, Write};
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
+use chrono::{DateTime, Local};
+
+use crate::classify::{Classifier, LogLevel};
 use crate::db::Server; // Use the Server struct from the db module
+#[cfg(unix)]
+use crate::detached;
+use crate::triggers::{self, Trigger, TriggerAction};
+
+// Every launch gets a new id so events from a previous run of the same server can be told apart
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(1);
 
 // Define events for server lifecycle
 #[derive(Clone, Debug)]
 pub enum ServerLifecycleEvent {
-    Exited { name: String },
+    Exited { name: String, run_id: u64 },
+    // A line matched one of the server's triggers, cooldowns are applied by the receiver
+    Triggered {
+        name: String,
+        run_id: u64,
+        rule: usize,
+        action: TriggerAction,
+        cooldown: Duration,
+        line: String,
+    },
 }
 
 pub struct ServerHandle {
     pub child: Option<Child>,
+    pub adopted: Option<u32>, // pid of a server a quitting launcher handed over, it is not our child
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub adopted_start: Option<u64>, // and its start time, to tell it from a later process with that pid
     pub name: String,
-    pub log_sender: Sender<ServerMessage>, // Renamed for clarity
+    pub run_id: u64,
     pub server_event_sender: Sender<ServerLifecycleEvent>,
-//    pub input: Stdin,
+    pub stdin: Option<ChildStdin>,
     pub running: bool,
+    pub ready: bool, // Set by a `ready` trigger
+    pub started_at: DateTime<Local>,
+    pub command_line: String, // what was actually run, shown in the details page
+    // Spare read ends of stdout and stderr, so a running server can be handed to a supervisor
+    #[cfg(unix)]
+    pub output: Option<(OwnedFd, OwnedFd)>,
 }
 
+#[derive(Clone)]
 pub struct ServerMessage{
     pub name: String,
     pub contents: String,
-    pub message_type: MessageType,
+    pub timestamp: DateTime<Local>, // When the line was captured, not when it was drawn
+    pub level: Option<LogLevel>, // None when no rule matched
+    pub stream: LogStream,
 }
 pub enum MessageType{
     Err , Main , None
 }
 
+// Where a line came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    Launcher,
+}
+
+impl LogStream {
+    pub const ALL: [LogStream; 3] = [LogStream::Stdout, LogStream::Stderr, LogStream::Launcher];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+            LogStream::Launcher => "launcher",
+        }
+    }
+}
+
+impl ServerMessage {
+    // Messages generated by the launcher itself
+    pub fn new(name: &str, contents: impl Into<String>, message_type: MessageType) -> ServerMessage {
+        let level = match message_type {
+            MessageType::Err => Some(LogLevel::Error),
+            MessageType::Main => Some(LogLevel::Info),
+            MessageType::None => None,
+        };
+        ServerMessage {
+            name: name.to_string(),
+            contents: contents.into(),
+            timestamp: Local::now(),
+            level,
+            stream: LogStream::Launcher,
+        }
+    }
+
+    // A line printed by a server process
+    pub fn output(
+        name: &str,
+        contents: impl Into<String>,
+        stream: LogStream,
+        level: Option<LogLevel>,
+    ) -> ServerMessage {
+        ServerMessage {
+            name: name.to_string(),
+            contents: contents.into(),
+            timestamp: Local::now(),
+            level,
+            stream,
+        }
+    }
+}
+
+// Killed processes have no code, report the signal as a negative number instead
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return -signal;
+        }
+    }
+    status.code().unwrap_or(-1)
+}
+
 impl ServerHandle {
     pub fn kill_process(&mut self) -> std::result::Result<(), String> {
         if let Some(ref mut child) = self.child {
@@ -37,8 +142,10 @@ impl ServerHandle {
                 Ok(_) => {
                     self.running = false;
                     // Send an exit event when killed
+                    let _ = child.wait(); // reap it so it doesn't linger as a zombie
                     let _ = self.server_event_sender.send(ServerLifecycleEvent::Exited {
                         name: self.name.clone(),
+                        run_id: self.run_id,
                     });
                     Ok(())
                 }
@@ -46,40 +153,164 @@ impl ServerHandle {
             }
         } else {
             // If there's no child process (e.g., dummy server or already stopped)
+            #[cfg(unix)]
+            if let Some(pid) = self.adopted.filter(|&pid| detached::is_same(pid, self.adopted_start)) {
+                unsafe {
+                    libc::kill(pid as libc::pid_t, libc::SIGKILL);
+                }
+            }
             self.running = false;
             // Send an exit event even for dummy servers or if no child process
             // Dummy Servers not exiting correctly
             let _ = self.server_event_sender.send(ServerLifecycleEvent::Exited {
                 name: self.name.clone(),
+                run_id: self.run_id,
             });
 
             Ok(())
         }
     }
+
+    // Asks the server to exit on its own, kill_process is the hard way
+    pub fn terminate(&self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.pid() {
+            if self.adopted.is_some() && !detached::is_same(pid, self.adopted_start) {
+                return;
+            }
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+
+    // Whether the server's process is gone. Test servers have none and never are, they send
+    // Exited themselves.
+    pub fn process_gone(&mut self) -> bool {
+        match (self.child.as_mut(), self.adopted) {
+            (Some(child), _) => !matches!(child.try_wait(), Ok(None)),
+            // Not our child, whoever it was handed to reaps it
+            #[cfg(unix)]
+            (None, Some(pid)) => !detached::is_same(pid, self.adopted_start),
+            (None, _) => false,
+        }
+    }
+
+    // Dummy servers have no process
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id).or(self.adopted)
+    }
+
+    // None while it is still running, and for dummy and adopted servers
+    pub fn exit_code(&mut self) -> Option<i32> {
+        let status = self.child.as_mut()?.try_wait().ok()??;
+        Some(exit_code(status))
+    }
+
+    // Writes one line to the server's console
+    pub fn send_input(&mut self, line: &str) -> std::result::Result<(), String> {
+        match self.stdin.as_mut() {
+            Some(stdin) => writeln!(stdin, "{}", line)
+                .and_then(|_| stdin.flush())
+                .map_err(|e| format!("Failed to send input to {}: {}", self.name, e)),
+            None => Err(format!("Server {} has no console input", self.name)),
+        }
+    }
+}
+
+// Runs a trigger hook from the server's directory without blocking the caller.
+// The hook gets SERVER_NAME and TRIGGER_LINE in its environment.
+pub fn run_hook(server: &Server, hook: &str, line: &str, sender: Sender<ServerMessage>) {
+    let (shell, shell_flag) = if std::env::consts::OS == "windows" {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut command = Command::new(shell);
+    command
+        .arg(shell_flag)
+        .arg(hook)
+        .current_dir(&server.path)
+        .env("SERVER_NAME", &server.name)
+        .env("TRIGGER_LINE", line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let name = server.name.clone();
+    let hook = hook.to_string();
+    thread::spawn(move || {
+        let message = match command.status() {
+            Ok(status) if status.success() => {
+                ServerMessage::new("", format!("Hook for {} finished: {}", name, hook), MessageType::Main)
+            }
+            Ok(status) => ServerMessage::new(
+                "",
+                format!("Hook for {} failed ({}): {}", name, status, hook),
+                MessageType::Err,
+            ),
+            Err(e) => ServerMessage::new(
+                "",
+                format!("Failed to run hook for {}: {}", name, e),
+                MessageType::Err,
+            ),
+        };
+        let _ = sender.send(message);
+    });
 }
 
 fn build_command(server: &Server) -> Result<Command> {
     let os = std::env::consts::OS;
-    let (shell, shell_flag, cd_prefix) = match os {
-        "windows" => ("cmd", "/C", "cd /d"),
-        "linux" | "macos" => ("sh", "-c", "cd"),
+    // `exec` replaces the shell so killing the child kills the server itself
+    let (shell, shell_flag, cd_prefix, exec_prefix) = match os {
+        "windows" => ("cmd", "/C", "cd /d", ""),
+        "linux" | "macos" => ("sh", "-c", "cd", "exec "),
         _ => return Err(io::Error::new(io::ErrorKind::Unsupported, "Unsupported OS")),
     };
 
     let cd_command = format!("{} {}", cd_prefix, shell_escape(&server.path));
-    let exec_command = format!("{} {}", server.executable, server.args.join(" "));
+    let exec_command = format!("{}{} {}", exec_prefix, server.executable, server.args.join(" "));
     let full_command = format!("{} && {}", cd_command, exec_command);
 
     let mut command = Command::new(shell);
     command
         .arg(shell_flag)
         .arg(full_command)
+        .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
+    // A group of its own, so a closed terminal hangs up the launcher and not the server,
+    // the quit policy decides what happens to it
+    #[cfg(unix)]
+    command.process_group(0);
 
     Ok(command)
 }
 
+fn describe_command(command: &Command) -> String {
+    let mut parts = vec![command.get_program().to_string_lossy().to_string()];
+    parts.extend(command.get_args().map(|arg| {
+        let arg = arg.to_string_lossy();
+        if arg.contains(' ') {
+            format!("\"{}\"", arg)
+        } else {
+            arg.to_string()
+        }
+    }));
+    parts.join(" ")
+}
+
+// The command line a launch of the server would run
+pub fn command_line(server: &Server) -> String {
+    if server.test_server {
+        return "(test server, no process)".to_string();
+    }
+    match build_command(server) {
+        Ok(command) => describe_command(&command),
+        Err(e) => e.to_string(),
+    }
+}
+
 fn shell_escape(path: &str) -> String {
     if std::env::consts::OS == "windows" {
         path.to_string()
@@ -88,21 +319,46 @@ fn shell_escape(path: &str) -> String {
     }
 }
 
-fn capture_output<R: Read + Send + 'static>(
-    reader: R,
+// Everything an output reader thread needs besides the stream itself
+#[derive(Clone)]
+struct OutputContext {
     name: String,
-    is_stderr: bool,
+    run_id: u64,
+    classifier: Arc<Classifier>,
+    triggers: Arc<Vec<Trigger>>,
     sender: Sender<ServerMessage>,
     event_sender: Sender<ServerLifecycleEvent>,
-) {
+}
+
+fn capture_output<R: Read + Send + 'static>(reader: R, is_stderr: bool, context: OutputContext) {
     thread::spawn(move || {
+        let OutputContext {
+            name,
+            run_id,
+            classifier,
+            triggers,
+            sender,
+            event_sender,
+        } = context;
         let reader = std::io::BufReader::new(reader);
+        let stream = if is_stderr { LogStream::Stderr } else { LogStream::Stdout };
         for line in reader.lines() {
             match line {
                 Ok(line_content) => {
-                    let prefix = if is_stderr { "[stderr] " } else { "" };
-                    let msg = format!(" {}{}", prefix, line_content);
-                    if let Err(e) = sender.send(ServerMessage{name:name.clone(),  contents: msg , message_type: MessageType::None}) {
+                    for (rule, trigger) in triggers::matching(&triggers, &line_content) {
+                        let _ = event_sender.send(ServerLifecycleEvent::Triggered {
+                            name: name.clone(),
+                            run_id,
+                            rule,
+                            action: trigger.action.clone(),
+                            cooldown: trigger.cooldown,
+                            line: line_content.clone(),
+                        });
+                    }
+
+                    let level = classifier.classify(&line_content);
+                    let msg = ServerMessage::output(&name, line_content, stream, level);
+                    if let Err(e) = sender.send(msg) {
                         eprintln!("[{}] Error sending log: {}", name, e);
                     }
                 }
@@ -117,7 +373,8 @@ fn capture_output<R: Read + Send + 'static>(
                 }
             }
         }
-        let _ = event_sender.send(ServerLifecycleEvent::Exited { name });
+        // Only the end of this stream, a server may close one of its pipes and keep running.
+        // Whoever owns the handle watches the process itself, see ServerHandle::process_gone.
     });
 }
 
@@ -131,6 +388,7 @@ pub fn launch(
     }
 
     let mut command = build_command(server)?;
+    let command_line = describe_command(&command);
     let mut child = command.spawn().map_err(|e| {
         io::Error::new(
             e.kind(),
@@ -141,36 +399,104 @@ pub fn launch(
     let stdout = child
         .stdout
         .take()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Could not capture stdout"))?;
+        .ok_or_else(|| io::Error::other("Could not capture stdout"))?;
     let stderr = child
         .stderr
         .take()
-        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Could not capture stderr"))?;
-
-    capture_output(
-        stdout,
-        server.name.clone(),
-        false,
-        log_sender.clone(),
-        server_event_sender.clone(),
-    );
-    capture_output(
-        stderr,
-        server.name.clone(),
-        true,
-        log_sender.clone(),
-        server_event_sender.clone(),
-    );
+        .ok_or_else(|| io::Error::other("Could not capture stderr"))?;
+    let stdin = child.stdin.take();
+
+    let run_id = NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed);
+    let context = output_context(server, run_id, log_sender, server_event_sender.clone());
+    #[cfg(unix)]
+    let output = {
+        let (stdout, stderr) = (OwnedFd::from(stdout), OwnedFd::from(stderr));
+        let output = (stdout.try_clone()?, stderr.try_clone()?);
+        capture_output(File::from(stdout), false, context.clone());
+        capture_output(File::from(stderr), true, context);
+        output
+    };
+    #[cfg(not(unix))]
+    {
+        capture_output(stdout, false, context.clone());
+        capture_output(stderr, true, context);
+    }
 
     Ok(ServerHandle {
         child: Some(child),
+        adopted: None,
+        adopted_start: None,
         name: server.name.clone(),
-        log_sender,
+        run_id,
         server_event_sender,
+        stdin,
         running: true,
+        ready: false,
+        started_at: Local::now(),
+        command_line,
+        #[cfg(unix)]
+        output: Some(output),
     })
 }
 
+fn output_context(
+    server: &Server,
+    run_id: u64,
+    log_sender: Sender<ServerMessage>,
+    server_event_sender: Sender<ServerLifecycleEvent>,
+) -> OutputContext {
+    OutputContext {
+        name: server.name.clone(),
+        run_id,
+        classifier: Arc::new(Classifier::new(server.log_preset, &server.log_rules)),
+        triggers: Arc::new(server.triggers.clone()),
+        sender: log_sender,
+        event_sender: server_event_sender,
+    }
+}
+
+// The pipes and process of a server started by a launcher that quit and left it running
+#[cfg(unix)]
+pub struct Handover {
+    pub pid: u32,
+    pub start: Option<u64>,
+    pub stdout: OwnedFd,
+    pub stderr: OwnedFd,
+    pub stdin: Option<OwnedFd>,
+    pub started_at: DateTime<Local>,
+}
+
+// Picks up a handed over server where the launcher left it. Its exit is noticed by polling
+// the pid, the exit code is lost since only its parent could wait for it.
+#[cfg(unix)]
+pub fn adopt(
+    server: &Server,
+    handover: Handover,
+    log_sender: Sender<ServerMessage>,
+    server_event_sender: Sender<ServerLifecycleEvent>,
+) -> ServerHandle {
+    let run_id = NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed);
+    let context = output_context(server, run_id, log_sender, server_event_sender.clone());
+    capture_output(File::from(handover.stdout), false, context.clone());
+    capture_output(File::from(handover.stderr), true, context);
+
+    ServerHandle {
+        child: None,
+        adopted: Some(handover.pid),
+        adopted_start: handover.start,
+        name: server.name.clone(),
+        run_id,
+        server_event_sender,
+        stdin: handover.stdin.map(ChildStdin::from),
+        running: true,
+        ready: false,
+        started_at: handover.started_at,
+        command_line: command_line(server),
+        #[cfg(unix)]
+        output: None,
+    }
+}
+
 // Dummy launch function updated to use the event sender
 fn dummy_launch(
     server: &Server,
@@ -178,25 +504,61 @@ fn dummy_launch(
     server_event_sender: Sender<ServerLifecycleEvent>,
 ) -> Result<ServerHandle> {
     let name = server.name.clone();
+    let run_id = NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed);
     let log_sender_clone = log_sender.clone();
     let event_sender_clone = server_event_sender.clone();
     thread::spawn(move || {
         for i in 0..15 {
             if let Err(e) =
-                log_sender_clone.send(ServerMessage{contents: format!("Dummy server running... {}", i), name: name.clone() , message_type: MessageType::None})
+                log_sender_clone.send(ServerMessage::output(&name, format!("Dummy server running... {}", i), LogStream::Stdout, None))
             {
                 eprintln!("[{}] Error sending dummy log: {}", name.clone(), e);
             }
             thread::sleep(std::time::Duration::from_secs(1));
         }
-        let _ = event_sender_clone.send(ServerLifecycleEvent::Exited { name: name.clone() });
+        let _ = event_sender_clone.send(ServerLifecycleEvent::Exited { name: name.clone(), run_id });
     });
 
     Ok(ServerHandle {
         child: None,
+        adopted: None,
+        adopted_start: None,
         name: server.name.clone(),
-        log_sender,
+        run_id,
         server_event_sender,
+        stdin: None,
         running: true,
+        ready: false,
+        started_at: Local::now(),
+        command_line: command_line(server),
+        #[cfg(unix)]
+        output: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn closing_a_pipe_does_not_end_the_run() {
+        let server = Server {
+            name: "Quiet".to_string(),
+            path: std::env::temp_dir().display().to_string(),
+            executable: "sh".to_string(),
+            args: vec!["-c".to_string(), "'exec >&- 2>&-; sleep 1'".to_string()],
+            ..Server::default()
+        };
+        let (log_sender, _logs) = std::sync::mpsc::channel();
+        let (event_sender, events) = std::sync::mpsc::channel();
+        let mut handle = launch(&server, log_sender, event_sender).unwrap();
+
+        thread::sleep(Duration::from_millis(300));
+        assert!(!handle.process_gone());
+        assert!(events.try_recv().is_err());
+
+        let _ = handle.child.as_mut().unwrap().wait();
+        assert!(handle.process_gone());
+    }
+}
@@ -0,0 +1,291 @@
+use rusqlite::{Connection, Result};
+
+use crate::db;
+use crate::keymap::KeyPreset;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimestampFormat {
+    Off,
+    WallClock,
+    Relative, // time since the server (or the launcher) started
+}
+
+impl TimestampFormat {
+    pub const ALL: [TimestampFormat; 3] = [
+        TimestampFormat::Off,
+        TimestampFormat::WallClock,
+        TimestampFormat::Relative,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimestampFormat::Off => "off",
+            TimestampFormat::WallClock => "clock",
+            TimestampFormat::Relative => "relative",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<TimestampFormat> {
+        TimestampFormat::ALL
+            .into_iter()
+            .find(|format| format.name() == name.trim())
+    }
+
+    pub fn next(self) -> TimestampFormat {
+        match self {
+            TimestampFormat::Off => TimestampFormat::WallClock,
+            TimestampFormat::WallClock => TimestampFormat::Relative,
+            TimestampFormat::Relative => TimestampFormat::Off,
+        }
+    }
+}
+
+// What happens to running servers when the launcher's own terminal quits
+#[derive(Clone, Copy, PartialEq)]
+pub enum QuitPolicy {
+    Ask,   // list them and let the user pick
+    Stop,  // SIGTERM, then kill whatever is still up after a while
+    Leave, // hand each, still running, to a supervisor like the ones `start` uses
+}
+
+impl QuitPolicy {
+    pub const ALL: [QuitPolicy; 3] = [QuitPolicy::Ask, QuitPolicy::Stop, QuitPolicy::Leave];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QuitPolicy::Ask => "ask",
+            QuitPolicy::Stop => "stop",
+            QuitPolicy::Leave => "leave",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<QuitPolicy> {
+        QuitPolicy::ALL.into_iter().find(|policy| policy.name() == name.trim())
+    }
+}
+
+// Choices for how many lines the log panel keeps in memory
+pub const LOG_BUFFER_SIZES: [usize; 4] = [500, 2000, 10000, 50000];
+
+// Launcher preferences, stored as key/value rows in the settings table
+#[derive(Clone)]
+pub struct Settings {
+    pub timestamp_format: TimestampFormat,
+    pub persist_logs: bool, // write every message under logfile::LOG_DIR
+    pub log_buffer_lines: usize,
+    pub api_address: String, // where the HTTP API listens, empty turns it off
+    pub ssh_address: String, // where the TUI is served over SSH, empty (the default) turns it off
+    pub ssh_authorized_keys: String, // public keys allowed to log in over SSH, one per line
+    pub quit_policy: QuitPolicy,
+    pub key_preset: KeyPreset,
+    pub keymap_file: String, // rebinds keys on top of the preset, see keymap.rs
+}
+
+impl Settings {
+    pub fn default() -> Settings {
+        Settings {
+            timestamp_format: TimestampFormat::WallClock,
+            persist_logs: true,
+            log_buffer_lines: 2000,
+            api_address: "127.0.0.1:8127".to_string(),
+            ssh_address: String::new(),
+            ssh_authorized_keys: "authorized_keys".to_string(),
+            quit_policy: QuitPolicy::Ask,
+            key_preset: KeyPreset::Vim,
+            keymap_file: "keys.json".to_string(),
+        }
+    }
+
+    // Missing or unreadable values keep their defaults
+    pub fn load(conn: &Connection) -> Settings {
+        let mut settings = Settings::default();
+        let Ok(stored) = db::load_settings(conn) else {
+            return settings;
+        };
+        if let Some(format) = stored.get("timestamp_format").and_then(|v| TimestampFormat::parse(v)) {
+            settings.timestamp_format = format;
+        }
+        if let Some(persist) = stored.get("persist_logs") {
+            settings.persist_logs = persist == "true";
+        }
+        if let Some(lines) = stored.get("log_buffer_lines").and_then(|v| v.parse().ok()) {
+            settings.log_buffer_lines = lines;
+        }
+        if let Some(address) = stored.get("api_address") {
+            settings.api_address = address.clone();
+        }
+        if let Some(address) = stored.get("ssh_address") {
+            settings.ssh_address = address.clone();
+        }
+        if let Some(path) = stored.get("ssh_authorized_keys") {
+            settings.ssh_authorized_keys = path.clone();
+        }
+        if let Some(policy) = stored.get("quit_policy").and_then(|v| QuitPolicy::parse(v)) {
+            settings.quit_policy = policy;
+        }
+        if let Some(preset) = stored.get("key_preset").and_then(|v| KeyPreset::parse(v)) {
+            settings.key_preset = preset;
+        }
+        if let Some(path) = stored.get("keymap_file") {
+            settings.keymap_file = path.clone();
+        }
+        settings
+    }
+
+    // Key/value pairs as stored, for listing and the `config` command
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("timestamp_format", self.timestamp_format.name().to_string()),
+            ("persist_logs", self.persist_logs.to_string()),
+            ("log_buffer_lines", self.log_buffer_lines.to_string()),
+            ("api_address", self.api_address.clone()),
+            ("ssh_address", self.ssh_address.clone()),
+            ("ssh_authorized_keys", self.ssh_authorized_keys.clone()),
+            ("quit_policy", self.quit_policy.name().to_string()),
+            ("key_preset", self.key_preset.name().to_string()),
+            ("keymap_file", self.keymap_file.clone()),
+        ]
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "timestamp_format" => {
+                self.timestamp_format = TimestampFormat::parse(value)
+                    .ok_or("use off, clock or relative")?;
+            }
+            "persist_logs" => {
+                self.persist_logs = value.parse().map_err(|_| "use true or false")?;
+            }
+            "log_buffer_lines" => {
+                self.log_buffer_lines = value
+                    .parse()
+                    .ok()
+                    .filter(|&lines| lines > 0)
+                    .ok_or("use a positive number")?;
+            }
+            "api_address" => {
+                if !value.is_empty() && value.parse::<std::net::SocketAddr>().is_err() {
+                    return Err("use ip:port, or an empty value to turn it off".to_string());
+                }
+                self.api_address = value.to_string();
+            }
+            "ssh_address" => {
+                if !value.is_empty() && value.parse::<std::net::SocketAddr>().is_err() {
+                    return Err("use ip:port, or an empty value to turn it off".to_string());
+                }
+                self.ssh_address = value.to_string();
+            }
+            "ssh_authorized_keys" => {
+                if value.is_empty() {
+                    return Err("use the path of an authorized_keys file".to_string());
+                }
+                self.ssh_authorized_keys = value.to_string();
+            }
+            "quit_policy" => {
+                self.quit_policy = QuitPolicy::parse(value).ok_or("use ask, stop or leave")?;
+            }
+            "key_preset" => {
+                self.key_preset = KeyPreset::parse(value).ok_or("use vim or arrows")?;
+            }
+            "keymap_file" => {
+                if value.is_empty() {
+                    return Err("use the path of a JSON keymap file".to_string());
+                }
+                self.keymap_file = value.to_string();
+            }
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, conn: &Connection) -> Result<()> {
+        db::save_setting(conn, "timestamp_format", self.timestamp_format.name())?;
+        db::save_setting(conn, "persist_logs", &self.persist_logs.to_string())?;
+        db::save_setting(conn, "log_buffer_lines", &self.log_buffer_lines.to_string())?;
+        db::save_setting(conn, "api_address", &self.api_address)?;
+        db::save_setting(conn, "ssh_address", &self.ssh_address)?;
+        db::save_setting(conn, "ssh_authorized_keys", &self.ssh_authorized_keys)?;
+        db::save_setting(conn, "quit_policy", self.quit_policy.name())?;
+        db::save_setting(conn, "key_preset", self.key_preset.name())?;
+        db::save_setting(conn, "keymap_file", &self.keymap_file)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_formats_parse_by_name() {
+        for format in TimestampFormat::ALL {
+            assert!(TimestampFormat::parse(format.name()) == Some(format));
+        }
+        assert!(TimestampFormat::parse(" relative ") == Some(TimestampFormat::Relative));
+        assert!(TimestampFormat::parse("sometimes").is_none());
+        // Cycling visits every format and comes back around
+        let mut format = TimestampFormat::Off;
+        for _ in TimestampFormat::ALL {
+            format = format.next();
+        }
+        assert!(format == TimestampFormat::Off);
+    }
+
+    fn changed() -> Settings {
+        let mut settings = Settings::default();
+        let values = [
+            ("timestamp_format", "relative"),
+            ("persist_logs", "false"),
+            ("log_buffer_lines", "50000"),
+            ("api_address", ""),
+            ("ssh_address", "0.0.0.0:2222"),
+            ("ssh_authorized_keys", "/etc/launcher/keys"),
+            ("quit_policy", "leave"),
+            ("key_preset", "arrows"),
+            ("keymap_file", "my-keys.json"),
+        ];
+        for (key, value) in values {
+            settings.set(key, value).unwrap();
+        }
+        settings
+    }
+
+    #[test]
+    fn entries_set_back_to_the_same_settings() {
+        let settings = changed();
+        let mut copy = Settings::default();
+        for (key, value) in settings.entries() {
+            copy.set(key, &value).unwrap();
+        }
+        assert_eq!(copy.entries(), settings.entries());
+    }
+
+    #[test]
+    fn saved_settings_load_back() {
+        let conn = db::connect_db(":memory:").unwrap();
+        assert_eq!(Settings::load(&conn).entries(), Settings::default().entries());
+        let settings = changed();
+        settings.save(&conn).unwrap();
+        assert_eq!(Settings::load(&conn).entries(), settings.entries());
+    }
+
+    #[test]
+    fn bad_values_are_refused() {
+        let mut settings = Settings::default();
+        for (key, value) in [
+            ("timestamp_format", "sometimes"),
+            ("persist_logs", "yes"),
+            ("log_buffer_lines", "0"),
+            ("api_address", "localhost"),
+            ("ssh_address", "2222"),
+            ("ssh_authorized_keys", ""),
+            ("quit_policy", "never"),
+            ("key_preset", "emacs"),
+            ("keymap_file", ""),
+            ("colour", "blue"),
+        ] {
+            assert!(settings.set(key, value).is_err(), "{} = {}", key, value);
+        }
+        assert_eq!(settings.entries(), Settings::default().entries());
+    }
+}
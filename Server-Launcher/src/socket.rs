@@ -0,0 +1,395 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::auth::Identity;
+use crate::classify::LogLevel;
+use crate::control::{self, ControlCommand, ControlError, ControlRequest, ControlResult, ErrorKind};
+use crate::detached;
+use crate::stream::{StreamEvent, StreamFilter};
+
+// Line-delimited JSON on a Unix socket, for scripts on this machine and the command line.
+// Only the launcher's own user can open it (0600, in a 0700 directory), so there is no login.
+//
+//   {"op": "list"}
+//   {"op": "status", "server": "Web"}
+//   {"op": "start", "server": "Web"}              also "stop" and "restart"
+//   {"op": "send", "server": "Web", "input": "say hi"}
+//   {"op": "save", "server": "Web", "definition": {...}}   as in `export`, without "server" it adds one
+//   {"op": "delete", "server": "Web"}              stops it first when it is running
+//   {"op": "subscribe_logs", "server": "Web", "level": "warn,error", "replay": 100}
+//   {"op": "subscribe_events", "server": "Web"}   started, ready, stopping, restarting, exited
+//   {"op": "attach", "cols": 120, "rows": 40}
+//
+// Every request gets one answer line, {"ok": true, "result": ...} or
+// {"ok": false, "kind": "not_found", "error": "..."}, with the request's "id" copied over.
+// After a subscribe, events follow as {"event": {...}} lines, between later answers.
+// After an attach the connection is a terminal showing the TUI: raw output comes back and
+// {"keys": "..."} and {"resize": [cols, rows]} lines go in, until either side hangs up.
+
+pub fn socket_path() -> std::path::PathBuf {
+    Path::new(detached::RUN_DIR).join("launcher.sock")
+}
+
+type Writer = Arc<Mutex<UnixStream>>;
+
+// A connection that asked to attach, handed to the TUI with whatever it sent after the request
+pub struct Attach {
+    pub reader: BufReader<UnixStream>,
+    pub writer: UnixStream,
+    pub size: (u16, u16),
+}
+
+pub fn spawn(path: &Path, control: Sender<ControlRequest>, attach: Sender<Attach>) -> io::Result<()> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "another launcher is already listening there",
+            ));
+        }
+        // Left behind by a launcher that did not shut down cleanly
+        fs::remove_file(path)?;
+    }
+    // bind creates the socket with the umask's mode and it is only narrowed after, the
+    // directory keeps everyone else out in between
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    }
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let control = control.clone();
+            let attach = attach.clone();
+            thread::spawn(move || serve(stream, &control, &attach));
+        }
+    });
+    Ok(())
+}
+
+fn send(writer: &Writer, line: &Value) -> io::Result<()> {
+    let mut writer = writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    writeln!(writer, "{}", line)
+}
+
+fn answer(id: &Value, result: ControlResult) -> Value {
+    let mut line = match result {
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(e) => json!({ "ok": false, "kind": e.kind.name(), "error": e.message }),
+    };
+    if !id.is_null() {
+        line["id"] = id.clone();
+    }
+    line
+}
+
+// One connection, requests are answered in order until the client hangs up or attaches
+fn serve(stream: UnixStream, control: &Sender<ControlRequest>, attach: &Sender<Attach>) {
+    let Ok(writer) = stream.try_clone() else {
+        return;
+    };
+    let writer: Writer = Arc::new(Mutex::new(writer));
+    let identity = Identity::socket();
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if !matches!(reader.read_line(&mut line), Ok(read) if read > 0) {
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = ControlError::new(ErrorKind::Invalid, format!("not a JSON request: {}", e));
+                if send(&writer, &answer(&Value::Null, Err(error))).is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let op = request["op"].as_str().unwrap_or_default();
+        if op == "attach" {
+            match attach_size(&request) {
+                Ok(size) => {
+                    let answered = send(&writer, &answer(&request["id"], Ok(json!({ "attached": true }))));
+                    let handed = writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).try_clone();
+                    if let (Ok(()), Ok(writer)) = (answered, handed) {
+                        let _ = attach.send(Attach { reader, writer, size });
+                    }
+                    return;
+                }
+                Err(e) => {
+                    if send(&writer, &answer(&request["id"], Err(e))).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            }
+        }
+        let sent = if op == "subscribe_logs" || op == "subscribe_events" {
+            // The answer goes out before the first event
+            match subscribe(&request, control, &identity) {
+                Ok(events) => {
+                    let sent = send(&writer, &answer(&request["id"], Ok(json!({ "subscribed": op }))));
+                    let writer = writer.clone();
+                    thread::spawn(move || forward(events, &writer));
+                    sent
+                }
+                Err(e) => send(&writer, &answer(&request["id"], Err(e))),
+            }
+        } else {
+            send(&writer, &answer(&request["id"], run(&request, control, &identity)))
+        };
+        if sent.is_err() {
+            break;
+        }
+    }
+}
+
+// Dropping the receiver once the client is gone unsubscribes on the next broadcast
+fn forward(events: Receiver<StreamEvent>, writer: &Writer) {
+    for event in events {
+        if send(writer, &json!({ "event": event.to_json() })).is_err() {
+            return;
+        }
+    }
+}
+
+fn string(request: &Value, key: &str) -> Result<String, ControlError> {
+    request[key].as_str().map(str::to_string).ok_or_else(|| {
+        let op = request["op"].as_str().unwrap_or_default();
+        ControlError::new(ErrorKind::Invalid, format!("{} needs \"{}\"", op, key))
+    })
+}
+
+fn attach_size(request: &Value) -> Result<(u16, u16), ControlError> {
+    let dimension = |key: &str| {
+        request[key]
+            .as_u64()
+            .filter(|&n| (1..=u16::MAX as u64).contains(&n))
+            .map(|n| n as u16)
+            .ok_or_else(|| ControlError::new(ErrorKind::Invalid, format!("attach needs \"{}\"", key)))
+    };
+    Ok((dimension("cols")?, dimension("rows")?))
+}
+
+fn run(request: &Value, control: &Sender<ControlRequest>, identity: &Identity) -> ControlResult {
+    let command = match request["op"].as_str().unwrap_or_default() {
+        "list" => ControlCommand::List,
+        "status" => ControlCommand::Get(string(request, "server")?),
+        "start" => ControlCommand::Start(string(request, "server")?),
+        "stop" => ControlCommand::Stop(string(request, "server")?),
+        "restart" => ControlCommand::Restart(string(request, "server")?),
+        "send" => ControlCommand::SendCommand {
+            name: string(request, "server")?,
+            command: string(request, "input")?,
+        },
+        "save" => ControlCommand::Save {
+            name: request["server"].as_str().map(str::to_string),
+            definition: serde_json::from_value(request["definition"].clone())
+                .map_err(|e| ControlError::new(ErrorKind::Invalid, format!("not a server definition: {}", e)))?,
+        },
+        "delete" => ControlCommand::Delete(string(request, "server")?),
+        "" => return Err(ControlError::new(ErrorKind::Invalid, "the request has no \"op\"")),
+        other => return Err(ControlError::new(ErrorKind::Invalid, format!("unknown op `{}`", other))),
+    };
+    control::request(control, identity, command)
+}
+
+fn subscribe(
+    request: &Value,
+    control: &Sender<ControlRequest>,
+    identity: &Identity,
+) -> Result<Receiver<StreamEvent>, ControlError> {
+    let logs = request["op"] == "subscribe_logs";
+    let mut filter = StreamFilter {
+        skip_logs: !logs,
+        skip_lifecycle: logs,
+        ..StreamFilter::default()
+    };
+    if let Some(server) = request["server"].as_str() {
+        filter.servers.push(server.to_string());
+    }
+    if let Some(levels) = request["level"].as_str() {
+        for label in levels.split(',') {
+            let level = LogLevel::parse(label)
+                .ok_or_else(|| ControlError::new(ErrorKind::Invalid, format!("unknown level `{}`", label)))?;
+            filter.levels.push(level);
+        }
+    }
+    if !request["replay"].is_null() {
+        filter.replay = request["replay"]
+            .as_u64()
+            .ok_or_else(|| ControlError::new(ErrorKind::Invalid, "replay takes a line count"))?
+            as usize;
+    }
+
+    let (events, receiver) = channel();
+    control::request(control, identity, ControlCommand::Subscribe { filter, events })?;
+    Ok(receiver)
+}
+
+// The command line's side, None when no launcher is listening
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    pub fn connect() -> Option<Client> {
+        let stream = UnixStream::connect(socket_path()).ok()?;
+        Some(Client {
+            writer: stream.try_clone().ok()?,
+            reader: BufReader::new(stream),
+        })
+    }
+
+    // Sends one request and waits for its answer, events on the way are skipped
+    pub fn request(&mut self, request: Value) -> ControlResult {
+        let failed = |e: io::Error| ControlError::new(ErrorKind::Failed, format!("launcher socket: {}", e));
+        writeln!(self.writer, "{}", request).map_err(failed)?;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).map_err(failed)? == 0 {
+                return Err(ControlError::new(ErrorKind::Failed, "the launcher closed the socket"));
+            }
+            let line: Value = serde_json::from_str(&line)
+                .map_err(|e| ControlError::new(ErrorKind::Failed, format!("bad answer from the launcher: {}", e)))?;
+            if line.get("event").is_some() {
+                continue;
+            }
+            if line["ok"] == true {
+                return Ok(line["result"].clone());
+            }
+            let kind = line["kind"].as_str().and_then(ErrorKind::parse).unwrap_or(ErrorKind::Failed);
+            return Err(ControlError::new(kind, line["error"].as_str().unwrap_or_default()));
+        }
+    }
+
+    // Turns the connection into a terminal of the launcher's TUI, see the top of the file
+    pub fn attach(mut self, cols: u16, rows: u16) -> Result<(BufReader<UnixStream>, UnixStream), ControlError> {
+        self.request(json!({ "op": "attach", "cols": cols, "rows": rows }))?;
+        Ok((self.reader, self.writer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::Lifecycle;
+
+    // Stands in for the UI loop: knows one server, `Web`, and sends one event per subscription
+    fn fake_launcher() -> Sender<ControlRequest> {
+        let (control, requests) = channel::<ControlRequest>();
+        thread::spawn(move || {
+            for request in requests {
+                let result = match request.command {
+                    ControlCommand::List => Ok(json!([{ "name": "Web" }])),
+                    ControlCommand::Get(name) | ControlCommand::Start(name) if name == "Web" => {
+                        Ok(json!({ "name": name }))
+                    }
+                    ControlCommand::SendCommand { command, .. } => Ok(json!({ "sent": command })),
+                    ControlCommand::Subscribe { events, .. } => {
+                        let _ = events.send(StreamEvent::lifecycle("Web", Lifecycle::Started));
+                        Ok(Value::Null)
+                    }
+                    _ => Err(ControlError::new(ErrorKind::NotFound, "no such server")),
+                };
+                let _ = request.reply.send(result);
+            }
+        });
+        control
+    }
+
+    fn connect() -> Client {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let control = fake_launcher();
+        let (attach, _) = channel();
+        thread::spawn(move || serve(theirs, &control, &attach));
+        Client {
+            writer: ours.try_clone().unwrap(),
+            reader: BufReader::new(ours),
+        }
+    }
+
+    fn read(client: &mut Client) -> Value {
+        let mut line = String::new();
+        client.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn ops_map_to_control_commands() {
+        let mut client = connect();
+        assert_eq!(client.request(json!({ "op": "list" })).unwrap()[0]["name"], "Web");
+        assert_eq!(client.request(json!({ "op": "status", "server": "Web" })).unwrap()["name"], "Web");
+        let sent = client.request(json!({ "op": "send", "server": "Web", "input": "say hi" }));
+        assert_eq!(sent.unwrap()["sent"], "say hi");
+
+        let missing = client.request(json!({ "op": "start", "server": "Nope" })).unwrap_err();
+        assert_eq!(missing.kind, ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn bad_requests_are_answered_not_dropped() {
+        let mut client = connect();
+        for request in [json!({}), json!({ "op": "explode" }), json!({ "op": "start" })] {
+            assert_eq!(client.request(request).unwrap_err().kind, ErrorKind::Invalid);
+        }
+        writeln!(client.writer, "not json").unwrap();
+        assert_eq!(read(&mut client)["kind"], "invalid");
+        // The connection is still usable afterwards
+        assert!(client.request(json!({ "op": "list" })).is_ok());
+    }
+
+    #[test]
+    fn answers_carry_the_request_id() {
+        let mut client = connect();
+        writeln!(client.writer, "{}", json!({ "op": "list", "id": 7 })).unwrap();
+        let answer = read(&mut client);
+        assert_eq!(answer["ok"], true);
+        assert_eq!(answer["id"], 7);
+    }
+
+    #[test]
+    fn subscriptions_answer_then_stream_events() {
+        let mut client = connect();
+        writeln!(client.writer, "{}", json!({ "op": "subscribe_events", "server": "Web" })).unwrap();
+        assert_eq!(read(&mut client)["result"]["subscribed"], "subscribe_events");
+        let event = read(&mut client);
+        assert_eq!(event["event"]["event"], "started");
+
+        let bad_level = json!({ "op": "subscribe_logs", "level": "loud" });
+        assert_eq!(client.request(bad_level).unwrap_err().kind, ErrorKind::Invalid);
+    }
+
+    #[test]
+    fn only_the_owner_can_reach_the_socket() {
+        let dir = std::env::temp_dir().join(format!("launcher-socket-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("run").join("launcher.sock");
+        let (attach, _) = channel();
+        spawn(&path, fake_launcher(), attach).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&path), 0o600);
+        assert!(UnixStream::connect(&path).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+
+use crate::classify::LogLevel;
+use crate::servers::ServerMessage;
+
+// Everything the log panel sees, plus state changes, for subscribers outside the TUI
+
+#[derive(Clone, Copy)]
+pub enum Lifecycle {
+    Started,
+    Ready,
+    Stopping, // a stop was requested, Exited follows once the process is gone
+    Restarting,
+    Exited(Option<i32>),
+}
+
+impl Lifecycle {
+    pub fn label(self) -> &'static str {
+        match self {
+            Lifecycle::Started => "started",
+            Lifecycle::Ready => "ready",
+            Lifecycle::Stopping => "stopping",
+            Lifecycle::Restarting => "restarting",
+            Lifecycle::Exited(_) => "exited",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum StreamEvent {
+    Log(ServerMessage),
+    Lifecycle {
+        name: String,
+        event: Lifecycle,
+        timestamp: DateTime<Local>,
+    },
+}
+
+impl StreamEvent {
+    pub fn lifecycle(name: &str, event: Lifecycle) -> StreamEvent {
+        StreamEvent::Lifecycle {
+            name: name.to_string(),
+            event,
+            timestamp: Local::now(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            StreamEvent::Log(message) => json!({
+                "type": "log",
+                "server": message.name,
+                "timestamp": message.timestamp.to_rfc3339(),
+                "level": message.level.map(|level| level.label()),
+                "stream": message.stream.label(),
+                "text": message.contents,
+            }),
+            StreamEvent::Lifecycle { name, event, timestamp } => {
+                let mut value = json!({
+                    "type": "lifecycle",
+                    "server": name,
+                    "timestamp": timestamp.to_rfc3339(),
+                    "event": event.label(),
+                });
+                if let Lifecycle::Exited(code) = event {
+                    value["exit_code"] = json!(code);
+                }
+                value
+            }
+        }
+    }
+}
+
+// What a subscriber asked for, empty lists let everything through
+#[derive(Clone, Default)]
+pub struct StreamFilter {
+    pub servers: Vec<String>,
+    pub levels: Vec<LogLevel>, // only applies to log lines, lifecycle events always pass
+    pub replay: usize,         // buffered lines to send before the live stream
+    pub skip_logs: bool,       // lifecycle events only
+    pub skip_lifecycle: bool,  // log lines only
+}
+
+impl StreamFilter {
+    pub fn matches(&self, event: &StreamEvent) -> bool {
+        match event {
+            StreamEvent::Log(message) => self.matches_log(message),
+            StreamEvent::Lifecycle { name, .. } => !self.skip_lifecycle && self.matches_server(name),
+        }
+    }
+
+    pub fn matches_log(&self, message: &ServerMessage) -> bool {
+        !self.skip_logs
+            && self.matches_server(&message.name)
+            && (self.levels.is_empty()
+                || message.level.is_some_and(|level| self.levels.contains(&level)))
+    }
+
+    fn matches_server(&self, name: &str) -> bool {
+        self.servers.is_empty() || self.servers.iter().any(|server| server == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::servers::LogStream;
+
+    fn line(server: &str, level: Option<LogLevel>) -> StreamEvent {
+        StreamEvent::Log(ServerMessage::output(server, "hello", LogStream::Stdout, level))
+    }
+
+    #[test]
+    fn empty_filter_lets_everything_through() {
+        let filter = StreamFilter::default();
+        assert!(filter.matches(&line("Web", None)));
+        assert!(filter.matches(&StreamEvent::lifecycle("Web", Lifecycle::Started)));
+    }
+
+    #[test]
+    fn filters_by_server_and_level() {
+        let filter = StreamFilter {
+            servers: vec!["Web".to_string()],
+            levels: vec![LogLevel::Warn, LogLevel::Error],
+            ..StreamFilter::default()
+        };
+        assert!(filter.matches(&line("Web", Some(LogLevel::Error))));
+        assert!(!filter.matches(&line("Web", Some(LogLevel::Info))));
+        // Lines no rule classified don't pass a level filter
+        assert!(!filter.matches(&line("Web", None)));
+        assert!(!filter.matches(&line("Minecraft", Some(LogLevel::Error))));
+        // Lifecycle events ignore the levels but not the servers
+        assert!(filter.matches(&StreamEvent::lifecycle("Web", Lifecycle::Ready)));
+        assert!(!filter.matches(&StreamEvent::lifecycle("Minecraft", Lifecycle::Ready)));
+    }
+
+    #[test]
+    fn json_shape() {
+        let log = line("Web", Some(LogLevel::Warn)).to_json();
+        assert_eq!(log["type"], "log");
+        assert_eq!(log["server"], "Web");
+        assert_eq!(log["level"], "WARN");
+        assert_eq!(log["stream"], "stdout");
+        assert_eq!(log["text"], "hello");
+
+        let exited = StreamEvent::lifecycle("Web", Lifecycle::Exited(Some(1))).to_json();
+        assert_eq!(exited["event"], "exited");
+        assert_eq!(exited["exit_code"], 1);
+        let started = StreamEvent::lifecycle("Web", Lifecycle::Started).to_json();
+        assert!(started.get("exit_code").is_none());
+    }
+    #[test]
+    fn subscriptions_can_skip_a_kind() {
+        let events_only = StreamFilter { skip_logs: true, ..StreamFilter::default() };
+        assert!(!events_only.matches(&line("Web", None)));
+        assert!(events_only.matches(&StreamEvent::lifecycle("Web", Lifecycle::Started)));
+
+        let logs_only = StreamFilter { skip_lifecycle: true, ..StreamFilter::default() };
+        assert!(logs_only.matches(&line("Web", None)));
+        assert!(!logs_only.matches(&StreamEvent::lifecycle("Web", Lifecycle::Started)));
+    }
+}
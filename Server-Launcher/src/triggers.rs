@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use regex::Regex;
+
+// What to do when a server prints a matching line
+#[derive(Clone, Debug, PartialEq)]
+pub enum TriggerAction {
+    Restart,
+    Stop,
+    SendCommand(String), // Written to the server's stdin
+    RunHook(String),     // Shell command run from the server's directory
+    Notify(String),      // Shown in the log panel, empty uses the matched line
+    MarkReady,
+}
+
+impl TriggerAction {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TriggerAction::Restart => "restart",
+            TriggerAction::Stop => "stop",
+            TriggerAction::SendCommand(_) => "command",
+            TriggerAction::RunHook(_) => "hook",
+            TriggerAction::Notify(_) => "notify",
+            TriggerAction::MarkReady => "ready",
+        }
+    }
+
+    pub fn argument(&self) -> &str {
+        match self {
+            TriggerAction::SendCommand(text)
+            | TriggerAction::RunHook(text)
+            | TriggerAction::Notify(text) => text,
+            _ => "",
+        }
+    }
+
+    pub fn parse(kind: &str, argument: &str) -> Option<TriggerAction> {
+        let argument = argument.to_string();
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "restart" => Some(TriggerAction::Restart),
+            "stop" => Some(TriggerAction::Stop),
+            "command" => Some(TriggerAction::SendCommand(argument)),
+            "hook" => Some(TriggerAction::RunHook(argument)),
+            "notify" => Some(TriggerAction::Notify(argument)),
+            "ready" => Some(TriggerAction::MarkReady),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Trigger {
+    pub pattern: Regex,
+    pub action: TriggerAction,
+    pub cooldown: Duration, // Matches inside this window after firing are ignored
+}
+
+impl Trigger {
+    pub fn new(
+        pattern: &str,
+        action: TriggerAction,
+        cooldown: Duration,
+    ) -> Result<Trigger, regex::Error> {
+        Ok(Trigger {
+            pattern: Regex::new(pattern)?,
+            action,
+            cooldown,
+        })
+    }
+}
+
+// Every trigger whose pattern matches the line, with its position in the list
+pub fn matching<'a>(
+    triggers: &'a [Trigger],
+    line: &'a str,
+) -> impl Iterator<Item = (usize, &'a Trigger)> + 'a {
+    triggers
+        .iter()
+        .enumerate()
+        .filter(move |(_, trigger)| trigger.pattern.is_match(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(pattern: &str, action: TriggerAction) -> Trigger {
+        Trigger::new(pattern, action, Duration::ZERO).unwrap()
+    }
+
+    #[test]
+    fn every_matching_trigger_with_its_position() {
+        let triggers = [
+            trigger("Done", TriggerAction::MarkReady),
+            trigger("OutOfMemory", TriggerAction::Restart),
+            trigger(r"Done \(", TriggerAction::Notify(String::new())),
+        ];
+        let found: Vec<usize> = matching(&triggers, "Done (3.2s)!").map(|(rule, _)| rule).collect();
+        assert_eq!(found, [0, 2]);
+        assert_eq!(matching(&triggers, "Saving chunks").count(), 0);
+    }
+
+    #[test]
+    fn actions_parse_back() {
+        let actions = [
+            TriggerAction::Restart,
+            TriggerAction::Stop,
+            TriggerAction::SendCommand("save-all".to_string()),
+            TriggerAction::RunHook("./backup.sh".to_string()),
+            TriggerAction::Notify("up".to_string()),
+            TriggerAction::MarkReady,
+        ];
+        for action in actions {
+            assert_eq!(TriggerAction::parse(action.kind(), action.argument()), Some(action));
+        }
+        assert_eq!(TriggerAction::parse(" Restart ", "ignored"), Some(TriggerAction::Restart));
+        assert_eq!(TriggerAction::parse("reboot", ""), None);
+    }
+
+    #[test]
+    fn bad_pattern_is_an_error() {
+        assert!(Trigger::new("(unclosed", TriggerAction::Stop, Duration::ZERO).is_err());
+    }
+}
@@ -1,28 +1,146 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, MouseEvent,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{enable_raw_mode, EnterAlternateScreen},
 };
 use ratatui::{
     prelude::*,
     style::{Modifier, Style},
-    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Paragraph, Tabs},
 };
-use std::collections::{vec_deque, HashMap, VecDeque};
+use chrono::{DateTime, Local};
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
-use std::io;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
 
+mod confirm;
+mod details_page;
+mod form;
+mod audit_page;
+mod client;
+mod history_page;
+mod host_page;
+mod keys;
+mod mouse;
+mod remote;
+mod servers_page;
+mod settings_page;
+mod shutdown;
+mod ssh;
+mod view;
+
+use crate::api;
+use crate::audit::{self, AuditAction, Outcome};
+use crate::classify::{LogLevel, LogPreset, LogRule};
+use crate::auth::{Identity, Permission};
+use crate::control::{ControlError, ControlRequest, ErrorKind};
+use crate::serverdef;
+use crate::db;
+use crate::detached;
+use crate::keymap::{Action, Hint, Keymap};
+use crate::logfile::{self, LogWriter};
 use crate::servers::{self, MessageType, ServerLifecycleEvent, ServerMessage};
+use crate::settings::{QuitPolicy, Settings, TimestampFormat};
+use crate::socket;
+use crate::stream::{Lifecycle, StreamEvent, StreamFilter};
+use crate::triggers::{Trigger, TriggerAction};
+use crate::usage::{self, LineCount, UsageHistory};
+use confirm::PendingAction;
+use form::FormResult;
+use mouse::Buttons;
+use view::View;
+use rusqlite::Connection;
 use crate::{db::Server, servers::ServerHandle};
 
+// One tab of the dashboard. Pages keep their own view state, View what the terminal's pages share
+// and App what every terminal shares
+trait Page {
+    fn title(&self) -> &'static str;
+    fn render(&mut self, frame: &mut Frame, area: Rect, app: &App, view: &View);
+    // Returns false for keys the page has no use for so the global ones still apply
+    fn handle_key(&mut self, app: &mut App, view: &mut View, key: KeyEvent) -> bool;
+    // Lines of hints for the controls panel, see Keymap::describe
+    fn controls(&self) -> &'static [&'static [Hint]];
+    // A page typing into a prompt gets every key, quit and page switching included
+    fn captures_input(&self) -> bool {
+        false
+    }
+    // Clicks, drags and the wheel over the page's area, buttons and tabs are handled before
+    fn handle_mouse(&mut self, _app: &mut App, _view: &mut View, _mouse: MouseEvent) {}
+}
+
+#[derive(Default)]
+struct LogSearch {
+    query: String,
+    pattern: Option<Regex>,
+    error: Option<String>,
+    case_sensitive: bool,
+    filter: bool,        // hide lines that do not match
+    selected_only: bool, // only search the selected server's output
+    current: Option<usize>, // index into App::logs of the focused match
+}
+
+impl LogSearch {
+    fn compile(&mut self) {
+        self.current = None;
+        if self.query.is_empty() {
+            self.pattern = None;
+            self.error = None;
+            return;
+        }
+        match RegexBuilder::new(&self.query)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+        {
+            Ok(pattern) => {
+                self.pattern = Some(pattern);
+                self.error = None;
+            }
+            Err(_) => {
+                self.pattern = None;
+                self.error = Some("invalid regex".to_string());
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.query.clear();
+        self.compile();
+    }
+}
+
+// How often the shared state takes in what the servers sent
+const TICK: Duration = Duration::from_millis(50);
+// How long servers get to stop on their own when the launcher quits, like `stop` on the command line
+const QUIT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The shared core: servers, their logs and the channels they report on. Every terminal
+// (this one, SSH sessions, attached ones) draws it through its own Session.
 struct App {
     counter: i32,
-    // Placeholder for server logs
     logs: VecDeque<ServerMessage>,
+    logs_dropped: u64, // lines trimmed off the front of `logs` so far
+    settings: Settings,
+    log_writer: Option<LogWriter>,
+    started_at: DateTime<Local>,
+    server_started: HashMap<String, DateTime<Local>>, // last launch of each server, kept after exit
+    runs: HashMap<u64, i64>, // run_id of each live handle to its row in the runs table
+    restarts: HashMap<String, u32>, // restarts of each server this session
+    last_exit: HashMap<String, i32>, // exit code of each server's previous run
+    usage: HashMap<String, UsageHistory>, // cpu/memory samples of each running server
+    usage_sampled_at: Instant,
+    lines_logged: HashMap<String, LineCount>, // keyed by server, "" for the launcher's own lines
+    trigger_fired: HashMap<(String, usize), Instant>, // keyed by server and rule, survives restarts
     available_servers: Vec<Server>,
-    selected_server: usize,
     allocated_servers: HashMap<String, ServerHandle>,
     // direct log data
     log_sender: Sender<ServerMessage>,
@@ -30,27 +148,67 @@ struct App {
     // server open / close
     server_event_sender: Sender<ServerLifecycleEvent>,
     server_event_receiver: Receiver<ServerLifecycleEvent>,
+    // requests from the HTTP API
+    control_sender: Sender<ControlRequest>,
+    control_receiver: Receiver<ControlRequest>,
+    subscribers: Vec<(StreamFilter, Sender<StreamEvent>)>, // log stream connections
+    keymap: Keymap, // shared by every terminal
+    db: Connection,
 }
 
 impl App {
-    fn new() -> App {
+    fn new(db: Connection) -> App {
         let (log_sender, log_receiver) = channel();
         let (server_event_sender, server_event_receiver) = channel();
+        let (control_sender, control_receiver) = channel();
+        let settings = Settings::load(&db);
         App {
             counter: 0,
-            logs: VecDeque::from(vec![ServerMessage{name: "".to_string() ,contents: "Log Panel Initialized".to_string() , message_type: MessageType::None}]),
-            available_servers: vec![
-                Server::default().id(1).name("Server 1").path("/C").executable("server.jar").test_server(true).display_color(Color::Rgb(30, 230, 180)),
-                Server::default().id(2).name("Timer 1").path("/Users/student/Projects/Server-Launcher/Server-Launcher/").executable("python3").args(vec!["-u".to_string(), "timer.py".to_string(), "8".to_string()]),
-                Server::default().id(3).name("Ascii Image").path("/Users/student/Projects/Server-Launcher/Server-Launcher/").executable("python3").args(vec!["-u".to_string(), "ascii_image.py".to_string()]).autostart(true),
-                Server::default().id(6).name("Minecraft Server").path("/Users/student/Downloads/MinecraftServer").executable("./start_Server.sh")
-            ],
-            selected_server: 0,
+            logs: VecDeque::from(vec![ServerMessage::new("", "Log Panel Initialized", MessageType::None)]),
+            logs_dropped: 0,
+            log_writer: settings.persist_logs.then(|| LogWriter::new(logfile::LOG_DIR)),
+            keymap: Keymap::preset(settings.key_preset),
+            settings,
+            started_at: Local::now(),
+            server_started: HashMap::new(),
+            runs: HashMap::new(),
+            restarts: HashMap::new(),
+            last_exit: HashMap::new(),
+            usage: HashMap::new(),
+            usage_sampled_at: Instant::now(),
+            lines_logged: HashMap::new(),
+            trigger_fired: HashMap::new(),
+            available_servers: load_or_seed_servers(&db),
             allocated_servers: HashMap::new(),
             log_sender,
             log_receiver,
             server_event_sender,
             server_event_receiver,
+            control_sender,
+            control_receiver,
+            subscribers: Vec::new(),
+            db,
+        }
+    }
+
+    // The preset with the keymap file on top, problems with the file go to the log
+    fn load_keymap(&mut self) {
+        let (keymap, problems) = Keymap::load(self.settings.key_preset, Path::new(&self.settings.keymap_file));
+        self.keymap = keymap;
+        for problem in problems {
+            self.push_log(ServerMessage::new("", format!("Keymap: {}", problem), MessageType::Err));
+        }
+    }
+
+    // Pick the list up from the db again after it was changed
+    fn reload_servers(&mut self) {
+        match db::load_servers(&self.db) {
+            Ok(servers) => self.available_servers = servers,
+            Err(e) => self.push_log(ServerMessage::new(
+                "",
+                format!("Failed to load servers: {}", e),
+                MessageType::Err,
+            )),
         }
     }
 
@@ -58,38 +216,17 @@ impl App {
         self.counter += 1;
 
         let mut server_names_to_remove = Vec::new();
+        let mut messages = Vec::new();
 
         for (name, handle) in self.allocated_servers.iter_mut() {
-            if handle.running {
-                // Only check/update servers that are supposed to be running
-                if let Some(ref mut child) = handle.child {
-                    match child.try_wait() {
-                        Ok(Some(_status)) => {
-                            // Process has exited
-                            self.logs.push_back(ServerMessage {
-                                name: "".to_string(),
-                                contents: format!("Server {} process has exited.", name),
-                                message_type: MessageType::Main }
-                            );
-                            handle.running = false; // Mark as not running
-                        }
-                        Ok(None) => { /* Process is still running */ }
-                        Err(e) => {
-                            self.logs.push_back(ServerMessage {
-                                name: "".to_string(),
-                                contents: format!("Error checking status for server {}: {}. Marking as not running.",name, e),
-                                message_type: MessageType::Err }
-                            );
-                            handle.running = false; // Mark as not running on error
-                        }
-                    }
-                } else {
-                    // For servers without a child process (e.g., dummy servers),
-                    // their `running` flag is primarily managed by lifecycle events
-                    // (like Exited) or explicit `kill_process` calls.
-                    // `try_wait` is not applicable here.
-                }
+            // Only check/update servers that are supposed to be running. The end of their
+            // output says nothing, a server may close a pipe and keep going.
+            if handle.running && handle.process_gone() {
+                messages.push(ServerMessage::new("", format!("Server {} process has exited.", name), MessageType::Main));
+                handle.running = false; // Mark as not running
             }
+            // Dummy servers have no process, their `running` flag is managed by lifecycle
+            // events (like Exited) or explicit `kill_process` calls.
 
             // If, after checks or an explicit kill, the handle is marked as not running, schedule for removal.
             if !handle.running {
@@ -98,12 +235,605 @@ impl App {
         }
 
         for name in server_names_to_remove {
-            self.allocated_servers.remove(&name);
+            if let Some(handle) = self.allocated_servers.remove(&name) {
+                self.finish_run(handle);
+            }
+        }
+        for message in messages {
+            self.push_log(message);
+        }
+
+        let elapsed = self.usage_sampled_at.elapsed();
+        if elapsed >= usage::SAMPLE_INTERVAL {
+            self.usage_sampled_at = Instant::now();
+            self.sample_usage();
+            for count in self.lines_logged.values_mut() {
+                count.sample(elapsed);
+            }
+        }
+    }
+
+    fn sample_usage(&mut self) {
+        self.usage
+            .retain(|name, _| self.allocated_servers.contains_key(name));
+        for (name, handle) in &self.allocated_servers {
+            let Some(pid) = handle.pid() else {
+                continue;
+            };
+            let history = self
+                .usage
+                .entry(name.clone())
+                .or_insert_with(|| UsageHistory::new(handle.run_id));
+            // A restart starts a fresh graph
+            if history.run_id != handle.run_id {
+                *history = UsageHistory::new(handle.run_id);
+            }
+            history.sample(pid);
+        }
+    }
+
+    // Refusals are logged too, so remote requests leave a trace in the panel
+    fn authorize(&mut self, who: &Identity, permission: Permission, server: Option<&str>) -> Result<(), ControlError> {
+        who.check(permission, server).map_err(|e| {
+            self.push_log(ServerMessage::new("", format!("Denied: {}", e), MessageType::Err));
+            ControlError::new(ErrorKind::Forbidden, e)
+        })
+    }
+
+    // Every action below goes through here so the audit log sees it, allowed or not
+    fn audit<T>(&mut self, who: &Identity, action: AuditAction, target: &str, detail: &str, result: &Result<T, ControlError>) {
+        if let Err(e) = audit::record(&self.db, who, action, target, detail, Outcome::of(result)) {
+            self.push_log(ServerMessage::new("", format!("Failed to write the audit log: {}", e), MessageType::Err));
+        }
+    }
+
+    // Errors are logged as well, the result is for callers that answer someone else (the API)
+    fn launch_server(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        let result = self.try_launch(who, name);
+        self.audit(who, AuditAction::Launch, name, "", &result);
+        result
+    }
+
+    fn try_launch(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        let Some(server) = self.available_servers.iter().find(|s| s.name == name) else {
+            return Err(ControlError::new(ErrorKind::NotFound, format!("No server named {}", name)));
+        };
+        let server = server.clone();
+        self.authorize(who, Permission::Control, Some(name))?;
+        if self.allocated_servers.contains_key(name) {
+            return Err(ControlError::new(ErrorKind::Conflict, format!("Server {} is already running", name)));
+        }
+        // Started from the command line, it already has a supervisor
+        if let Some(running) = detached::read(name) {
+            let message = format!(
+                "Server {} is already running in the background (supervisor pid {}), stop it with `stop {}`",
+                name, running.supervisor_pid, name
+            );
+            self.push_log(ServerMessage::new("", message.clone(), MessageType::Err));
+            return Err(ControlError::new(ErrorKind::Conflict, message));
+        }
+
+        match servers::launch(
+            &server,
+            self.log_sender.clone(),
+            self.server_event_sender.clone(),
+        ) {
+            Ok(handle) => {
+                match db::start_run(&self.db, &server, handle.started_at) {
+                    Ok(row) => {
+                        self.runs.insert(handle.run_id, row);
+                    }
+                    Err(e) => self.push_log(ServerMessage::new(
+                        "",
+                        format!("Failed to record run of {}: {}", name, e),
+                        MessageType::Err,
+                    )),
+                }
+                self.server_started.insert(name.to_string(), handle.started_at);
+                self.allocated_servers.insert(name.to_string(), handle);
+                self.broadcast(StreamEvent::lifecycle(name, Lifecycle::Started));
+                self.push_log(ServerMessage::new(
+                    "",
+                    format!("Server {} launched successfully.", name),
+                    MessageType::Main,
+                ));
+                Ok(())
+            }
+            Err(e) => {
+                let message = format!("Failed to launch server {}: {}", name, e);
+                self.push_log(ServerMessage::new("", message.clone(), MessageType::Err));
+                Err(ControlError::new(ErrorKind::Failed, message))
+            }
+        }
+    }
+
+    // Close the run's history row once its handle is dropped
+    fn finish_run(&mut self, mut handle: ServerHandle) {
+        let exit_code = handle.exit_code();
+        if let Some(code) = exit_code {
+            self.last_exit.insert(handle.name.clone(), code);
+        }
+        self.broadcast(StreamEvent::lifecycle(&handle.name, Lifecycle::Exited(exit_code)));
+        let Some(row) = self.runs.remove(&handle.run_id) else {
+            return;
+        };
+        if let Err(e) = db::finish_run(&self.db, row, exit_code) {
+            self.push_log(ServerMessage::new(
+                "",
+                format!("Failed to record exit of {}: {}", handle.name, e),
+                MessageType::Err,
+            ));
+        }
+    }
+
+    fn kill_server(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        let result = self.try_kill(who, name);
+        self.audit(who, AuditAction::Kill, name, "", &result);
+        result
+    }
+
+    fn try_kill(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        self.authorize(who, Permission::Control, Some(name))?;
+        let (message, result) = match self.allocated_servers.get_mut(name) {
+            Some(handle) => match handle.kill_process() {
+                Ok(_) => (ServerMessage::new("", format!("Attempting to kill server: {}. It will be removed from the list if successful.", name), MessageType::Main), Ok(())),
+                Err(e) => {
+                    let e = format!("Failed to kill server {}: {}", name, e);
+                    (ServerMessage::new("", e.clone(), MessageType::Err), Err(ControlError::new(ErrorKind::Failed, e)))
+                }
+            },
+            None => {
+                let e = format!("Server {} is not currently running or allocated.", name);
+                (ServerMessage::new("", e.clone(), MessageType::Err), Err(ControlError::new(ErrorKind::Conflict, e)))
+            }
+        };
+        if result.is_ok() {
+            self.broadcast(StreamEvent::lifecycle(name, Lifecycle::Stopping));
+        }
+        self.push_log(message);
+        result
+    }
+
+    fn restart_server(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        let result = self.try_restart(who, name);
+        self.audit(who, AuditAction::Restart, name, "", &result);
+        result
+    }
+
+    // Launches through try_launch so the restart is one entry in the audit log
+    fn try_restart(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        self.authorize(who, Permission::Control, Some(name))?;
+        // Checked up front so subscribers only hear about restarts that get under way
+        if !self.available_servers.iter().any(|s| s.name == name) {
+            return Err(ControlError::new(ErrorKind::NotFound, format!("No server named {}", name)));
+        }
+        if !self.allocated_servers.contains_key(name) {
+            if let Some(running) = detached::read(name) {
+                let message = format!(
+                    "Server {} is running in the background (supervisor pid {}), restart it with `restart {}`",
+                    name, running.supervisor_pid, name
+                );
+                return Err(ControlError::new(ErrorKind::Conflict, message));
+            }
+        }
+        self.broadcast(StreamEvent::lifecycle(name, Lifecycle::Restarting));
+        if let Some(mut handle) = self.allocated_servers.remove(name) {
+            if let Err(e) = handle.kill_process() {
+                self.push_log(ServerMessage::new("", e, MessageType::Err));
+            }
+            self.finish_run(handle);
+        }
+        self.try_launch(who, name)?;
+        // Only restarts that started the server again count
+        *self.restarts.entry(name.to_string()).or_default() += 1;
+        Ok(())
+    }
+
+    // A line typed into the server's console
+    fn send_command(&mut self, who: &Identity, name: &str, command: &str) -> Result<(), ControlError> {
+        let result = self.try_send_command(who, name, command);
+        self.audit(who, AuditAction::Command, name, command, &result);
+        result
+    }
+
+    fn try_send_command(&mut self, who: &Identity, name: &str, command: &str) -> Result<(), ControlError> {
+        self.authorize(who, Permission::Control, Some(name))?;
+        let Some(handle) = self.allocated_servers.get_mut(name) else {
+            return Err(ControlError::new(ErrorKind::Conflict, format!("Server {} is not running", name)));
+        };
+        handle
+            .send_input(command)
+            .map_err(|e| ControlError::new(ErrorKind::Failed, e))
+    }
+
+    // Inserts the server when its id is unset, otherwise overwrites the stored one. Returns its id.
+    fn save_server(&mut self, who: &Identity, server: Server) -> Result<i32, ControlError> {
+        let action = match self.available_servers.iter().any(|s| s.id == server.id) {
+            true => AuditAction::EditServer,
+            false => AuditAction::AddServer,
+        };
+        let name = server.name.clone();
+        let result = self.try_save_server(who, server);
+        self.audit(who, action, &name, "", &result);
+        result
+    }
+
+    fn try_save_server(&mut self, who: &Identity, server: Server) -> Result<i32, ControlError> {
+        self.authorize(who, Permission::Edit, Some(&server.name))?;
+        serverdef::validate(&server, &self.available_servers)
+            .map_err(|e| ControlError::new(ErrorKind::Invalid, e))?;
+        let current = self.available_servers.iter().find(|s| s.id == server.id);
+        if let Some(current) = current {
+            if current.name != server.name && self.allocated_servers.contains_key(&current.name) {
+                return Err(ControlError::new(ErrorKind::Conflict, format!("Stop {} before renaming it", current.name)));
+            }
+        }
+        let editing = current.is_some();
+        let saved = if editing {
+            db::update_server(&self.db, &server).map(|_| server.id)
+        } else {
+            db::insert_server(&self.db, &server)
+        };
+        let id = saved.map_err(|e| ControlError::new(ErrorKind::Failed, format!("Failed to save server: {}", e)))?;
+        self.reload_servers();
+        let verb = if editing { "Updated" } else { "Added" };
+        self.push_log(ServerMessage::new("", format!("{} server {}", verb, server.name), MessageType::Main));
+        Ok(id)
+    }
+
+    // Stops it first when it is running
+    fn delete_server(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        let result = self.try_delete_server(who, name);
+        self.audit(who, AuditAction::DeleteServer, name, "", &result);
+        result
+    }
+
+    fn try_delete_server(&mut self, who: &Identity, name: &str) -> Result<(), ControlError> {
+        self.authorize(who, Permission::Edit, Some(name))?;
+        let Some(id) = self.available_servers.iter().find(|s| s.name == name).map(|s| s.id) else {
+            return Err(ControlError::new(ErrorKind::NotFound, format!("No server named {}", name)));
+        };
+        if let Some(mut handle) = self.allocated_servers.remove(name) {
+            if let Err(e) = handle.kill_process() {
+                self.push_log(ServerMessage::new("", e, MessageType::Err));
+            }
+            self.finish_run(handle);
+        }
+        match db::delete_server(&self.db, id) {
+            Ok(_) => {
+                self.reload_servers();
+                self.push_log(ServerMessage::new("", format!("Deleted server {}", name), MessageType::Main));
+                Ok(())
+            }
+            Err(e) => {
+                let message = format!("Failed to delete server {}: {}", name, e);
+                self.push_log(ServerMessage::new("", message.clone(), MessageType::Err));
+                Err(ControlError::new(ErrorKind::Failed, message))
+            }
         }
     }
+
+    // Writes a file on the launcher's host, so viewers may not
+    fn export_logs(&mut self, who: &Identity, lines: &[usize]) -> Result<PathBuf, ControlError> {
+        let result = self.try_export_logs(who, lines);
+        let path = result.as_ref().map(|path| path.display().to_string()).unwrap_or_default();
+        self.audit(who, AuditAction::ExportLogs, &path, &format!("{} lines", lines.len()), &result);
+        result
+    }
+
+    fn try_export_logs(&mut self, who: &Identity, lines: &[usize]) -> Result<PathBuf, ControlError> {
+        self.authorize(who, Permission::Control, None)?;
+        match logfile::export(lines.iter().map(|&i| &self.logs[i])) {
+            Ok(path) => {
+                self.push_log(ServerMessage::new(
+                    "",
+                    format!("Exported log panel to {}", path.display()),
+                    MessageType::Main,
+                ));
+                Ok(path)
+            }
+            Err(e) => {
+                let message = format!("Failed to export logs: {}", e);
+                self.push_log(ServerMessage::new("", message.clone(), MessageType::Err));
+                Err(ControlError::new(ErrorKind::Failed, message))
+            }
+        }
+    }
+
+    fn handle_server_event(&mut self, event: ServerLifecycleEvent) {
+        match event {
+            ServerLifecycleEvent::Exited { name, run_id } => {
+                // A restarted server already has a new handle, the old run's exit is old news
+                let Some(handle) = self.allocated_servers.get_mut(&name) else {
+                    return;
+                };
+                if handle.run_id != run_id {
+                    return;
+                }
+                handle.running = false;
+                self.push_log(ServerMessage::new("", format!("Server {} has Exited" , name), MessageType::Err));
+            }
+            ServerLifecycleEvent::Triggered { name, run_id, rule, action, cooldown, line } => {
+                if self.allocated_servers.get(&name).map(|handle| handle.run_id) != Some(run_id) {
+                    return;
+                }
+                let key = (name.clone(), rule);
+                if let Some(fired) = self.trigger_fired.get(&key) {
+                    if fired.elapsed() < cooldown {
+                        return;
+                    }
+                }
+                self.trigger_fired.insert(key, Instant::now());
+                self.run_trigger(&name, action, &line);
+            }
+        }
+    }
+
+    fn run_trigger(&mut self, name: &str, action: TriggerAction, line: &str) {
+        match action {
+            TriggerAction::Restart => {
+                self.push_log(ServerMessage::new("", format!("Trigger: restarting {}", name), MessageType::Main));
+                let _ = self.restart_server(&Identity::trigger(), name);
+            }
+            TriggerAction::Stop => {
+                self.push_log(ServerMessage::new("", format!("Trigger: stopping {}", name), MessageType::Main));
+                let _ = self.kill_server(&Identity::trigger(), name);
+            }
+            TriggerAction::SendCommand(command) => {
+                if !self.allocated_servers.contains_key(name) {
+                    return;
+                }
+                let message = match self.send_command(&Identity::trigger(), name, &command) {
+                    Ok(_) => ServerMessage::new("", format!("Trigger: sent `{}` to {}", command, name), MessageType::Main),
+                    Err(e) => ServerMessage::new("", e.message, MessageType::Err),
+                };
+                self.push_log(message);
+            }
+            TriggerAction::RunHook(hook) => {
+                if let Some(server) = self.available_servers.iter().find(|s| s.name == name) {
+                    servers::run_hook(server, &hook, line, self.log_sender.clone());
+                }
+            }
+            TriggerAction::Notify(text) => {
+                let text = if text.is_empty() { line.trim().to_string() } else { text };
+                self.push_log(ServerMessage::new("", format!("[notify] {}: {}", name, text), MessageType::Main));
+            }
+            TriggerAction::MarkReady => {
+                if let Some(handle) = self.allocated_servers.get_mut(name) {
+                    handle.ready = true;
+                }
+                self.broadcast(StreamEvent::lifecycle(name, Lifecycle::Ready));
+                self.push_log(ServerMessage::new("", format!("Server {} is ready", name), MessageType::Main));
+            }
+        }
+    }
+
+    fn push_log(&mut self, message: ServerMessage) {
+        if let Some(writer) = self.log_writer.as_mut() {
+            if let Err(e) = writer.write(&message) {
+                // Stop persisting instead of failing on every following line
+                self.log_writer = None;
+                self.push_log(ServerMessage::new(
+                    "",
+                    format!("Could not write log file, persistence disabled: {}", e),
+                    MessageType::Err,
+                ));
+            }
+        }
+        if !self.subscribers.is_empty() {
+            self.broadcast(StreamEvent::Log(message.clone()));
+        }
+        self.lines_logged.entry(message.name.clone()).or_default().count();
+        self.logs.push_back(message);
+    }
+
+    // Hands the event to every subscriber that wants it, closed connections are dropped
+    fn broadcast(&mut self, event: StreamEvent) {
+        self.subscribers
+            .retain(|(filter, sender)| !filter.matches(&event) || sender.send(event.clone()).is_ok());
+    }
+
+    // Replays the newest buffered lines that pass the filter, then keeps streaming
+    fn subscribe(&mut self, filter: StreamFilter, sender: Sender<StreamEvent>) {
+        let replay: Vec<&ServerMessage> = self
+            .logs
+            .iter()
+            .rev()
+            .filter(|message| filter.matches_log(message))
+            .take(filter.replay)
+            .collect();
+        for message in replay.into_iter().rev() {
+            if sender.send(StreamEvent::Log(message.clone())).is_err() {
+                return;
+            }
+        }
+        self.subscribers.push((filter, sender));
+    }
+
+    fn timestamp_label(&self, message: &ServerMessage, format: TimestampFormat) -> Option<String> {
+        match format {
+            TimestampFormat::Off => None,
+            TimestampFormat::WallClock => Some(message.timestamp.format("%H:%M:%S").to_string()),
+            TimestampFormat::Relative => {
+                // Launcher lines are relative to the launcher itself
+                let start = self
+                    .server_started
+                    .get(&message.name)
+                    .copied()
+                    .unwrap_or(self.started_at);
+                let elapsed = (message.timestamp - start).num_seconds();
+                let sign = if elapsed < 0 { "-" } else { "+" };
+                let elapsed = elapsed.abs();
+                Some(format!(
+                    "{}{:02}:{:02}:{:02}",
+                    sign,
+                    elapsed / 3600,
+                    elapsed % 3600 / 60,
+                    elapsed % 60
+                ))
+            }
+        }
+    }
+
+    // Keep the log buffer bounded, views catch up with what was dropped on their own
+    fn trim_logs(&mut self) {
+        if self.logs.len() > self.settings.log_buffer_lines {
+            let excess = self.logs.len() - self.settings.log_buffer_lines;
+            self.logs.drain(0..excess);
+            self.logs_dropped += excess as u64;
+        }
+    }
+
+    // Counts every line ever logged, dropped ones included
+    fn logs_total(&self) -> u64 {
+        self.logs_dropped + self.logs.len() as u64
+    }
+
+    // Takes in whatever the servers, the API and the socket sent since the last call
+    fn pump(&mut self) {
+        while let Ok(log_message) = self.log_receiver.try_recv() {
+            self.push_log(log_message);
+        }
+
+        while let Ok(event) = self.server_event_receiver.try_recv() {
+            self.handle_server_event(event);
+        }
+
+        while let Ok(request) = self.control_receiver.try_recv() {
+            self.handle_control(request);
+        }
+
+        self.on_tick();
+        self.trim_logs();
+    }
+
+    // Names of the servers with a live process, sorted for the quit dialog
+    fn running_servers(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .allocated_servers
+            .iter()
+            .filter(|(_, handle)| handle.running)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    // Applies the quit policy to whatever is still running, Ask with nobody to ask means Stop.
+    // The terminal is already restored by now, so progress goes to stdout. After a SIGHUP
+    // nobody reads it and the writes fail, which must not stop the servers from being handled.
+    fn shut_down(&mut self, policy: QuitPolicy) {
+        let mut names = self.running_servers();
+        let mut out = io::stdout();
+        if policy == QuitPolicy::Leave {
+            // Whatever can't be handed over is stopped like with `stop`
+            names.retain(|name| !self.leave_running(name, &mut out));
+        }
+        if names.is_empty() {
+            return;
+        }
+        // All of them get the chance to save at once, then stragglers are killed
+        let _ = writeln!(out, "Stopping {}...", names.join(", "));
+        for name in &names {
+            self.allocated_servers[name].terminate();
+        }
+        // Adopted servers are waited for by pid like our own children, test servers have
+        // nothing to wait for
+        let deadline = Instant::now() + QUIT_STOP_TIMEOUT;
+        while Instant::now() < deadline
+            && names.iter().any(|name| {
+                self.allocated_servers
+                    .get_mut(name)
+                    .is_some_and(|handle| handle.pid().is_some() && !handle.process_gone())
+            })
+        {
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        let who = Identity::local();
+        for name in names {
+            let Some(mut handle) = self.allocated_servers.remove(&name) else {
+                continue;
+            };
+            let killed = handle.pid().is_some() && !handle.process_gone();
+            if killed {
+                let _ = handle.kill_process();
+            }
+            self.finish_run(handle);
+            self.audit(&who, AuditAction::Kill, &name, "launcher quit", &Ok(()));
+            let _ = if killed {
+                writeln!(out, "{} did not stop in time and was killed", name)
+            } else {
+                writeln!(out, "{} stopped", name)
+            };
+        }
+    }
+
+    // Gives the server, still running, to a background supervisor along with its open run.
+    // False when it could not be handed over and has to be stopped instead.
+    fn leave_running(&mut self, name: &str, out: &mut impl Write) -> bool {
+        let Some(handle) = self.allocated_servers.get_mut(name) else {
+            return true;
+        };
+        let run = self.runs.get(&handle.run_id).copied();
+        if let Err(e) = detached::hand_over(handle, run) {
+            let _ = writeln!(out, "Could not move {} to the background ({}), stopping it", name, e);
+            return false;
+        }
+        if let Some(handle) = self.allocated_servers.remove(name) {
+            self.runs.remove(&handle.run_id);
+        }
+        let _ = writeln!(out, "{} keeps running in the background, see `Server-Launcher status`", name);
+        true
+    }
+
+    // Settings take effect right away and are stored for the next start
+    fn save_settings(&mut self) {
+        if self.settings.persist_logs != self.log_writer.is_some() {
+            self.log_writer = self
+                .settings
+                .persist_logs
+                .then(|| LogWriter::new(logfile::LOG_DIR));
+        }
+        if self.settings.key_preset != self.keymap.preset {
+            self.load_keymap();
+        }
+        if let Err(e) = self.settings.save(&self.db) {
+            self.push_log(ServerMessage::new(
+                "",
+                format!("Failed to save settings: {}", e),
+                MessageType::Err,
+            ));
+        }
+    }
+}
+
+// Example entries so a fresh database doesn't start out empty
+fn example_servers() -> Vec<Server> {
+    vec![
+        Server::default().name("Server 1").path("/C").executable("server.jar").test_server(true).display_color(Color::Rgb(30, 230, 180)),
+        Server::default().name("Timer 1").path("/Users/student/Projects/Server-Launcher/Server-Launcher/").executable("python3").args(vec!["-u".to_string(), "timer.py".to_string(), "8".to_string()]).log_rules(vec![LogRule::new(r"^Timer will go", LogLevel::Info).unwrap(), LogRule::new(r"^ \d+ ", LogLevel::Debug).unwrap()]),
+        Server::default().name("Ascii Image").path("/Users/student/Projects/Server-Launcher/Server-Launcher/").executable("python3").args(vec!["-u".to_string(), "ascii_image.py".to_string()]).autostart(true),
+        Server::default().name("Minecraft Server").path("/Users/student/Downloads/MinecraftServer").executable("./start_Server.sh").log_preset(LogPreset::Minecraft).triggers(vec![
+            Trigger::new(r"java\.lang\.OutOfMemoryError", TriggerAction::Restart, Duration::from_secs(60)).unwrap(),
+            Trigger::new(r"Done \(", TriggerAction::MarkReady, Duration::ZERO).unwrap(),
+            Trigger::new(r"joined the game", TriggerAction::Notify(String::new()), Duration::ZERO).unwrap(),
+        ]),
+    ]
 }
 
-pub fn init_tui() -> Result<(), Box<dyn Error>> {
+fn load_or_seed_servers(conn: &Connection) -> Vec<Server> {
+    let servers = db::load_servers(conn).unwrap_or_default();
+    if !servers.is_empty() {
+        return servers;
+    }
+    for server in example_servers() {
+        let _ = db::insert_server(conn, &server);
+    }
+    db::load_servers(conn).unwrap_or_default()
+}
+
+pub fn init_tui(db: Connection) -> Result<(), Box<dyn Error>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
 
@@ -111,284 +841,366 @@ pub fn init_tui() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new();
-    let res = run_app(&mut terminal, &mut app);
+    let mut app = App::new(db);
+    app.load_keymap();
+    if !app.settings.api_address.is_empty() {
+        let started = db::connect_db(db::DB_PATH)
+            .map_err(|e| io::Error::other(e.to_string()))
+            .and_then(|conn| api::spawn(&app.settings.api_address, app.control_sender.clone(), conn));
+        let message = match started {
+            Ok(()) => ServerMessage::new(
+                "",
+                format!("HTTP API listening on http://{}", app.settings.api_address),
+                MessageType::Main,
+            ),
+            Err(e) => ServerMessage::new(
+                "",
+                format!("Could not start the HTTP API on {}: {}", app.settings.api_address, e),
+                MessageType::Err,
+            ),
+        };
+        app.push_log(message);
+    }
+    let socket_path = socket::socket_path();
+    let (attach_sender, attaches) = channel();
+    let socket_started = socket::spawn(&socket_path, app.control_sender.clone(), attach_sender);
+    let message = match &socket_started {
+        Ok(()) => ServerMessage::new("", format!("Control socket at {}", socket_path.display()), MessageType::Main),
+        Err(e) => ServerMessage::new(
+            "",
+            format!("Could not open the control socket at {}: {}", socket_path.display(), e),
+            MessageType::Err,
+        ),
+    };
+    app.push_log(message);
+    let ssh_address = app.settings.ssh_address.clone();
+    let authorized_keys = app.settings.ssh_authorized_keys.clone();
+    let app = Arc::new(Mutex::new(app));
+    spawn_core(app.clone());
+    {
+        let app = app.clone();
+        thread::spawn(move || client::serve_attached(app, attaches));
+    }
+    if !ssh_address.is_empty() {
+        let message = match ssh::spawn(&ssh_address, Path::new(&authorized_keys), app.clone()) {
+            Ok(()) => ServerMessage::new(
+                "",
+                format!("SSH listening on {}, keys from {}", ssh_address, authorized_keys),
+                MessageType::Main,
+            ),
+            Err(e) => ServerMessage::new(
+                "",
+                format!("Could not start SSH on {}: {}", ssh_address, e),
+                MessageType::Err,
+            ),
+        };
+        lock(&app).push_log(message);
+    }
+    shutdown::install_panic_hook(app.clone());
+    {
+        let (app, socket_path, socket_started) = (app.clone(), socket_path.clone(), socket_started.is_ok());
+        shutdown::on_signal(move |signal| {
+            // Held throughout so the UI thread can't draw over the restored terminal
+            let mut app = lock(&app);
+            shutdown::restore_terminal();
+            if socket_started {
+                let _ = std::fs::remove_file(&socket_path);
+            }
+            let contents = format!("Received {}, quitting", shutdown::signal_name(signal));
+            app.push_log(ServerMessage::new("", contents, MessageType::Main));
+            let policy = app.settings.quit_policy;
+            app.shut_down(policy);
+            std::process::exit(128 + signal);
+        });
+    }
+    // A panic on this thread has already been reported by the hook, the servers are handled below
+    shutdown::mark_critical();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| run_app(&mut terminal, &app)))
+        .unwrap_or_else(|_| Err(io::Error::other("the UI panicked")));
+    if socket_started.is_ok() {
+        let _ = std::fs::remove_file(&socket_path);
+    }
+
+    shutdown::restore_terminal();
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture,
-    )?;
-    terminal.show_cursor()?;
+    shutdown::finish_report(&lock(&app));
+    // Nobody is left to ask once the terminal is gone
+    let policy = match &res {
+        Ok(policy) => *policy,
+        Err(_) => lock(&app).settings.quit_policy,
+    };
+    lock(&app).shut_down(policy);
 
     if let Err(err) = res {
-        println!("Error running TUI: {:?}", err);
+        let _ = writeln!(io::stdout(), "Error running TUI: {:?}", err);
         return Err(Box::new(err));
     }
 
     Ok(())
 }
 
-fn run_app<backend: Backend>(terminal: &mut Terminal<backend>, app: &mut App) -> io::Result<()> {
-    loop {
-        let mut log_panel_frame_rect = Rect::default(); // To store the log panel's frame Rect
+fn lock(app: &Mutex<App>) -> MutexGuard<'_, App> {
+    app.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// The shared state moves on by itself, whichever terminals are drawing it
+fn spawn_core(app: Arc<Mutex<App>>) {
+    thread::spawn(move || {
+        shutdown::mark_critical();
+        loop {
+            let mut app = lock(&app);
+            shutdown::log_contained(&mut app);
+            app.pump();
+            drop(app);
+            thread::sleep(TICK);
+        }
+    });
+}
 
-        terminal.draw(|f| {
-            log_panel_frame_rect = ui::<backend>(f, app); // ui now returns the log panel's frame Rect
-        })?;
+// Returns what to do with the servers that are still running
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &Arc<Mutex<App>>) -> io::Result<QuitPolicy> {
+    let mut session = Session::local();
+
+    loop {
+        if shutdown::panicked() {
+            return Err(io::Error::other("a thread panicked"));
+        }
+        {
+            let app = lock(app);
+            session.view.catch_up(&app);
+            terminal.draw(|f| session.draw(f, &app))?;
+        }
 
         // Event handling with a timeout. 1000 / 50 => 20fps
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => return Ok(()),
-                        KeyCode::Char('j') | KeyCode::Char('J') | KeyCode::Down => {
-                            app.selected_server =
-                                wrap_index(app.selected_server, app.available_servers.len() - 1, 1);
-                        }
-                        KeyCode::Char('k') | KeyCode::Char('K') | KeyCode::Up => {
-                            app.selected_server = wrap_index(
-                                app.selected_server,
-                                app.available_servers.len() - 1,
-                                -1,
-                            );
-                        }
-                        KeyCode::Enter => {
-                            if app
-                                .allocated_servers
-                                .contains_key(&app.available_servers[app.selected_server].name)
-                            {
-                                continue;
-                            }
-
-                            match servers::launch(
-                                &app.available_servers[app.selected_server],
-                                app.log_sender.clone(),
-                                app.server_event_sender.clone(),
-                            ) {
-                                Ok(handle) => {
-                                    app.allocated_servers.insert(
-                                        app.available_servers[app.selected_server].name.clone(),
-                                        handle,
-                                    );
-
-                                    app.logs.push_back(ServerMessage {
-                                        name: "".to_string(),
-                                        contents: format!(
-                                            "Server {} launched successfully.",
-                                            app.available_servers[app.selected_server].name
-                                        ),
-                                        message_type: MessageType::Main }
-                                    );
-                                }
-                                Err(e) => {
-                                    app.logs.push_back(ServerMessage {
-                                        name: "".to_string(),
-                                        contents: format!(
-                                            "Failed to launch server {}: {}",
-                                            app.available_servers[app.selected_server].name, e
-                                        ),
-                                        message_type: MessageType::Err }
-                                    );
-                                }
-                            }
-                        }
-                        KeyCode::Char('x') | KeyCode::Char('X') => {
-                            if !app.available_servers.is_empty() {
-                                let server_name_to_kill =
-                                    app.available_servers[app.selected_server].name.clone();
-                                if let Some(handle) =
-                                    app.allocated_servers.get_mut(&server_name_to_kill)
-                                {
-                                    match handle.kill_process() {
-                                        Ok(_) => {
-                                            app.logs.push_back(ServerMessage {
-                                                name: "".to_string(),
-                                                contents: format!("Attempting to kill server: {}. It will be removed from the list if successful.", server_name_to_kill),
-                                                message_type: MessageType::Main }
-                                            );
-                                        }
-                                        Err(e) => {
-
-                                            app.logs.push_back(ServerMessage {
-                                                name: "".to_string(),
-                                                contents: format!(
-                                                    "Failed to kill server {}: {}",
-                                                    server_name_to_kill, e
-                                                ),
-                                                message_type: MessageType::Err }
-                                            );
-                                        }
-                                    }
-                                } else {
-                                    app.logs.push_back(
-                                        ServerMessage{
-                                            name: "".to_string(),
-                                            contents: format!(
-                                            "Server {} is not currently running or allocated.",
-                                            server_name_to_kill),
-                                            message_type: MessageType::Err
-                                        });
-                                }
-                            }
-                        }
-                        KeyCode::Char('c') | KeyCode::Char('C') => {
-                            app.logs.clear();
-                        }
-                        KeyCode::Char(' ') => {
-                            app.logs
-                                .push_back(
-                                    ServerMessage{
-                                    name: "".to_string(),
-                                    contents: format!("Pressed Space should open popup"),
-                                    message_type: MessageType::Err
-                                });
-                        }
-                        _ => {}
+        if event::poll(TICK)? && session.handle_event(&mut lock(app), event::read()?) {
+            return Ok(session.view.quit.unwrap_or(QuitPolicy::Stop));
+        }
+    }
+}
+
+// The last line of the controls panel, the same on every page
+const GLOBAL_CONTROLS: &[Hint] = &[
+    Hint::Actions(&[Action::NextPage, Action::PrevPage], "Next/Previous Page"),
+    Hint::Fixed("F1-F6", "Go to Page"),
+    Hint::Actions(&[Action::Quit], "Quit"),
+];
+
+// One terminal's dashboard: its pages and what it looks at, drawn from the shared App
+struct Session {
+    view: View,
+    pages: Vec<Box<dyn Page>>,
+    current_page: usize,
+    local: bool, // the launcher's own terminal, quitting it quits the launcher
+    tabs: Vec<Rect>,  // where each page's tab was last drawn
+    buttons: Buttons, // the hints that can be clicked in the last frame
+}
+
+impl Session {
+    fn local() -> Session {
+        Session {
+            local: true,
+            ..Session::new(Identity::local())
+        }
+    }
+
+    fn new(identity: Identity) -> Session {
+        Session {
+            view: View::new(identity),
+            pages: vec![
+                Box::new(servers_page::ServersPage::new()),
+                Box::new(details_page::DetailsPage::new()),
+                Box::new(history_page::HistoryPage::new()),
+                Box::new(audit_page::AuditPage::new()),
+                Box::new(host_page::HostPage::new()),
+                Box::new(settings_page::SettingsPage::new()),
+            ],
+            current_page: 0,
+            local: false,
+            tabs: Vec::new(),
+            buttons: Vec::new(),
+        }
+    }
+
+    // Returns true once the terminal asked to quit
+    fn handle_event(&mut self, app: &mut App, event: Event) -> bool {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => self.handle_key(app, key),
+            Event::Mouse(mouse) => self.handle_mouse(app, mouse),
+            _ => false,
+        }
+    }
+
+    // Returns true once the terminal asked to quit
+    fn handle_key(&mut self, app: &mut App, key: KeyEvent) -> bool {
+        self.view.catch_up(app);
+        if self.view.confirm.is_some() {
+            self.handle_confirm_input(app, key);
+        } else if self.view.form.is_some() {
+            self.handle_form_input(app, key);
+        } else {
+            let page = &mut self.pages[self.current_page];
+            let captured = page.captures_input();
+            if !page.handle_key(app, &mut self.view, key) && !captured {
+                let count = self.pages.len();
+                match (app.keymap.action(&key), key.code) {
+                    (Some(Action::Quit), _) => return self.quit(app),
+                    (Some(Action::NextPage), _) => self.current_page = (self.current_page + 1) % count,
+                    (Some(Action::PrevPage), _) => self.current_page = (self.current_page + count - 1) % count,
+                    (_, KeyCode::F(n)) if (1..=count).contains(&(n as usize)) => {
+                        self.current_page = n as usize - 1;
                     }
+                    _ => {}
                 }
             }
         }
+        self.view.quit.is_some()
+    }
 
-        while let Ok(log_message) = app.log_receiver.try_recv() {
-            app.logs
-                .push_back(log_message)
-            //let _ = Line::from(Span::from(log_message.contents))::Style;
+    // Hints are buttons that press their key. While a dialog or the form is open only theirs are.
+    fn handle_mouse(&mut self, app: &mut App, mouse: MouseEvent) -> bool {
+        self.view.catch_up(app);
+        if let Some(key) = mouse::clicked(&self.buttons, &mouse) {
+            return self.handle_key(app, key);
         }
+        if self.view.confirm.is_some() || self.view.form.is_some() {
+            return false;
+        }
+        let tab = self.tabs.iter().position(|&tab| mouse::contains(tab, &mouse));
+        match tab {
+            Some(page) if mouse::is_left_click(&mouse) => self.current_page = page,
+            _ => self.pages[self.current_page].handle_mouse(app, &mut self.view, mouse),
+        }
+        self.view.quit.is_some()
+    }
 
-        while let Ok(event) = app.server_event_receiver.try_recv() {
-            match event {
-                ServerLifecycleEvent::Exited { name } => {
-                    app.logs.push_back(
-                        ServerMessage{
-                        name: "".to_string(),
-                        contents: format!("Server {} has Exited" , name),
-                        message_type: MessageType::Err
-                    });
+    // Other terminals just close, the launcher's own one decides what happens to the servers
+    fn quit(&mut self, app: &App) -> bool {
+        !self.local || self.view.confirm_quit(app)
+    }
 
-                    if let Some(handle) = app.allocated_servers.get_mut(&name) {
-                        handle.running = false;
+    fn handle_form_input(&mut self, app: &mut App, key: KeyEvent) {
+        let view = &mut self.view;
+        let Some(form) = view.form.as_mut() else {
+            return;
+        };
+        match form.handle_key(key) {
+            FormResult::Continue => {}
+            FormResult::Cancel => view.form = None,
+            FormResult::Submit => {
+                // Errors are already shown next to the fields
+                if !form.validate(&app.available_servers).is_empty() {
+                    return;
+                }
+                let server = form.to_server().id(form.editing.unwrap_or(-1));
+                match app.save_server(&view.identity, server) {
+                    Ok(id) => {
+                        view.form = None;
+                        if let Some(index) = app.available_servers.iter().position(|s| s.id == id) {
+                            view.selected_server = index;
+                        }
+                    }
+                    Err(e) => {
+                        if let Some(form) = view.form.as_mut() {
+                            form.error = Some(e.message);
+                        }
                     }
                 }
             }
         }
+    }
 
-        // Trim logs to fit the panel, ensuring we keep the latest entries
-        let displayable_log_lines = log_panel_frame_rect.height.saturating_sub(2).max(0) as usize;
-        if app.logs.len() > displayable_log_lines {
-            app.logs.drain(0..(app.logs.len() - displayable_log_lines));
+    fn handle_confirm_input(&mut self, app: &mut App, key: KeyEvent) {
+        if let Some(PendingAction::Quit(QuitPolicy::Ask)) = self.view.confirm.as_ref().map(|dialog| &dialog.action) {
+            let policy = match key.code {
+                KeyCode::Char('s') | KeyCode::Char('S') => QuitPolicy::Stop,
+                KeyCode::Char('l') | KeyCode::Char('L') => QuitPolicy::Leave,
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                    self.view.confirm = None;
+                    return;
+                }
+                _ => return,
+            };
+            self.view.confirm = None;
+            self.view.run_pending(app, PendingAction::Quit(policy));
+            return;
+        }
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                if let Some(dialog) = self.view.confirm.take() {
+                    self.view.run_pending(app, dialog.action);
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.view.confirm = None,
+            _ => {}
         }
-
-        app.on_tick();
     }
-}
 
-fn ui<backend: Backend>(frame: &mut Frame, app: &App) -> Rect {
-    // Return the Rect of the log panel frame
-    let main_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(
-            [
-                Constraint::Min(0),    // Main content area takes the rest of the space
-                Constraint::Length(4), // Controls panel: 2 lines for text, 2 for borders
-            ]
-            .as_ref(),
-        )
-        .split(frame.size());
-
-    let content_area_chunk = main_chunks[0];
-    let controls_chunk = main_chunks[1];
-
-    // Horizontal layout for the content area (servers and logs)
-    let content_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
-        .split(content_area_chunk); // Split the top part
-
-    let left_split_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
-        .split(content_chunks[0]);
-
-    // Left Panel: Server List
-    let server_items: Vec<ListItem> = app
-        .available_servers
-        .iter()
-        .enumerate()
-        .map(|(i, server)| {
-            let line = Line::from(Span::styled(
-                if i == app.selected_server {
-                    format!("> {}", server.name)
-                } else {
-                    format!("{}", server.name)
-                },
-                server_list_style_builder(i, server.clone(), app),
-            ));
-            ListItem::new(line)
-        })
-        .collect();
-
-    let server_list = List::new(server_items)
-        .block(
-            Block::default()
-                .title("Servers")
-                .borders(Borders::ALL)
-                .border_style(Style::new().fg(Color::Indexed(33))),
-        )
-        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
-        .highlight_symbol("> ");
-
-    frame.render_widget(server_list, left_split_chunks[0]);
-
-    // make little image output or animtaion in the small bottom left box
-    let blb = Block::default()
-        .bg(Color::Indexed(200))
-        .border_style(Style::new().fg(Color::Indexed(50)));
-    frame.render_widget(blb, left_split_chunks[1]);
-
-    // Right Panel: Log Output
-    let log_panel_frame_rect = content_chunks[1]; // The Rect for the entire log panel widget (frame included)
-
-    let log_text: Vec<Line> = app
-        .logs
-        .iter()
-        .map(|log| -> Line {output_log_style_builder(log)})
-        .collect();
-
-    // Calculate the inner height of the log panel for scrolling content (area inside borders)
-    let inner_log_area_height = log_panel_frame_rect.height.saturating_sub(2).max(0);
-
-    let scroll_offset_y = (app.logs.len() as u16).saturating_sub(inner_log_area_height);
-
-    let right_panel_content = Paragraph::new(log_text)
-        .block(
-            Block::default()
-                .title("Log Stream")
-                .borders(Borders::ALL)
-                .border_style(Style::new().fg(Color::Indexed(208))),
-        )
-        .wrap(Wrap { trim: true })
-        .scroll((scroll_offset_y, 0)); // Add scroll to show the bottom of the logs
-    frame.render_widget(right_panel_content, log_panel_frame_rect);
-
-    // Bottom Panel: Controls
-    let controls_line1 = Line::from(vec![Span::raw(
-        "(J/Down, K/Up) Navigate Servers | (Enter) Launch/Select",
-    )]);
-    let controls_line2 = Line::from(vec![Span::raw(
-        "(X) Kill Server | (C) Clear Logs | (Q/Esc) Quit",
-    )]);
-
-    let controls_text = vec![controls_line1, controls_line2];
-
-    let controls_panel = Paragraph::new(controls_text)
-        .block(Block::default().title("Controls").borders(Borders::ALL))
-        .alignment(Alignment::Center);
-    frame.render_widget(controls_panel, controls_chunk);
-
-    content_chunks[1] // Return the Rect of the log panel's frame
+    fn draw(&mut self, frame: &mut Frame, app: &App) {
+        // Written out from the keymap, so the panel shows whatever the keys are now
+        let mut controls: Vec<String> = self.pages[self.current_page]
+            .controls()
+            .iter()
+            .map(|line| app.keymap.describe(line))
+            .filter(|line| !line.is_empty())
+            .collect();
+        controls.push(app.keymap.describe(GLOBAL_CONTROLS));
+        let main_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(1), // Tab bar
+                    Constraint::Min(0),    // Page content takes the rest of the space
+                    Constraint::Length(controls.len() as u16 + 2), // Page controls, the global line and borders
+                ]
+                .as_ref(),
+            )
+            .split(frame.size());
+
+        let titles: Vec<String> = self
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| format!("F{} {}", i + 1, page.title()))
+            .collect();
+        self.tabs = mouse::tab_areas(&titles, main_chunks[0]);
+        let tabs = Tabs::new(titles)
+            .select(self.current_page)
+            .highlight_style(
+                Style::new()
+                    .fg(Color::Indexed(33))
+                    .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            );
+        frame.render_widget(tabs, main_chunks[0]);
+
+        self.pages[self.current_page].render(frame, main_chunks[1], app, &self.view);
+
+        // Bottom Panel: Controls
+        let controls_block = Block::default().title("Controls").borders(Borders::ALL);
+        let inner = controls_block.inner(main_chunks[2]);
+        // A page typing into a prompt would take the clicked key as text
+        self.buttons = if self.pages[self.current_page].captures_input() {
+            Vec::new()
+        } else {
+            controls
+                .iter()
+                .zip(inner.y..inner.bottom())
+                .flat_map(|(line, y)| mouse::centered_hint_buttons(line, inner, y))
+                .collect()
+        };
+
+        let controls_text: Vec<Line> = controls.into_iter().map(Line::raw).collect();
+        let controls_panel = Paragraph::new(controls_text)
+            .block(controls_block)
+            .alignment(Alignment::Center);
+        frame.render_widget(controls_panel, main_chunks[2]);
+
+        if let Some(form) = &self.view.form {
+            self.buttons = form.render(frame, &form.validate(&app.available_servers));
+        }
+        if let Some(dialog) = &self.view.confirm {
+            self.buttons = dialog.render(frame);
+        }
+    }
 }
 
 fn wrap_index(index: usize, max_index: usize, delta: isize) -> usize {
@@ -400,47 +1212,58 @@ fn wrap_index(index: usize, max_index: usize, delta: isize) -> usize {
     result_signed as usize
 }
 
-fn server_list_style_builder(index: usize, server: Server, app: &App) -> Style {
-    let mut style = Style::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if app.allocated_servers.contains_key(&server.name) {
-        style = style.fg(Color::Green);
-    } else {
-        style = style.fg(Color::Red);
-    }
+    #[test]
+    fn search_ignores_case_unless_asked() {
+        let mut search = LogSearch { query: "error".to_string(), ..LogSearch::default() };
+        search.compile();
+        assert!(search.pattern.as_ref().unwrap().is_match("ERROR: disk full"));
 
-    if index == app.selected_server {
-        style = style.patch(
-            Style::default()
-                .add_modifier(Modifier::UNDERLINED)
-                .add_modifier(Modifier::BOLD),
-        );
+        search.case_sensitive = true;
+        search.compile();
+        assert!(!search.pattern.as_ref().unwrap().is_match("ERROR: disk full"));
     }
 
-    style
-}
+    #[test]
+    fn invalid_regex_reports_an_error() {
+        let mut search = LogSearch { query: "(unclosed".to_string(), ..LogSearch::default() };
+        search.compile();
+        assert!(search.pattern.is_none());
+        assert_eq!(search.error.as_deref(), Some("invalid regex"));
 
-fn output_log_style_builder(message: &ServerMessage) -> Line {
-    let mut style: Style = Style::new();
-    match message.message_type {
-        MessageType::Err => {
-            style = style.fg(Color::Red).add_modifier(Modifier::BOLD);
-            return Line::from(vec![
-                Span::from(message.name.as_str()).style(style),
-                Span::from(message.contents.as_str()).style(style),
-            ]);
-        }
-        MessageType::None => {
-            style = Style::blue(style); // make a server attrbute for this, prob in rgb
-            return Line::from(vec![
-                Span::from(format!("[{}] " , message.name.as_str())).style(style),
-                Span::from(message.contents.as_str()).style(style),
-            ]);
-        }
-        _ => {
-            style = style;
-        }
+        search.clear();
+        assert!(search.pattern.is_none());
+        assert!(search.error.is_none());
     }
 
-    Line::from(Span::from(message.contents.as_str()))
+    #[test]
+    fn viewers_cannot_export_logs() {
+        let mut app = App::new(db::connect_db(":memory:").unwrap());
+        let viewer = Identity { role: crate::auth::Role::Viewer, ..Identity::local() };
+        let result = app.export_logs(&viewer, &[]);
+        assert_eq!(result.unwrap_err().kind, ErrorKind::Forbidden);
+        let entries = db::load_audit(&app.db, None, None, 10).unwrap();
+        assert_eq!(entries[0].action, "export logs");
+        assert_eq!(entries[0].outcome, "denied");
+    }
+
+    #[test]
+    fn failed_restarts_are_not_counted_or_announced() {
+        let mut app = App::new(db::connect_db(":memory:").unwrap());
+        let (events, heard) = channel();
+        app.subscribe(StreamFilter { skip_logs: true, ..StreamFilter::default() }, events);
+        let who = Identity::local();
+        assert!(app.restart_server(&who, "No Such Server").is_err());
+        assert!(app.restarts.is_empty());
+        assert!(heard.try_recv().is_err());
+
+        let test_server = app.available_servers.iter().find(|s| s.test_server).unwrap().name.clone();
+        app.restart_server(&who, &test_server).unwrap();
+        assert_eq!(app.restarts[&test_server], 1);
+        let labels: Vec<String> = heard.try_iter().map(|event| event.to_json()["event"].to_string()).collect();
+        assert_eq!(labels, ["\"restarting\"", "\"started\""]);
+    }
 }
@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::KeyEvent;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Cell, Row, Table, TableState},
+};
+
+use super::{App, Page, View};
+use crate::db::{self, AuditEntry};
+use crate::keymap::{Action, Hint};
+
+// How many entries are read from the db
+const AUDIT_LIMIT: usize = 500;
+const REFRESH_EVERY: Duration = Duration::from_secs(1);
+
+// Who launched, stopped or changed what, newest first
+pub struct AuditPage {
+    entries: Vec<AuditEntry>,
+    loaded_at: Option<Instant>,
+    state: TableState,
+}
+
+impl AuditPage {
+    pub fn new() -> AuditPage {
+        AuditPage {
+            entries: Vec::new(),
+            loaded_at: None,
+            state: TableState::default(),
+        }
+    }
+
+    fn refresh(&mut self, app: &App) {
+        if self.loaded_at.is_some_and(|loaded| loaded.elapsed() < REFRESH_EVERY) {
+            return;
+        }
+        self.entries = db::load_audit(&app.db, None, None, AUDIT_LIMIT).unwrap_or_default();
+        self.loaded_at = Some(Instant::now());
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        let selected = self.state.selected().unwrap_or(0);
+        self.state
+            .select(Some(selected.saturating_add_signed(delta).min(last)));
+    }
+}
+
+fn outcome_cell(entry: &AuditEntry) -> Cell<'static> {
+    let color = match entry.outcome.as_str() {
+        "ok" => Color::Green,
+        "denied" => Color::Yellow,
+        _ => Color::Red,
+    };
+    Cell::from(entry.outcome.clone()).style(Style::new().fg(color))
+}
+
+impl Page for AuditPage {
+    fn title(&self) -> &'static str {
+        "Audit"
+    }
+
+    fn handle_key(&mut self, app: &mut App, _view: &mut View, key: KeyEvent) -> bool {
+        match app.keymap.action(&key) {
+            Some(Action::Down) => self.scroll(1),
+            Some(Action::Up) => self.scroll(-1),
+            Some(Action::PageDown) => self.scroll(20),
+            Some(Action::PageUp) => self.scroll(-20),
+            Some(Action::Top) => self.state.select(Some(0)),
+            _ => return false,
+        }
+        true
+    }
+
+    fn controls(&self) -> &'static [&'static [Hint]] {
+        &[&[
+            Hint::Actions(&[Action::Down, Action::Up, Action::PageDown, Action::PageUp], "Scroll"),
+            Hint::Actions(&[Action::Top], "Newest"),
+        ]]
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, app: &App, _view: &View) {
+        self.refresh(app);
+
+        let rows: Vec<Row> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                // The console command, or why it did not go through
+                let detail = match (entry.detail.is_empty(), entry.message.is_empty()) {
+                    (_, false) => entry.message.clone(),
+                    (false, true) => entry.detail.clone(),
+                    (true, true) => String::new(),
+                };
+                Row::new(vec![
+                    Cell::from(entry.at.format("%Y-%m-%d %H:%M:%S").to_string()),
+                    Cell::from(entry.user.clone()),
+                    Cell::from(entry.source.clone()),
+                    Cell::from(entry.action.clone()),
+                    Cell::from(entry.target.clone()),
+                    outcome_cell(entry),
+                    Cell::from(detail),
+                ])
+            })
+            .collect();
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(19),
+                Constraint::Length(12),
+                Constraint::Length(18),
+                Constraint::Length(14),
+                Constraint::Length(16),
+                Constraint::Length(7),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(vec!["Time", "User", "Source", "Action", "Target", "Outcome", "Detail"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(
+            Block::default()
+                .title(format!("Audit Log ({})", self.entries.len()))
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Color::Indexed(208))),
+        )
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(table, area, &mut self.state);
+    }
+}
@@ -0,0 +1,249 @@
+#[cfg(unix)]
+use std::io::BufRead;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::net::Shutdown;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{prelude::*, TerminalOptions, Viewport};
+#[cfg(unix)]
+use serde_json::Value;
+
+use super::{keys, lock, App, Session};
+use crate::auth::Identity;
+use crate::servers::{MessageType, ServerMessage};
+use crate::socket::Attach;
+
+// Dashboards on terminals the launcher does not own: SSH sessions and `attach` over the
+// control socket. Each runs on its own thread with its own Session, drawing from the shared App.
+
+// How often a dashboard redraws while nobody types, same as the local terminal
+const FRAME: Duration = Duration::from_millis(50);
+
+// A far end that takes longer than this to accept a frame has stalled, its dashboard is closed
+pub(super) const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Keys and window size changes from the far end
+pub enum Input {
+    Data(Vec<u8>),
+    Resize(u16, u16),
+}
+
+// Runs one dashboard until it quits or the far end goes away. `from` says where it is for the log.
+pub fn run<W: Write + Clone>(
+    app: &Arc<Mutex<App>>,
+    identity: Identity,
+    from: &str,
+    writer: W,
+    size: (u16, u16),
+    input: &Receiver<Input>,
+) {
+    let log = |contents: String| lock(app).push_log(ServerMessage::new("", contents, MessageType::Main));
+    let user = identity.user.clone();
+    log(format!("{} opened a dashboard ({})", user, from));
+
+    let mut remote = Remote {
+        writer: writer.clone(),
+        frame: Arc::new(Mutex::new(Vec::new())),
+        size,
+    };
+    let _ = remote.draw_until_quit(app, Session::new(identity), input);
+    let mut writer = writer;
+    let _ = execute!(writer, LeaveAlternateScreen, DisableMouseCapture, Show);
+    log(format!("{} closed a dashboard ({})", user, from));
+}
+
+struct Remote<W> {
+    writer: W,
+    frame: Arc<Mutex<Vec<u8>>>, // what the terminal drew, sent once the App lock is let go
+    size: (u16, u16),
+}
+
+// Draws into memory. Sending is left to Remote::send, a far end that stops reading must not
+// hold up everyone else waiting for the App lock.
+#[derive(Clone)]
+struct FrameWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for FrameWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write + Clone> Remote<W> {
+    // The far end's window size is not something crossterm can ask for, so the viewport is
+    // fixed and the terminal is made anew when the window changes
+    fn terminal(&self) -> io::Result<Terminal<CrosstermBackend<FrameWriter>>> {
+        let (width, height) = self.size;
+        let mut terminal = Terminal::with_options(
+            CrosstermBackend::new(FrameWriter(self.frame.clone())),
+            TerminalOptions {
+                viewport: Viewport::Fixed(Rect::new(0, 0, width, height)),
+            },
+        )?;
+        terminal.clear()?;
+        Ok(terminal)
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        let frame = std::mem::take(&mut *self.frame.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        if frame.is_empty() {
+            return Ok(());
+        }
+        self.writer.write_all(&frame)?;
+        self.writer.flush()
+    }
+
+    fn draw_until_quit(&mut self, app: &Arc<Mutex<App>>, mut session: Session, input: &Receiver<Input>) -> io::Result<()> {
+        execute!(self.writer, EnterAlternateScreen, EnableMouseCapture, Hide)?;
+        let mut terminal = self.terminal()?;
+        loop {
+            {
+                let app = lock(app);
+                session.view.catch_up(&app);
+                terminal.draw(|f| session.draw(f, &app))?;
+            }
+            self.send()?;
+
+            match input.recv_timeout(FRAME) {
+                Ok(Input::Data(bytes)) => {
+                    let mut app = lock(app);
+                    for event in keys::parse(&bytes) {
+                        if session.handle_event(&mut app, event) {
+                            return Ok(());
+                        }
+                    }
+                }
+                Ok(Input::Resize(width, height)) => {
+                    self.size = (width, height);
+                    terminal = self.terminal()?;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+// Terminals attached over the control socket, one thread each
+pub fn serve_attached(app: Arc<Mutex<App>>, attaches: Receiver<Attach>) {
+    for attach in attaches {
+        let app = app.clone();
+        thread::spawn(move || attached(&app, attach));
+    }
+}
+
+#[cfg(unix)]
+#[derive(Clone)]
+struct SocketWriter(Arc<UnixStream>);
+
+#[cfg(unix)]
+impl Write for SocketWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        (&*self.0).write(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self.0).flush()
+    }
+}
+
+// The attached side sends {"keys": "..."} and {"resize": [cols, rows]} lines and gets the raw
+// terminal output back
+#[cfg(unix)]
+fn attached(app: &Arc<Mutex<App>>, attach: Attach) {
+    let (input, receiver) = mpsc::channel();
+    let reader = attach.reader;
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            let sent = if let Some(keys) = message["keys"].as_str() {
+                input.send(Input::Data(keys.as_bytes().to_vec()))
+            } else if let (Some(cols), Some(rows)) = (message["resize"][0].as_u64(), message["resize"][1].as_u64()) {
+                input.send(Input::Resize(cols as u16, rows as u16))
+            } else {
+                Ok(())
+            };
+            if sent.is_err() {
+                return;
+            }
+        }
+    });
+
+    let _ = attach.writer.set_write_timeout(Some(WRITE_TIMEOUT));
+    let writer = SocketWriter(Arc::new(attach.writer));
+    run(app, Identity::socket(), "control socket", writer.clone(), attach.size, &receiver);
+    // Ends the reader above and tells the attached side to let go of its terminal
+    let _ = writer.0.shutdown(Shutdown::Both);
+}
+
+// Without the control socket nothing attaches
+#[cfg(not(unix))]
+fn attached(_app: &Arc<Mutex<App>>, attach: Attach) {
+    match attach {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    // Fails every write made while the App is locked
+    #[derive(Clone)]
+    struct Checked {
+        app: Arc<Mutex<App>>,
+        written: Arc<Mutex<usize>>,
+    }
+
+    impl Write for Checked {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            if self.app.try_lock().is_err() {
+                return Err(io::Error::other("written while the App was locked"));
+            }
+            *self.written.lock().unwrap() += bytes.len();
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_are_sent_after_the_lock_is_let_go() {
+        let app = Arc::new(Mutex::new(App::new(db::connect_db(":memory:").unwrap())));
+        let writer = Checked { app: app.clone(), written: Arc::new(Mutex::new(0)) };
+        let mut remote = Remote {
+            writer: writer.clone(),
+            frame: Arc::new(Mutex::new(Vec::new())),
+            size: (80, 24),
+        };
+        // Nothing to read, so one frame is drawn and the dashboard ends
+        let (_, input) = mpsc::channel();
+        remote.draw_until_quit(&app, Session::new(Identity::local()), &input).unwrap();
+        assert!(*writer.written.lock().unwrap() > 80 * 24);
+    }
+}
@@ -0,0 +1,55 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+
+use super::form::centered_rect;
+use super::mouse::{self, Buttons};
+use crate::settings::QuitPolicy;
+
+// What to do once the user says yes
+pub enum PendingAction {
+    DeleteServer { name: String },
+    // Quit::Ask asks which of the other two it should be
+    Quit(QuitPolicy),
+}
+
+// Yes/no modal, takes all keys while open
+pub struct ConfirmDialog {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub action: PendingAction,
+}
+
+impl ConfirmDialog {
+    // Returns where its choices can be clicked
+    pub fn render(&self, frame: &mut Frame) -> Buttons {
+        let height = self.lines.len() as u16 + 4;
+        let hint = match self.action {
+            PendingAction::Quit(QuitPolicy::Ask) => "(S) Stop Them | (L) Leave Them Running | (Esc) Cancel",
+            _ => "(Y/Enter) Yes | (N/Esc) No",
+        };
+        let area = centered_rect(frame.size(), 50, height);
+
+        let mut text: Vec<Line> = self.lines.iter().map(|line| Line::raw(line.as_str())).collect();
+        text.push(Line::raw(""));
+        text.push(Line::styled(hint, Style::new().fg(Color::DarkGray)));
+
+        let block = Block::default()
+            .title(self.title.as_str())
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(Color::Red));
+        let inner = block.inner(area);
+        let dialog = Paragraph::new(text).wrap(Wrap { trim: true }).block(block);
+        frame.render_widget(Clear, area);
+        frame.render_widget(dialog, area);
+
+        // Below the lines as wrapped, near enough for the short ones dialogs have
+        let width = inner.width.max(1) as usize;
+        let rows: usize = self.lines.iter().map(|line| line.chars().count().max(1).div_ceil(width)).sum();
+        if hint.len() > width {
+            return Vec::new();
+        }
+        mouse::hint_buttons(hint, inner.x, inner.y + rows as u16 + 1)
+    }
+}
@@ -0,0 +1,279 @@
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use crossterm::event::KeyEvent;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph, Wrap},
+};
+
+use super::form::ServerForm;
+use super::servers_page::output_log_style_builder;
+use super::{wrap_index, App, Page, View};
+use crate::keymap::{Action, Hint};
+use crate::procinfo::{self, ListeningPort};
+use crate::servers::{self, LogStream};
+use crate::triggers::TriggerAction;
+
+// How often the sockets of the selected server are looked up again
+const PORTS_REFRESH: Duration = Duration::from_secs(2);
+
+// Everything known about the selected server
+pub struct DetailsPage {
+    ports: Vec<ListeningPort>,
+    ports_of: Option<(u32, Instant)>, // pid the ports belong to and when they were read
+}
+
+impl DetailsPage {
+    pub fn new() -> DetailsPage {
+        DetailsPage {
+            ports: Vec::new(),
+            ports_of: None,
+        }
+    }
+
+    // Walking /proc for sockets is too slow to do every frame
+    fn refresh_ports(&mut self, pid: Option<u32>) {
+        let Some(pid) = pid else {
+            self.ports.clear();
+            self.ports_of = None;
+            return;
+        };
+        if let Some((last_pid, read_at)) = self.ports_of {
+            if last_pid == pid && read_at.elapsed() < PORTS_REFRESH {
+                return;
+            }
+        }
+        self.ports = procinfo::listening_ports(&procinfo::process_tree(pid));
+        self.ports_of = Some((pid, Instant::now()));
+    }
+}
+
+fn field<'a>(label: &'a str, value: String) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(format!("{:<14}", label), Style::new().fg(Color::DarkGray)),
+        Span::raw(value),
+    ])
+}
+
+impl Page for DetailsPage {
+    fn title(&self) -> &'static str {
+        "Server Details"
+    }
+
+    fn handle_key(&mut self, app: &mut App, view: &mut View, key: KeyEvent) -> bool {
+        if app.available_servers.is_empty() {
+            return false;
+        }
+        let name = app.available_servers[view.selected_server].name.clone();
+        match app.keymap.action(&key) {
+            Some(Action::Down) => {
+                view.selected_server =
+                    wrap_index(view.selected_server, app.available_servers.len() - 1, 1);
+            }
+            Some(Action::Up) => {
+                view.selected_server =
+                    wrap_index(view.selected_server, app.available_servers.len() - 1, -1);
+            }
+            Some(Action::Launch) => {
+                let _ = app.launch_server(&view.identity, &name);
+            }
+            Some(Action::Kill) => {
+                let _ = app.kill_server(&view.identity, &name);
+            }
+            Some(Action::Restart) => {
+                let _ = app.restart_server(&view.identity, &name);
+            }
+            Some(Action::EditServer) => {
+                let server = &app.available_servers[view.selected_server];
+                let running = app.allocated_servers.contains_key(&server.name);
+                view.form = Some(ServerForm::edit(server, running));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn controls(&self) -> &'static [&'static [Hint]] {
+        &[&[
+            Hint::Actions(&[Action::Down, Action::Up], "Previous/Next Server"),
+            Hint::Actions(&[Action::Launch], "Launch"),
+            Hint::Actions(&[Action::Kill], "Kill"),
+            Hint::Actions(&[Action::Restart], "Restart"),
+            Hint::Actions(&[Action::EditServer], "Edit"),
+        ]]
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, app: &App, view: &View) {
+        let Some(server) = view.selected(app) else {
+            let block = Block::default().title("Server Details").borders(Borders::ALL);
+            frame.render_widget(Paragraph::new("No servers").block(block), area);
+            return;
+        };
+        let handle = app.allocated_servers.get(&server.name);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[0]);
+
+        // Process
+        let waiting_for_ready = server
+            .triggers
+            .iter()
+            .any(|trigger| trigger.action == TriggerAction::MarkReady);
+        let state = match handle {
+            Some(handle) if waiting_for_ready && !handle.ready => {
+                Span::styled("starting", Style::new().fg(Color::Yellow))
+            }
+            Some(handle) if handle.ready => Span::styled("running (ready)", Style::new().fg(Color::Green)),
+            Some(_) => Span::styled("running", Style::new().fg(Color::Green)),
+            None => Span::styled("stopped", Style::new().fg(Color::Red)),
+        };
+        let pid = handle.and_then(|handle| handle.pid());
+        self.refresh_ports(pid);
+
+        let mut process = vec![
+            Line::from(vec![
+                Span::styled(format!("{:<14}", "State"), Style::new().fg(Color::DarkGray)),
+                state,
+            ]),
+            field("PID", pid.map_or("-".to_string(), |pid| pid.to_string())),
+            field(
+                "Uptime",
+                handle.map_or("-".to_string(), |handle| {
+                    format_uptime((Local::now() - handle.started_at).num_seconds())
+                }),
+            ),
+            field(
+                "Last launch",
+                app.server_started
+                    .get(&server.name)
+                    .map_or("never".to_string(), |time| time.format("%Y-%m-%d %H:%M:%S").to_string()),
+            ),
+            field(
+                "Restarts",
+                app.restarts.get(&server.name).copied().unwrap_or(0).to_string(),
+            ),
+            field(
+                "Last exit",
+                app.last_exit.get(&server.name).map_or("-".to_string(), |&code| {
+                    if code < 0 {
+                        format!("signal {}", -code)
+                    } else {
+                        code.to_string()
+                    }
+                }),
+            ),
+            field(
+                "Working dir",
+                pid.and_then(procinfo::cwd)
+                    .map_or(server.path.clone(), |path| path.display().to_string()),
+            ),
+            field(
+                "Ports",
+                if self.ports.is_empty() {
+                    "-".to_string()
+                } else {
+                    self.ports
+                        .iter()
+                        .map(|port| format!("{}/{}", port.port, port.protocol))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                },
+            ),
+            field(
+                "Command",
+                handle.map_or_else(|| servers::command_line(server), |handle| handle.command_line.clone()),
+            ),
+        ];
+        if handle.is_none() {
+            process.push(Line::styled(
+                "Not running, the command is what the next launch would run",
+                Style::new().fg(Color::DarkGray),
+            ));
+        }
+        let process = Paragraph::new(process)
+            .block(
+                Block::default()
+                    .title(Span::styled(
+                        server.name.as_str(),
+                        Style::new().fg(server.display_color).add_modifier(Modifier::BOLD),
+                    ))
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(Color::Indexed(33))),
+            )
+            .wrap(Wrap { trim: false });
+        frame.render_widget(process, columns[0]);
+
+        // Definition
+        let mut lines = vec![
+            field("Path", server.path.clone()),
+            field("Executable", server.executable.clone()),
+            field("Args", server.args.join(" ")),
+            field("Autostart", server.autostart.to_string()),
+            field("Test server", server.test_server.to_string()),
+            field("Log preset", server.log_preset.name().to_string()),
+            Line::raw(""),
+            Line::styled("Log rules", Style::new().add_modifier(Modifier::BOLD)),
+        ];
+        lines.extend(server.log_rules.iter().map(|rule| {
+            field(rule.level.label(), rule.pattern.as_str().to_string())
+        }));
+        lines.push(Line::raw(""));
+        lines.push(Line::styled("Triggers", Style::new().add_modifier(Modifier::BOLD)));
+        lines.extend(server.triggers.iter().map(|trigger| {
+            let mut action = trigger.action.kind().to_string();
+            if !trigger.action.argument().is_empty() {
+                action.push_str(&format!(" `{}`", trigger.action.argument()));
+            }
+            if !trigger.cooldown.is_zero() {
+                action.push_str(&format!(" (cooldown {}s)", trigger.cooldown.as_secs()));
+            }
+            Line::raw(format!("/{}/ -> {}", trigger.pattern.as_str(), action))
+        }));
+
+        let definition = Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .title("Definition")
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(Color::Indexed(33))),
+            )
+            .wrap(Wrap { trim: false });
+        frame.render_widget(definition, columns[1]);
+
+        // Recent output, newest at the bottom like the log panel
+        let height = rows[1].height.saturating_sub(2) as usize;
+        let mut recent: Vec<Line> = app
+            .logs
+            .iter()
+            .rev()
+            .filter(|message| message.name == server.name && message.stream != LogStream::Launcher)
+            .take(height)
+            .map(|message| output_log_style_builder(message, app.timestamp_label(message, view.timestamp_format(app)), None, false))
+            .collect();
+        recent.reverse();
+        let recent = Paragraph::new(recent).block(
+            Block::default()
+                .title("Recent Output")
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Color::Indexed(208))),
+        );
+        frame.render_widget(recent, rows[1]);
+    }
+}
+
+fn format_uptime(seconds: i64) -> String {
+    format!(
+        "{}d {:02}:{:02}:{:02}",
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,318 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::mouse::{self, Buttons};
+use crate::db::Server;
+
+const HINT: &str = "(Tab/Up/Down) Move | (Space) Toggle | (Enter) Save | (Esc) Cancel";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    Name,
+    Path,
+    Executable,
+    Args,
+    Autostart,
+    TestServer,
+    Color,
+}
+
+impl Field {
+    const ALL: [Field; 7] = [
+        Field::Name,
+        Field::Path,
+        Field::Executable,
+        Field::Args,
+        Field::Autostart,
+        Field::TestServer,
+        Field::Color,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Name => "Name",
+            Field::Path => "Path",
+            Field::Executable => "Executable",
+            Field::Args => "Args",
+            Field::Autostart => "Autostart",
+            Field::TestServer => "Test server",
+            Field::Color => "Color",
+        }
+    }
+
+    fn is_text(self) -> bool {
+        !matches!(self, Field::Autostart | Field::TestServer)
+    }
+}
+
+pub enum FormResult {
+    Continue,
+    Cancel,
+    Submit,
+}
+
+// Modal for entering a server definition, every field is kept as typed until saved
+pub struct ServerForm {
+    base: Server, // what the fields are applied on top of, keeps rules and triggers when editing
+    pub editing: Option<i32>, // id of the server being edited, None when adding
+    running: bool, // the edited server is running right now
+    name: String,
+    path: String,
+    executable: String,
+    args: String,
+    autostart: bool,
+    test_server: bool,
+    color: String, // #rrggbb, empty for the default
+    focused: usize,
+    pub error: Option<String>, // set when saving fails
+}
+
+impl ServerForm {
+    pub fn new() -> ServerForm {
+        ServerForm {
+            base: Server::default(),
+            editing: None,
+            running: false,
+            name: String::new(),
+            path: String::new(),
+            executable: String::new(),
+            args: String::new(),
+            autostart: false,
+            test_server: false,
+            color: String::new(),
+            focused: 0,
+            error: None,
+        }
+    }
+
+    pub fn edit(server: &Server, running: bool) -> ServerForm {
+        let color = match server.display_color {
+            Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            _ => String::new(),
+        };
+        ServerForm {
+            base: server.clone(),
+            editing: Some(server.id),
+            running,
+            name: server.name.clone(),
+            path: server.path.clone(),
+            executable: server.executable.clone(),
+            args: server.args.join(" "),
+            autostart: server.autostart,
+            test_server: server.test_server,
+            color,
+            focused: 0,
+            error: None,
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.editing {
+            Some(_) => format!("Edit Server: {}", self.base.name),
+            None => "Add Server".to_string(),
+        }
+    }
+
+    fn focused(&self) -> Field {
+        Field::ALL[self.focused]
+    }
+
+    fn text_mut(&mut self, field: Field) -> Option<&mut String> {
+        match field {
+            Field::Name => Some(&mut self.name),
+            Field::Path => Some(&mut self.path),
+            Field::Executable => Some(&mut self.executable),
+            Field::Args => Some(&mut self.args),
+            Field::Color => Some(&mut self.color),
+            Field::Autostart | Field::TestServer => None,
+        }
+    }
+
+    fn value(&self, field: Field) -> String {
+        let check = |on: bool| if on { "[x]" } else { "[ ]" }.to_string();
+        match field {
+            Field::Name => self.name.clone(),
+            Field::Path => self.path.clone(),
+            Field::Executable => self.executable.clone(),
+            Field::Args => self.args.clone(),
+            Field::Autostart => check(self.autostart),
+            Field::TestServer => check(self.test_server),
+            Field::Color => self.color.clone(),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> FormResult {
+        let field = self.focused();
+        match key.code {
+            KeyCode::Esc => return FormResult::Cancel,
+            KeyCode::Enter => return FormResult::Submit,
+            KeyCode::Tab | KeyCode::Down => {
+                self.focused = (self.focused + 1) % Field::ALL.len();
+            }
+            KeyCode::BackTab | KeyCode::Up => {
+                self.focused = (self.focused + Field::ALL.len() - 1) % Field::ALL.len();
+            }
+            KeyCode::Char(' ') if field == Field::Autostart => self.autostart = !self.autostart,
+            KeyCode::Char(' ') if field == Field::TestServer => {
+                self.test_server = !self.test_server
+            }
+            KeyCode::Backspace => {
+                if let Some(text) = self.text_mut(field) {
+                    text.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(text) = self.text_mut(field) {
+                    text.push(c);
+                }
+            }
+            _ => {}
+        }
+        FormResult::Continue
+    }
+
+    fn parsed_color(&self) -> Option<Color> {
+        match self.color.trim() {
+            "" => Some(Color::White),
+            text => match Color::from_str(text) {
+                Ok(color @ Color::Rgb(..)) => Some(color),
+                _ => None,
+            },
+        }
+    }
+
+    // Problems that keep the form from being saved, checked on every draw
+    pub fn validate(&self, existing: &[Server]) -> Vec<(Field, String)> {
+        let mut errors = Vec::new();
+        let name = self.name.trim();
+        if name.is_empty() {
+            errors.push((Field::Name, "required".to_string()));
+        } else if existing.iter().any(|server| {
+            Some(server.id) != self.editing && server.name.eq_ignore_ascii_case(name)
+        }) {
+            errors.push((Field::Name, "already used".to_string()));
+        } else if self.running && name != self.base.name {
+            // Running servers are tracked by name
+            errors.push((Field::Name, "stop the server to rename it".to_string()));
+        }
+
+        // Test servers never touch the disk
+        if !self.test_server {
+            if self.path.trim().is_empty() {
+                errors.push((Field::Path, "required".to_string()));
+            } else if !Path::new(self.path.trim()).is_dir() {
+                errors.push((Field::Path, "no such directory".to_string()));
+            }
+            if self.executable.trim().is_empty() {
+                errors.push((Field::Executable, "required".to_string()));
+            }
+        }
+
+        if self.parsed_color().is_none() {
+            errors.push((Field::Color, "use #rrggbb".to_string()));
+        }
+        errors
+    }
+
+    pub fn to_server(&self) -> Server {
+        self.base
+            .clone()
+            .name(self.name.trim())
+            .path(self.path.trim())
+            .executable(self.executable.trim())
+            .args(self.args.split_whitespace().map(String::from).collect())
+            .autostart(self.autostart)
+            .test_server(self.test_server)
+            .display_color(self.parsed_color().unwrap_or(Color::White))
+    }
+
+    // Returns where its hints can be clicked
+    pub fn render(&self, frame: &mut Frame, errors: &[(Field, String)]) -> Buttons {
+        let height = Field::ALL.len() as u16 + 5 + self.running as u16;
+        let area = centered_rect(frame.size(), 70, height);
+
+        let mut lines: Vec<Line> = Field::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, &field)| {
+                let focused = i == self.focused;
+                let label_style = if focused {
+                    Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::new()
+                };
+                let mut spans = vec![
+                    Span::styled(format!("{:<12}", field.label()), label_style),
+                    Span::raw(self.value(field)),
+                ];
+                if focused && field.is_text() {
+                    spans.push(Span::styled("_", Style::new().add_modifier(Modifier::SLOW_BLINK)));
+                }
+                if field == Field::Color {
+                    if let Some(color) = self.parsed_color() {
+                        spans.push(Span::styled(" ██", Style::new().fg(color)));
+                    }
+                }
+                if let Some((_, error)) = errors.iter().find(|(f, _)| *f == field) {
+                    spans.push(Span::styled(
+                        format!("  ! {}", error),
+                        Style::new().fg(Color::Red),
+                    ));
+                }
+                Line::from(spans)
+            })
+            .collect();
+
+        lines.push(Line::raw(""));
+        if self.running {
+            lines.push(Line::styled(
+                "Server is running, changes apply on its next launch",
+                Style::new().fg(Color::Yellow),
+            ));
+        }
+        let hint_row = lines.len() as u16;
+        match &self.error {
+            Some(error) => lines.push(Line::styled(error.as_str(), Style::new().fg(Color::Red))),
+            None => lines.push(Line::styled(HINT, Style::new().fg(Color::DarkGray))),
+        }
+
+        let block = Block::default()
+            .title(self.title())
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(Color::Indexed(33)));
+        let inner = block.inner(area);
+        let form = Paragraph::new(lines).block(block);
+        frame.render_widget(Clear, area);
+        frame.render_widget(form, area);
+
+        match self.error {
+            Some(_) => Vec::new(),
+            None => mouse::hint_buttons(HINT, inner.x, inner.y + hint_row),
+        }
+    }
+}
+
+// A rect of `percent_x` width and fixed height in the middle of `area`
+pub fn centered_rect(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Fill(1),
+            Constraint::Length(height.min(area.height)),
+            Constraint::Fill(1),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
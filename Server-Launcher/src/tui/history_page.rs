@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use crossterm::event::KeyEvent;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Cell, Row, Table, TableState},
+};
+
+use super::{App, Page, View};
+use crate::db::{self, Run};
+use crate::keymap::{Action, Hint};
+
+// How many runs are read from the db
+const HISTORY_LIMIT: usize = 500;
+const REFRESH_EVERY: Duration = Duration::from_secs(1);
+
+// Past launches from the runs table, newest first
+pub struct HistoryPage {
+    runs: Vec<Run>,
+    loaded_at: Option<Instant>,
+    state: TableState,
+}
+
+impl HistoryPage {
+    pub fn new() -> HistoryPage {
+        HistoryPage {
+            runs: Vec::new(),
+            loaded_at: None,
+            state: TableState::default(),
+        }
+    }
+
+    fn refresh(&mut self, app: &App) {
+        if self.loaded_at.is_some_and(|loaded| loaded.elapsed() < REFRESH_EVERY) {
+            return;
+        }
+        self.runs = db::load_runs(&app.db, HISTORY_LIMIT).unwrap_or_default();
+        self.loaded_at = Some(Instant::now());
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        let last = self.runs.len().saturating_sub(1);
+        let selected = self.state.selected().unwrap_or(0);
+        self.state
+            .select(Some(selected.saturating_add_signed(delta).min(last)));
+    }
+}
+
+fn format_duration(seconds: i64) -> String {
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+fn exit_cell(run: &Run) -> Cell<'static> {
+    match (run.ended_at, run.exit_code) {
+        (None, _) => Cell::from("running").style(Style::new().fg(Color::Green)),
+        (Some(_), Some(0)) => Cell::from("0"),
+        (Some(_), Some(code)) if code < 0 => {
+            Cell::from(format!("signal {}", -code)).style(Style::new().fg(Color::Yellow))
+        }
+        (Some(_), Some(code)) => Cell::from(code.to_string()).style(Style::new().fg(Color::Red)),
+        (Some(_), None) => Cell::from("-"),
+    }
+}
+
+impl Page for HistoryPage {
+    fn title(&self) -> &'static str {
+        "History"
+    }
+
+    fn handle_key(&mut self, app: &mut App, _view: &mut View, key: KeyEvent) -> bool {
+        match app.keymap.action(&key) {
+            Some(Action::Down) => self.scroll(1),
+            Some(Action::Up) => self.scroll(-1),
+            Some(Action::PageDown) => self.scroll(20),
+            Some(Action::PageUp) => self.scroll(-20),
+            Some(Action::Top) => self.state.select(Some(0)),
+            _ => return false,
+        }
+        true
+    }
+
+    fn controls(&self) -> &'static [&'static [Hint]] {
+        &[&[
+            Hint::Actions(&[Action::Down, Action::Up, Action::PageDown, Action::PageUp], "Scroll"),
+            Hint::Actions(&[Action::Top], "Newest"),
+        ]]
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, app: &App, _view: &View) {
+        self.refresh(app);
+
+        let rows: Vec<Row> = self
+            .runs
+            .iter()
+            .map(|run| {
+                // Unfinished runs from an earlier session never got an end time
+                let duration = (run.ended_at.unwrap_or_else(Local::now) - run.started_at).num_seconds();
+                Row::new(vec![
+                    Cell::from(run.server_name.clone()),
+                    Cell::from(run.started_at.format("%Y-%m-%d %H:%M:%S").to_string()),
+                    Cell::from(
+                        run.ended_at
+                            .map_or("-".to_string(), |end| end.format("%H:%M:%S").to_string()),
+                    ),
+                    Cell::from(format_duration(duration)),
+                    exit_cell(run),
+                ])
+            })
+            .collect();
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(19),
+                Constraint::Length(8),
+                Constraint::Length(9),
+                Constraint::Length(10),
+            ],
+        )
+        .header(
+            Row::new(vec!["Server", "Started", "Ended", "Duration", "Exit"])
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .block(
+            Block::default()
+                .title(format!("Run History ({})", self.runs.len()))
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Color::Indexed(208))),
+        )
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(table, area, &mut self.state);
+    }
+}
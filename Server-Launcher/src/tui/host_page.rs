@@ -0,0 +1,329 @@
+use std::fs;
+use std::time::Duration;
+
+use crossterm::event::KeyEvent;
+use ratatui::{
+    prelude::*,
+    symbols,
+    widgets::{Axis, Bar, BarChart, BarGroup, Block, Borders, Chart, Dataset, Gauge, GraphType, Paragraph},
+};
+
+use super::{App, Page, View};
+use crate::hoststats::{self, HostSampler};
+use crate::keymap::Hint;
+
+const SAMPLE_EVERY: Duration = Duration::from_secs(1);
+
+// Overview of the machine the servers run on
+pub struct HostPage {
+    sampler: HostSampler,
+}
+
+fn read_proc(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|text| text.trim().to_string())
+}
+
+fn uptime() -> Option<String> {
+    let text = read_proc("/proc/uptime")?;
+    let seconds = text.split_whitespace().next()?.parse::<f64>().ok()? as u64;
+    Some(format!(
+        "{}d {:02}h {:02}m",
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60
+    ))
+}
+
+fn bordered(title: String, color: u8) -> Block<'static> {
+    Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::new().fg(Color::Indexed(color)))
+}
+
+fn ratio(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        (used as f64 / total as f64).clamp(0.0, 1.0)
+    }
+}
+
+// Green while there is room, then yellow and red
+fn usage_color(ratio: f64) -> Color {
+    if ratio >= 0.9 {
+        Color::Red
+    } else if ratio >= 0.7 {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+fn gauge(label: String, ratio: f64) -> Gauge<'static> {
+    Gauge::default()
+        .gauge_style(Style::new().fg(usage_color(ratio)))
+        .ratio(ratio)
+        .label(label)
+}
+
+impl HostPage {
+    pub fn new() -> HostPage {
+        HostPage {
+            sampler: HostSampler::new(),
+        }
+    }
+
+    fn render_summary(&self, frame: &mut Frame, area: Rect, app: &App) {
+        let unknown = || "unknown".to_string();
+        let load = read_proc("/proc/loadavg")
+            .map(|text| text.split_whitespace().take(3).collect::<Vec<_>>().join(" "));
+        let summary = Paragraph::new(Line::raw(format!(
+            "{} | up {} | load {} | {} of {} servers running",
+            read_proc("/proc/sys/kernel/hostname").unwrap_or_else(unknown),
+            uptime().unwrap_or_else(unknown),
+            load.unwrap_or_else(unknown),
+            app.allocated_servers.len(),
+            app.available_servers.len()
+        )))
+        .block(bordered("Host".to_string(), 50));
+        frame.render_widget(summary, area);
+    }
+
+    fn render_cores(&self, frame: &mut Frame, area: Rect) {
+        let bars: Vec<Bar> = self
+            .sampler
+            .cores
+            .iter()
+            .enumerate()
+            .map(|(i, &percent)| {
+                Bar::default()
+                    .label(Line::raw(i.to_string()))
+                    .value(percent)
+                    .text_value(format!("{}", percent))
+                    .style(Style::new().fg(usage_color(percent as f64 / 100.0)))
+            })
+            .collect();
+        // Squeeze the bars so every core fits
+        let cores = bars.len().max(1) as u16;
+        let inner_width = area.width.saturating_sub(2);
+        let bar_width = (inner_width / cores).saturating_sub(1).clamp(1, 5);
+
+        let chart = BarChart::default()
+            .block(bordered("CPU % per core".to_string(), 33))
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width)
+            .bar_gap(1)
+            .max(100);
+        frame.render_widget(chart, area);
+    }
+
+    fn render_memory(&self, frame: &mut Frame, area: Rect) {
+        let block = bordered("Memory".to_string(), 33);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let Some(memory) = &self.sampler.memory else {
+            return;
+        };
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Length(1)])
+            .split(inner);
+
+        let kb = |kb: u64| hoststats::format_bytes(kb as f64 * 1024.0);
+        let used = memory.total_kb.saturating_sub(memory.available_kb);
+        frame.render_widget(
+            gauge(
+                format!("RAM {} / {}", kb(used), kb(memory.total_kb)),
+                ratio(used, memory.total_kb),
+            ),
+            rows[0],
+        );
+        let swap_used = memory.swap_total_kb.saturating_sub(memory.swap_free_kb);
+        let swap_label = if memory.swap_total_kb == 0 {
+            "no swap".to_string()
+        } else {
+            format!("Swap {} / {}", kb(swap_used), kb(memory.swap_total_kb))
+        };
+        frame.render_widget(gauge(swap_label, ratio(swap_used, memory.swap_total_kb)), rows[2]);
+    }
+
+    fn render_disks(&self, frame: &mut Frame, area: Rect) {
+        let block = bordered("Disks of server paths".to_string(), 33);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        if self.sampler.disks.is_empty() {
+            frame.render_widget(
+                Paragraph::new("no server path found on disk").style(Style::new().fg(Color::DarkGray)),
+                inner,
+            );
+            return;
+        }
+
+        // Each disk gets a label line and a gauge
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(1); self.sampler.disks.len() * 2])
+            .split(inner);
+        for (i, disk) in self.sampler.disks.iter().enumerate() {
+            let used = disk.total.saturating_sub(disk.available);
+            frame.render_widget(
+                Paragraph::new(format!("{} ({})", disk.mount, disk.servers.join(", "))),
+                rows[i * 2],
+            );
+            frame.render_widget(
+                gauge(
+                    format!(
+                        "{} / {}",
+                        hoststats::format_bytes(used as f64),
+                        hoststats::format_bytes(disk.total as f64)
+                    ),
+                    ratio(used, disk.total),
+                ),
+                rows[i * 2 + 1],
+            );
+        }
+    }
+
+    fn render_network(&self, frame: &mut Frame, area: Rect) {
+        let rx: Vec<(f64, f64)> = self.sampler.rx_history.iter().copied().collect();
+        let tx: Vec<(f64, f64)> = self.sampler.tx_history.iter().copied().collect();
+        let start = rx.first().map_or(0.0, |point| point.0);
+        let end = rx.last().map_or(1.0, |point| point.0).max(start + 1.0);
+        let peak = rx
+            .iter()
+            .chain(tx.iter())
+            .map(|point| point.1)
+            .fold(1024.0, f64::max);
+
+        let rates: Vec<String> = self
+            .sampler
+            .interfaces
+            .iter()
+            .filter(|interface| interface.rx_rate > 0.0 || interface.tx_rate > 0.0)
+            .map(|interface| {
+                format!(
+                    "{} ↓{}/s ↑{}/s",
+                    interface.name,
+                    hoststats::format_bytes(interface.rx_rate),
+                    hoststats::format_bytes(interface.tx_rate)
+                )
+            })
+            .collect();
+        let title = if rates.is_empty() {
+            "Network".to_string()
+        } else {
+            format!("Network | {}", rates.join(" | "))
+        };
+
+        let datasets = vec![
+            Dataset::default()
+                .name("rx")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::new().fg(Color::Cyan))
+                .data(&rx),
+            Dataset::default()
+                .name("tx")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::new().fg(Color::Magenta))
+                .data(&tx),
+        ];
+        let chart = Chart::new(datasets)
+            .block(bordered(title, 208))
+            .x_axis(Axis::default().bounds([start, end]))
+            .y_axis(
+                Axis::default()
+                    .bounds([0.0, peak])
+                    .labels(vec![
+                        Span::raw("0"),
+                        Span::raw(format!("{}/s", hoststats::format_bytes(peak))),
+                    ]),
+            );
+        frame.render_widget(chart, area);
+    }
+
+    fn render_temperatures(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = if self.sampler.temperatures.is_empty() {
+            vec![Line::styled("no sensors found", Style::new().fg(Color::DarkGray))]
+        } else {
+            self.sampler
+                .temperatures
+                .iter()
+                .map(|temperature| {
+                    let color = if temperature.celsius >= 85.0 {
+                        Color::Red
+                    } else if temperature.celsius >= 70.0 {
+                        Color::Yellow
+                    } else {
+                        Color::Green
+                    };
+                    Line::from(vec![
+                        Span::raw(format!("{:<24}", temperature.label)),
+                        Span::styled(format!("{:>5.1}°C", temperature.celsius), Style::new().fg(color)),
+                    ])
+                })
+                .collect()
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(bordered("Temperatures".to_string(), 50)),
+            area,
+        );
+    }
+}
+
+impl Page for HostPage {
+    fn title(&self) -> &'static str {
+        "Host Stats"
+    }
+
+    fn handle_key(&mut self, _app: &mut App, _view: &mut View, _key: KeyEvent) -> bool {
+        false
+    }
+
+    fn controls(&self) -> &'static [&'static [Hint]] {
+        &[]
+    }
+
+    // Only sampled while the page is shown
+    fn render(&mut self, frame: &mut Frame, area: Rect, app: &App, _view: &View) {
+        if self
+            .sampler
+            .last_sample()
+            .is_none_or(|last| last.elapsed() >= SAMPLE_EVERY)
+        {
+            let paths: Vec<(String, String)> = app
+                .available_servers
+                .iter()
+                .filter(|server| !server.test_server)
+                .map(|server| (server.name.clone(), server.path.clone()))
+                .collect();
+            self.sampler.sample(&paths);
+        }
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Fill(1), Constraint::Fill(1)])
+            .split(area);
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(rows[1]);
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(rows[2]);
+        let memory_and_disks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(5), Constraint::Fill(1)])
+            .split(top[1]);
+
+        self.render_summary(frame, rows[0], app);
+        self.render_cores(frame, top[0]);
+        self.render_memory(frame, memory_and_disks[0]);
+        self.render_disks(frame, memory_and_disks[1]);
+        self.render_network(frame, bottom[0]);
+        self.render_temperatures(frame, bottom[1]);
+    }
+}
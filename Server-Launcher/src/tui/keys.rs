@@ -0,0 +1,205 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+
+// Turns the bytes a remote terminal sends into the key and mouse events crossterm gives the
+// local one. Covers what xterm-like terminals send for the keys the pages use, and mouse
+// reports in the SGR and the older X10 encoding.
+
+pub fn parse(bytes: &[u8]) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (event, used) = next(rest);
+        if let Some(event) = event {
+            events.push(event);
+        }
+        rest = &rest[used..];
+    }
+    events
+}
+
+fn key(code: KeyCode) -> Option<Event> {
+    Some(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)))
+}
+
+// One event off the front, with how many bytes it took
+fn next(bytes: &[u8]) -> (Option<Event>, usize) {
+    match bytes[0] {
+        0x1b => escape(bytes),
+        b'\r' | b'\n' => (key(KeyCode::Enter), 1),
+        b'\t' => (key(KeyCode::Tab), 1),
+        0x7f | 0x08 => (key(KeyCode::Backspace), 1),
+        byte @ 0x01..=0x1a => {
+            let letter = (b'a' + byte - 1) as char;
+            (Some(Event::Key(KeyEvent::new(KeyCode::Char(letter), KeyModifiers::CONTROL))), 1)
+        }
+        _ => {
+            let (c, used) = utf8_char(bytes);
+            (c.and_then(|c| key(KeyCode::Char(c))), used)
+        }
+    }
+}
+
+fn utf8_char(bytes: &[u8]) -> (Option<char>, usize) {
+    let len = match bytes[0] {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return (None, 1),
+    };
+    let len = len.min(bytes.len());
+    match std::str::from_utf8(&bytes[..len]) {
+        Ok(text) => (text.chars().next(), len),
+        Err(_) => (None, len),
+    }
+}
+
+fn escape(bytes: &[u8]) -> (Option<Event>, usize) {
+    match bytes.get(1) {
+        // A lone Esc, terminals send the whole sequence in one write
+        None => (key(KeyCode::Esc), 1),
+        Some(b'[') if bytes.get(2) == Some(&b'M') => x10_mouse(bytes),
+        Some(b'[') => csi(bytes),
+        Some(b'O') => match bytes.get(2) {
+            Some(&final_byte) => (ss3(final_byte).and_then(key), 3),
+            None => (Some(Event::Key(KeyEvent::new(KeyCode::Char('O'), KeyModifiers::ALT))), 2),
+        },
+        Some(0x1b) => (key(KeyCode::Esc), 1),
+        Some(_) => {
+            // Alt held down
+            let (inner, used) = next(&bytes[1..]);
+            let inner = inner.map(|inner| match inner {
+                Event::Key(mut inner) => {
+                    inner.modifiers |= KeyModifiers::ALT;
+                    Event::Key(inner)
+                }
+                other => other,
+            });
+            (inner, used + 1)
+        }
+    }
+}
+
+fn ss3(final_byte: u8) -> Option<KeyCode> {
+    Some(match final_byte {
+        b'A' => KeyCode::Up,
+        b'B' => KeyCode::Down,
+        b'C' => KeyCode::Right,
+        b'D' => KeyCode::Left,
+        b'H' => KeyCode::Home,
+        b'F' => KeyCode::End,
+        b'P' => KeyCode::F(1),
+        b'Q' => KeyCode::F(2),
+        b'R' => KeyCode::F(3),
+        b'S' => KeyCode::F(4),
+        _ => return None,
+    })
+}
+
+// ESC [ params final, e.g. ESC[A for Up or ESC[15~ for F5
+fn csi(bytes: &[u8]) -> (Option<Event>, usize) {
+    let Some(end) = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b)) else {
+        return (None, bytes.len());
+    };
+    let used = end + 3;
+    let params = std::str::from_utf8(&bytes[2..end + 2]).unwrap_or_default();
+    if let Some(params) = params.strip_prefix('<') {
+        return (sgr_mouse(params, bytes[end + 2] == b'M'), used);
+    }
+    let mut numbers = params.split(';').map(|n| n.parse::<u8>().unwrap_or(1));
+    let first = numbers.next().unwrap_or(1);
+    // xterm sends 1 + shift(1) + alt(2) + ctrl(4) as the second parameter
+    let bits = numbers.next().unwrap_or(1).saturating_sub(1);
+    let mut modifiers = KeyModifiers::NONE;
+    if bits & 1 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if bits & 2 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if bits & 4 != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+
+    let code = match bytes[end + 2] {
+        b'Z' => Some(KeyCode::BackTab),
+        b'~' => match first {
+            1 | 7 => Some(KeyCode::Home),
+            2 => Some(KeyCode::Insert),
+            3 => Some(KeyCode::Delete),
+            4 | 8 => Some(KeyCode::End),
+            5 => Some(KeyCode::PageUp),
+            6 => Some(KeyCode::PageDown),
+            11..=15 => Some(KeyCode::F(first - 10)),
+            17..=21 => Some(KeyCode::F(first - 11)),
+            23 | 24 => Some(KeyCode::F(first - 12)),
+            _ => None,
+        },
+        final_byte => ss3(final_byte),
+    };
+    (code.map(|code| Event::Key(KeyEvent::new(code, modifiers))), used)
+}
+
+// ESC [ < button ; column ; row M, or m when a button is let go. Columns and rows count from 1.
+fn sgr_mouse(params: &str, pressed: bool) -> Option<Event> {
+    let mut numbers = params.split(';').map(|n| n.parse::<u16>().ok());
+    let (button, column, row) = (numbers.next()??, numbers.next()??, numbers.next()??);
+    mouse(button, column.checked_sub(1)?, row.checked_sub(1)?, pressed)
+}
+
+// ESC [ M and three bytes: the SGR button plus 32, then column and row plus 33.
+// Releases say button 3 instead of which one.
+fn x10_mouse(bytes: &[u8]) -> (Option<Event>, usize) {
+    let Some(&[button, column, row]) = bytes.get(3..6) else {
+        return (None, bytes.len());
+    };
+    let event = match (button.checked_sub(32), column.checked_sub(33), row.checked_sub(33)) {
+        (Some(button), Some(column), Some(row)) => {
+            mouse(button as u16, column as u16, row as u16, button & 3 != 3)
+        }
+        _ => None,
+    };
+    (event, 6)
+}
+
+// The button number holds which button in its low bits, then shift(4), alt(8), ctrl(16),
+// motion(32) and wheel(64)
+fn mouse(button: u16, column: u16, row: u16, pressed: bool) -> Option<Event> {
+    let mut modifiers = KeyModifiers::NONE;
+    if button & 4 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    if button & 8 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    if button & 16 != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    let which = match button & 3 {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None,
+    };
+    let kind = if button & 64 != 0 {
+        match button & 3 {
+            0 => MouseEventKind::ScrollUp,
+            1 => MouseEventKind::ScrollDown,
+            2 => MouseEventKind::ScrollLeft,
+            _ => MouseEventKind::ScrollRight,
+        }
+    } else if button & 32 != 0 {
+        which.map_or(MouseEventKind::Moved, MouseEventKind::Drag)
+    } else if pressed {
+        MouseEventKind::Down(which?)
+    } else {
+        // X10 releases carry no button, Left stands in for it
+        MouseEventKind::Up(which.unwrap_or(MouseButton::Left))
+    };
+    Some(Event::Mouse(MouseEvent {
+        kind,
+        column,
+        row,
+        modifiers,
+    }))
+}
@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::prelude::*;
+
+use crate::keymap::Key;
+
+// Hit testing for the mouse. Areas are remembered while drawing and looked up when the
+// events come in. The `(K) Label` hints drawn everywhere double as buttons that press their key.
+
+// Two left clicks on the same item within this count as a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+pub type Buttons = Vec<(Rect, KeyEvent)>;
+
+pub fn contains(area: Rect, mouse: &MouseEvent) -> bool {
+    (area.x..area.right()).contains(&mouse.column) && (area.y..area.bottom()).contains(&mouse.row)
+}
+
+pub fn is_left_click(mouse: &MouseEvent) -> bool {
+    mouse.kind == MouseEventKind::Down(MouseButton::Left)
+}
+
+// The key of the button a left click landed on
+pub fn clicked(buttons: &Buttons, mouse: &MouseEvent) -> Option<KeyEvent> {
+    if !is_left_click(mouse) {
+        return None;
+    }
+    buttons
+        .iter()
+        .find(|(area, _)| contains(*area, mouse))
+        .map(|&(_, key)| key)
+}
+
+// The buttons in a hint line like "(x) Kill Server | (c) Clear Logs" drawn from `x` on row `y`.
+// Hints for several keys at once, like "(j/Down, k/Up) Navigate", are not buttons.
+pub fn hint_buttons(line: &str, x: u16, y: u16) -> Buttons {
+    let mut buttons = Vec::new();
+    let mut start = x;
+    for segment in line.split(" | ") {
+        let width = segment.chars().count() as u16;
+        let key = segment
+            .strip_prefix('(')
+            .and_then(|rest| rest.split_once(") "))
+            .and_then(|(keys, _)| hint_key(keys));
+        if let Some(key) = key {
+            buttons.push((Rect::new(start, y, width, 1), key));
+        }
+        start += width + 3;
+    }
+    buttons
+}
+
+// Same for a line centered in `area`, as Alignment::Center draws it
+pub fn centered_hint_buttons(line: &str, area: Rect, y: u16) -> Buttons {
+    let width = line.chars().count() as u16;
+    if width > area.width {
+        return Vec::new(); // wrapped, the columns are anyone's guess
+    }
+    hint_buttons(line, area.x + (area.width - width) / 2, y)
+}
+
+// The first of the keys named in a hint, "y/Enter" is y
+fn hint_key(keys: &str) -> Option<KeyEvent> {
+    if keys.contains(',') {
+        return None;
+    }
+    let name = if keys == "/" { keys } else { keys.split('/').next()? };
+    Key::parse(name).map(Key::event)
+}
+
+// Where each tab of a Tabs widget with the default padding and divider went, one row high
+pub fn tab_areas(titles: &[String], area: Rect) -> Vec<Rect> {
+    let mut x = area.x;
+    titles
+        .iter()
+        .map(|title| {
+            // " title " and then the divider
+            let width = title.chars().count() as u16 + 2;
+            let tab = Rect::new(x, area.y, width, 1).intersection(area);
+            x = x.saturating_add(width + 1);
+            tab
+        })
+        .collect()
+}
+
+// Remembers the last click to tell double clicks apart
+#[derive(Default)]
+pub struct Clicks {
+    last: Option<(Instant, usize)>,
+}
+
+impl Clicks {
+    // Call for every left click on an item, true when it is the second on the same one
+    pub fn double(&mut self, item: usize) -> bool {
+        let now = Instant::now();
+        let double = self
+            .last
+            .is_some_and(|(at, last)| last == item && now - at < DOUBLE_CLICK);
+        // A third click starts over instead of making another double
+        self.last = if double { None } else { Some((now, item)) };
+        double
+    }
+}
@@ -0,0 +1,256 @@
+use chrono::{DateTime, Local};
+use serde_json::{json, Value};
+
+use super::App;
+use crate::audit::AuditAction;
+use crate::auth::{Identity, Permission};
+use crate::control::{ControlCommand, ControlError, ControlRequest, ControlResult, ErrorKind};
+use crate::db::Server;
+use crate::detached;
+use crate::serverdef::ServerDef;
+use crate::servers::{MessageType, ServerMessage};
+use crate::triggers::TriggerAction;
+
+// Answers requests from the HTTP API with the same calls the keys make
+impl App {
+    pub(super) fn handle_control(&mut self, request: ControlRequest) {
+        let result = self.run_control(&request.identity, request.command);
+        // The caller may have given up waiting
+        let _ = request.reply.send(result);
+    }
+
+    fn run_control(&mut self, who: &Identity, command: ControlCommand) -> ControlResult {
+        match command {
+            ControlCommand::List => {
+                self.authorize(who, Permission::View, None)?;
+                Ok(Value::Array(
+                    self.available_servers
+                        .iter()
+                        .map(|server| self.server_json(server))
+                        .collect(),
+                ))
+            }
+            ControlCommand::Get(name) => {
+                self.authorize(who, Permission::View, None)?;
+                self.get(&name)
+            }
+            // Permission comes first, a refused caller learns nothing about the server
+            ControlCommand::Start(name) => {
+                self.allow(who, Permission::Control, AuditAction::Launch, &name, "")?;
+                self.find_server(&name)?;
+                self.remote_note(who, format!("start {}", name));
+                self.launch_server(who, &name)?;
+                self.get(&name)
+            }
+            ControlCommand::Stop(name) => {
+                self.allow(who, Permission::Control, AuditAction::Kill, &name, "")?;
+                self.find_server(&name)?;
+                self.require_running(&name)?;
+                self.remote_note(who, format!("stop {}", name));
+                self.kill_server(who, &name)?;
+                self.get(&name)
+            }
+            ControlCommand::Restart(name) => {
+                self.allow(who, Permission::Control, AuditAction::Restart, &name, "")?;
+                self.find_server(&name)?;
+                if !self.allocated_servers.contains_key(&name) {
+                    if let Some(running) = detached::read(&name) {
+                        return Err(background_error(&name, running.supervisor_pid));
+                    }
+                }
+                self.remote_note(who, format!("restart {}", name));
+                self.restart_server(who, &name)?;
+                self.get(&name)
+            }
+            ControlCommand::SendCommand { name, command } => {
+                self.allow(who, Permission::Control, AuditAction::Command, &name, &command)?;
+                self.find_server(&name)?;
+                self.require_running(&name)?;
+                self.send_command(who, &name, &command)?;
+                self.remote_note(who, format!("sent `{}` to {}", command, name));
+                Ok(json!({ "sent": command }))
+            }
+            ControlCommand::Save { name, definition } => {
+                let (action, target) = match &name {
+                    Some(name) => (AuditAction::EditServer, name),
+                    None => (AuditAction::AddServer, &definition.name),
+                };
+                self.allow(who, Permission::Edit, action, target, "")?;
+                let id = match name {
+                    Some(name) => self.find_server(&name)?.id,
+                    None => -1,
+                };
+                let server = definition
+                    .to_server()
+                    .map_err(|e| ControlError::new(ErrorKind::Invalid, e))?
+                    .id(id);
+                self.remote_note(who, format!("save {}", server.name));
+                self.save_server(who, server.clone())?;
+                self.get(&server.name)
+            }
+            ControlCommand::Delete(name) => {
+                self.allow(who, Permission::Edit, AuditAction::DeleteServer, &name, "")?;
+                self.find_server(&name)?;
+                self.remote_note(who, format!("delete {}", name));
+                self.delete_server(who, &name)?;
+                Ok(json!({ "name": name, "deleted": true }))
+            }
+            ControlCommand::Subscribe { filter, events } => {
+                self.authorize(who, Permission::View, None)?;
+                self.subscribe(filter, events);
+                Ok(Value::Null)
+            }
+            ControlCommand::Metrics => {
+                self.authorize(who, Permission::View, None)?;
+                Ok(self.metrics_json())
+            }
+        }
+    }
+
+    // Refusals are audited here, the action itself would have recorded them
+    fn allow(
+        &mut self,
+        who: &Identity,
+        permission: Permission,
+        action: AuditAction,
+        name: &str,
+        detail: &str,
+    ) -> Result<(), ControlError> {
+        let result = self.authorize(who, permission, Some(name));
+        if result.is_err() {
+            self.audit(who, action, name, detail, &result);
+        }
+        result
+    }
+
+    fn get(&self, name: &str) -> ControlResult {
+        Ok(self.server_json(self.find_server(name)?))
+    }
+
+    fn find_server(&self, name: &str) -> Result<&Server, ControlError> {
+        self.available_servers
+            .iter()
+            .find(|server| server.name == name)
+            .ok_or_else(|| ControlError::new(ErrorKind::NotFound, format!("no server named {}", name)))
+    }
+
+    fn require_running(&self, name: &str) -> Result<(), ControlError> {
+        if self.allocated_servers.contains_key(name) {
+            return Ok(());
+        }
+        match detached::read(name) {
+            Some(running) => Err(background_error(name, running.supervisor_pid)),
+            None => Err(ControlError::new(ErrorKind::Conflict, format!("{} is not running", name))),
+        }
+    }
+
+    fn remote_note(&mut self, who: &Identity, text: String) {
+        self.push_log(ServerMessage::new("", format!("Remote request from {}: {}", who.user, text), MessageType::Main));
+    }
+
+    // Definition plus live state, the shape both list and get return
+    pub(super) fn server_json(&self, server: &Server) -> Value {
+        let handle = self.allocated_servers.get(&server.name);
+        let background = handle.is_none().then(|| detached::read(&server.name)).flatten();
+        let waiting_for_ready = server
+            .triggers
+            .iter()
+            .any(|trigger| trigger.action == TriggerAction::MarkReady);
+        let state = match (handle, &background) {
+            (Some(handle), _) if waiting_for_ready && !handle.ready => "starting",
+            (Some(_), _) | (None, Some(_)) => "running",
+            (None, None) => "stopped",
+        };
+        let pid = match (handle, &background) {
+            (Some(handle), _) => handle.pid(),
+            (None, Some(running)) => running.server_pid,
+            (None, None) => None,
+        };
+        let started_at = match (handle, &background) {
+            (Some(handle), _) => Some(handle.started_at.to_rfc3339()),
+            (None, Some(running)) => Some(running.started_at.to_rfc3339()),
+            (None, None) => None,
+        };
+
+        json!({
+            "id": server.id,
+            "name": server.name,
+            "state": state,
+            "background": background.is_some(),
+            "pid": pid,
+            "started_at": started_at,
+            "ready": handle.is_some_and(|handle| handle.ready),
+            "restarts": self.restarts.get(&server.name).copied().unwrap_or(0),
+            "last_exit_code": self.last_exit.get(&server.name),
+            "definition": ServerDef::from_server(server),
+        })
+    }
+
+    // Usage is only sampled for servers this TUI runs, background ones report state and uptime
+    fn metrics_json(&self) -> Value {
+        let servers: Vec<Value> = self
+            .available_servers
+            .iter()
+            .map(|server| {
+                let status = self.server_json(server);
+                let uptime = status["started_at"]
+                    .as_str()
+                    .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                    .map(|at| (Local::now() - at.with_timezone(&Local)).num_milliseconds() as f64 / 1000.0);
+                let usage = self.usage.get(&server.name);
+                let lines = self.lines_logged.get(&server.name);
+                json!({
+                    "name": server.name,
+                    "state": status["state"],
+                    "uptime_secs": uptime,
+                    "restarts": status["restarts"],
+                    "cpu_percent": usage.and_then(|usage| usage.cpu.back()),
+                    "rss_bytes": usage.and_then(|usage| usage.rss_kb.back()).map(|kb| kb * 1024),
+                    "log_lines": lines.map_or(0, |lines| lines.total),
+                    "log_lines_per_second": lines.map_or(0.0, |lines| lines.per_second),
+                    "last_exit_code": status["last_exit_code"],
+                })
+            })
+            .collect();
+
+        json!({
+            "uptime_secs": (Local::now() - self.started_at).num_milliseconds() as f64 / 1000.0,
+            "servers_running": servers.iter().filter(|server| server["state"] != "stopped").count(),
+            "stream_clients": self.subscribers.len(),
+            "log_lines": self.lines_logged.values().map(|lines| lines.total).sum::<u64>(),
+            "servers": servers,
+        })
+    }
+}
+
+fn background_error(name: &str, supervisor_pid: u32) -> ControlError {
+    ControlError::new(
+        ErrorKind::Conflict,
+        format!(
+            "{} runs in the background (supervisor pid {}), stop it with `stop {}`",
+            name, supervisor_pid, name
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::db;
+
+    #[test]
+    fn refusals_come_before_anything_about_the_server() {
+        let mut app = App::new(db::connect_db(":memory:").unwrap());
+        let viewer = Identity { role: Role::Viewer, ..Identity::local() };
+        let known = app.available_servers[0].name.clone();
+        for name in [known, "No Such Server".to_string()] {
+            let error = app.run_control(&viewer, ControlCommand::Delete(name)).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::Forbidden);
+        }
+        let error = app.run_control(&viewer, ControlCommand::Start("No Such Server".to_string())).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Forbidden);
+        assert!(!app.logs.iter().any(|line| line.contents.starts_with("Remote request")));
+        assert_eq!(db::load_audit(&app.db, None, None, 10).unwrap().len(), 3);
+    }
+}
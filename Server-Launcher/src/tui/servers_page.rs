@@ -0,0 +1,600 @@
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, Paragraph, Sparkline, Wrap},
+};
+use regex::Regex;
+use std::collections::VecDeque;
+
+use super::form::ServerForm;
+use super::mouse::{self, Clicks};
+use super::{wrap_index, App, Page, View};
+use crate::classify::LogLevel;
+use crate::hoststats;
+use crate::keymap::{Action, Hint};
+use crate::servers::{LogStream, ServerMessage};
+use crate::triggers::TriggerAction;
+
+// Share of the width the server list starts with, and how far dragging the divider can move it
+const LIST_PERCENT: u16 = 30;
+const LIST_PERCENT_RANGE: (u16, u16) = (10, 80);
+// Log lines one notch of the mouse wheel scrolls
+const WHEEL_LINES: isize = 3;
+
+#[derive(PartialEq)]
+enum InputMode {
+    Normal,
+    Search, // typing into the `/` prompt
+}
+
+// Server list on the left, the shared log stream on the right
+pub struct ServersPage {
+    input_mode: InputMode,
+    log_view_height: usize, // inner height of the log panel as last drawn
+    list_percent: u16,      // width of the left column, moved by dragging the divider
+    dragging: bool,         // the divider is held down
+    // Where the parts were last drawn, for the mouse
+    area: Rect,
+    list_area: Rect,
+    log_area: Rect,
+    clicks: Clicks,
+}
+
+impl ServersPage {
+    pub fn new() -> ServersPage {
+        ServersPage {
+            input_mode: InputMode::Normal,
+            log_view_height: 0,
+            list_percent: LIST_PERCENT,
+            dragging: false,
+            area: Rect::default(),
+            list_area: Rect::default(),
+            log_area: Rect::default(),
+            clicks: Clicks::default(),
+        }
+    }
+
+    // The border columns on either side of the split
+    fn on_divider(&self, mouse: &MouseEvent) -> bool {
+        let divider = Rect::new(self.log_area.x.saturating_sub(1), self.area.y, 2, self.area.height);
+        mouse::contains(divider, mouse)
+    }
+
+    // The server drawn on the clicked row of the list, if any
+    fn server_at(&self, app: &App, mouse: &MouseEvent) -> Option<usize> {
+        let inner = Rect {
+            x: self.list_area.x + 1,
+            y: self.list_area.y + 1,
+            width: self.list_area.width.saturating_sub(2),
+            height: self.list_area.height.saturating_sub(2),
+        };
+        if !mouse::contains(inner, mouse) {
+            return None;
+        }
+        let index = (mouse.row - inner.y) as usize;
+        (index < app.available_servers.len()).then_some(index)
+    }
+
+    fn handle_search_input(&mut self, app: &App, view: &mut View, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                view.search.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Enter => {
+                self.input_mode = InputMode::Normal;
+                view.jump_to_match(app, false, self.log_view_height);
+            }
+            KeyCode::Backspace => {
+                view.search.query.pop();
+                view.search.compile();
+            }
+            KeyCode::Char(c) => {
+                view.search.query.push(c);
+                view.search.compile();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Page for ServersPage {
+    fn title(&self) -> &'static str {
+        "Servers"
+    }
+
+    fn captures_input(&self) -> bool {
+        self.input_mode == InputMode::Search
+    }
+
+    fn handle_key(&mut self, app: &mut App, view: &mut View, key: KeyEvent) -> bool {
+        if self.input_mode == InputMode::Search {
+            self.handle_search_input(app, view, key);
+            return true;
+        }
+        let has_servers = !app.available_servers.is_empty();
+        match app.keymap.action(&key) {
+            // Quit backs out of an active search first
+            Some(Action::Quit) if !view.search.query.is_empty() => view.search.clear(),
+            Some(Action::Down) if has_servers => {
+                view.selected_server =
+                    wrap_index(view.selected_server, app.available_servers.len() - 1, 1);
+            }
+            Some(Action::Up) if has_servers => {
+                view.selected_server =
+                    wrap_index(view.selected_server, app.available_servers.len() - 1, -1);
+            }
+            Some(Action::Launch) if has_servers => {
+                let name = app.available_servers[view.selected_server].name.clone();
+                let _ = app.launch_server(&view.identity, &name);
+            }
+            Some(Action::Kill) if has_servers => {
+                let name = app.available_servers[view.selected_server].name.clone();
+                let _ = app.kill_server(&view.identity, &name);
+            }
+            Some(Action::ClearLogs) => view.clear_logs(app),
+            Some(Action::Search) => {
+                self.input_mode = InputMode::Search;
+            }
+            Some(Action::NextMatch) => view.jump_to_match(app, true, self.log_view_height),
+            Some(Action::PrevMatch) => view.jump_to_match(app, false, self.log_view_height),
+            Some(Action::FilterMatches) => {
+                view.search.filter = !view.search.filter;
+                view.log_scroll = 0;
+            }
+            Some(Action::CaseSensitive) => {
+                view.search.case_sensitive = !view.search.case_sensitive;
+                view.search.compile();
+            }
+            Some(Action::SelectedOnly) => {
+                view.search.selected_only = !view.search.selected_only;
+                view.search.current = None;
+            }
+            Some(Action::ToggleError) => view.toggle_level(LogLevel::Error),
+            Some(Action::ToggleWarn) => view.toggle_level(LogLevel::Warn),
+            Some(Action::ToggleInfo) => view.toggle_level(LogLevel::Info),
+            Some(Action::ToggleDebug) => view.toggle_level(LogLevel::Debug),
+            Some(Action::ToggleChat) => view.toggle_level(LogLevel::Chat),
+            Some(Action::ToggleStdout) => view.toggle_stream(LogStream::Stdout),
+            Some(Action::ToggleStderr) => view.toggle_stream(LogStream::Stderr),
+            Some(Action::ToggleLauncher) => view.toggle_stream(LogStream::Launcher),
+            Some(Action::ShowAllLogs) => view.clear_filters(),
+            Some(Action::Timestamps) => view.cycle_timestamps(app),
+            Some(Action::ExportLogs) => {
+                let _ = app.export_logs(&view.identity, &view.visible_logs(app));
+            }
+            Some(Action::PageUp) => {
+                view.scroll_logs(app, self.log_view_height as isize / 2, self.log_view_height)
+            }
+            Some(Action::PageDown) => {
+                view.scroll_logs(app, -(self.log_view_height as isize / 2), self.log_view_height)
+            }
+            Some(Action::Bottom) => view.log_scroll = 0,
+            Some(Action::AddServer) => {
+                view.form = Some(ServerForm::new());
+            }
+            Some(Action::EditServer) if has_servers => {
+                let server = &app.available_servers[view.selected_server];
+                let running = app.allocated_servers.contains_key(&server.name);
+                view.form = Some(ServerForm::edit(server, running));
+            }
+            Some(Action::DeleteServer) if has_servers => {
+                view.confirm_delete(app);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn handle_mouse(&mut self, app: &mut App, view: &mut View, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) if self.on_divider(&mouse) => self.dragging = true,
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging => {
+                let (min, max) = LIST_PERCENT_RANGE;
+                let column = mouse.column.saturating_sub(self.area.x) as u32;
+                let percent = (column * 100 + self.area.width as u32 / 2) / self.area.width.max(1) as u32;
+                self.list_percent = (percent as u16).clamp(min, max);
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging = false,
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(index) = self.server_at(app, &mouse) else {
+                    return;
+                };
+                view.selected_server = index;
+                if self.clicks.double(index) {
+                    let name = app.available_servers[index].name.clone();
+                    let _ = app.launch_server(&view.identity, &name);
+                }
+            }
+            MouseEventKind::ScrollUp if mouse::contains(self.log_area, &mouse) => {
+                view.scroll_logs(app, WHEEL_LINES, self.log_view_height)
+            }
+            MouseEventKind::ScrollDown if mouse::contains(self.log_area, &mouse) => {
+                view.scroll_logs(app, -WHEEL_LINES, self.log_view_height)
+            }
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown
+                if mouse::contains(self.list_area, &mouse) && !app.available_servers.is_empty() =>
+            {
+                let delta = if mouse.kind == MouseEventKind::ScrollUp { -1 } else { 1 };
+                view.selected_server =
+                    wrap_index(view.selected_server, app.available_servers.len() - 1, delta);
+            }
+            _ => {}
+        }
+    }
+
+    fn controls(&self) -> &'static [&'static [Hint]] {
+        &[
+            &[
+                Hint::Actions(&[Action::Down, Action::Up], "Navigate Servers"),
+                Hint::Actions(&[Action::Launch], "Launch/Select"),
+                Hint::Actions(&[Action::AddServer], "Add Server"),
+                Hint::Actions(&[Action::EditServer], "Edit"),
+                Hint::Actions(&[Action::DeleteServer], "Delete"),
+            ],
+            &[
+                Hint::Actions(&[Action::Kill], "Kill Server"),
+                Hint::Actions(&[Action::ClearLogs], "Clear Logs"),
+                Hint::Actions(&[Action::PageUp, Action::PageDown, Action::Bottom], "Scroll Logs"),
+                Hint::Fixed("Wheel", "Scroll"),
+                Hint::Fixed("Click", "Select"),
+                Hint::Fixed("Double Click", "Launch"),
+            ],
+            &[
+                Hint::Actions(&[Action::Search], "Search"),
+                Hint::Actions(&[Action::NextMatch, Action::PrevMatch], "Next/Prev Match"),
+                Hint::Actions(&[Action::FilterMatches], "Filter"),
+                Hint::Actions(&[Action::CaseSensitive], "Case"),
+                Hint::Actions(&[Action::SelectedOnly], "Selected Server Only"),
+                Hint::Actions(&[Action::Timestamps], "Timestamps"),
+                Hint::Actions(&[Action::ExportLogs], "Export"),
+            ],
+            &[
+                Hint::Actions(
+                    &[
+                        Action::ToggleError,
+                        Action::ToggleWarn,
+                        Action::ToggleInfo,
+                        Action::ToggleDebug,
+                        Action::ToggleChat,
+                    ],
+                    "Toggle ERROR/WARN/INFO/DEBUG/CHAT",
+                ),
+                Hint::Actions(
+                    &[Action::ToggleStdout, Action::ToggleStderr, Action::ToggleLauncher],
+                    "Toggle stdout/stderr/launcher",
+                ),
+                Hint::Actions(&[Action::ShowAllLogs], "Show All"),
+            ],
+        ]
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, app: &App, view: &View) {
+        // Horizontal layout for the content area (servers and logs)
+        let content_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Percentage(self.list_percent),
+                    Constraint::Percentage(100 - self.list_percent),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let left_split_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+            .split(content_chunks[0]);
+
+        // Left Panel: Server List
+        let server_items: Vec<ListItem> = app
+            .available_servers
+            .iter()
+            .enumerate()
+            .map(|(i, server)| {
+                let line = Line::from(Span::styled(
+                    if i == view.selected_server {
+                        format!("> {}", server.name)
+                    } else {
+                        server.name.to_string()
+                    },
+                    server_list_style_builder(i, app, view),
+                ));
+                ListItem::new(line)
+            })
+            .collect();
+
+        let server_list = List::new(server_items)
+            .block(
+                Block::default()
+                    .title("Servers")
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(Color::Indexed(33))),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+            .highlight_symbol("> ");
+
+        frame.render_widget(server_list, left_split_chunks[0]);
+        self.area = area;
+        self.list_area = left_split_chunks[0];
+        self.log_area = content_chunks[1];
+
+        render_usage(frame, left_split_chunks[1], app, view);
+
+        // Right Panel: Log Output
+        let log_panel_frame_rect = content_chunks[1];
+
+        // Calculate the inner height of the log panel (area inside borders)
+        let inner_log_area_height = log_panel_frame_rect.height.saturating_sub(2) as usize;
+        self.log_view_height = inner_log_area_height;
+
+        // Only the slice that fits is rendered, `log_scroll` moves it up from the newest line
+        let visible_logs = view.visible_logs(app);
+        let end = visible_logs.len().saturating_sub(view.log_scroll);
+        let start = end.saturating_sub(inner_log_area_height);
+
+        let log_text: Vec<Line> = visible_logs[start..end]
+            .iter()
+            .map(|&i| -> Line {
+                let log = &app.logs[i];
+                let highlight = if view.is_match(app, log) {
+                    view.search.pattern.as_ref()
+                } else {
+                    None
+                };
+                output_log_style_builder(
+                    log,
+                    app.timestamp_label(log, view.timestamp_format(app)),
+                    highlight,
+                    view.search.current == Some(i),
+                )
+            })
+            .collect();
+
+        let mut log_block = Block::default()
+            .title(log_panel_title(app, view))
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(Color::Indexed(208)));
+        if self.input_mode == InputMode::Search {
+            log_block = log_block.title_bottom(format!("/{}_", view.search.query));
+        }
+        if let Some(error) = &view.search.error {
+            log_block =
+                log_block.title_bottom(Span::styled(error.as_str(), Style::new().fg(Color::Red)));
+        }
+
+        let right_panel_content = Paragraph::new(log_text)
+            .block(log_block)
+            .wrap(Wrap { trim: true });
+        frame.render_widget(right_panel_content, log_panel_frame_rect);
+    }
+}
+
+// CPU and memory of the selected server in the small bottom left box
+fn render_usage(frame: &mut Frame, area: Rect, app: &App, view: &View) {
+    let server = view.selected(app);
+    let block = Block::default()
+        .title(server.map_or("Usage".to_string(), |server| format!("Usage: {}", server.name)))
+        .borders(Borders::ALL)
+        .border_style(Style::new().fg(Color::Indexed(50)));
+    let history = server.and_then(|server| app.usage.get(&server.name));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let Some(history) = history.filter(|history| !history.rss_kb.is_empty()) else {
+        frame.render_widget(
+            Paragraph::new("not running").style(Style::new().fg(Color::DarkGray)),
+            inner,
+        );
+        return;
+    };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+        ])
+        .split(inner);
+
+    // Only as much history as fits, newest on the right
+    let tail = |samples: &VecDeque<u64>| -> Vec<u64> {
+        let skip = samples.len().saturating_sub(inner.width as usize);
+        samples.iter().skip(skip).copied().collect()
+    };
+
+    let cpu = history.cpu.back().copied().unwrap_or(0);
+    frame.render_widget(
+        Paragraph::new(format!("CPU {}%  threads {}", cpu, history.threads)),
+        rows[0],
+    );
+    frame.render_widget(
+        Sparkline::default()
+            .data(&tail(&history.cpu))
+            .style(Style::new().fg(Color::Indexed(200))),
+        rows[1],
+    );
+
+    let rss = history.rss_kb.back().copied().unwrap_or(0);
+    frame.render_widget(Paragraph::new(format!("RSS {}", hoststats::format_bytes(rss as f64 * 1024.0))), rows[2]);
+    frame.render_widget(
+        Sparkline::default()
+            .data(&tail(&history.rss_kb))
+            .style(Style::new().fg(Color::Indexed(50))),
+        rows[3],
+    );
+}
+
+fn server_list_style_builder(index: usize, app: &App, view: &View) -> Style {
+    let server = &app.available_servers[index];
+    let mut style = Style::new();
+
+    if let Some(handle) = app.allocated_servers.get(&server.name) {
+        // Servers with a `ready` trigger show as starting until it fires
+        let waiting_for_ready = server
+            .triggers
+            .iter()
+            .any(|trigger| trigger.action == TriggerAction::MarkReady);
+        if waiting_for_ready && !handle.ready {
+            style = style.fg(Color::Yellow);
+        } else {
+            style = style.fg(Color::Green);
+        }
+    } else {
+        style = style.fg(Color::Red);
+    }
+
+    if index == view.selected_server {
+        style = style.patch(
+            Style::default()
+                .add_modifier(Modifier::UNDERLINED)
+                .add_modifier(Modifier::BOLD),
+        );
+    }
+
+    style
+}
+
+fn log_panel_title(app: &App, view: &View) -> String {
+    let mut title = "Log Stream".to_string();
+    let mut hidden: Vec<&str> = LogLevel::ALL
+        .into_iter()
+        .filter(|level| view.hidden_levels.contains(level))
+        .map(LogLevel::label)
+        .collect();
+    hidden.extend(
+        LogStream::ALL
+            .into_iter()
+            .filter(|stream| view.hidden_streams.contains(stream))
+            .map(LogStream::label),
+    );
+    if !hidden.is_empty() {
+        title.push_str(&format!(" | hiding {}", hidden.join(",")));
+    }
+    if view.search.pattern.is_some() {
+        let matches = view.match_indices(app);
+        let position = view
+            .search
+            .current
+            .and_then(|current| matches.iter().position(|&i| i == current))
+            .map_or("-".to_string(), |p| (p + 1).to_string());
+        title.push_str(&format!(
+            " | /{}/ {}/{}",
+            view.search.query,
+            position,
+            matches.len()
+        ));
+        if view.search.filter {
+            title.push_str(" [filter]");
+        }
+        if view.search.case_sensitive {
+            title.push_str(" [Aa]");
+        }
+        if let Some(name) = view.search_scope(app) {
+            title.push_str(&format!(" [{}]", name));
+        }
+    }
+    title
+}
+
+// Split `text` into spans so every regex match gets its own highlighted span
+fn highlight_spans<'a>(
+    text: &'a str,
+    style: Style,
+    pattern: Option<&Regex>,
+    is_current: bool,
+) -> Vec<Span<'a>> {
+    let Some(pattern) = pattern else {
+        return vec![Span::styled(text, style)];
+    };
+
+    let match_style = if is_current {
+        Style::new()
+            .fg(Color::Black)
+            .bg(Color::LightYellow)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::new().fg(Color::Black).bg(Color::Yellow)
+    };
+
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for found in pattern.find_iter(text) {
+        if found.is_empty() {
+            continue;
+        }
+        if found.start() > last {
+            spans.push(Span::styled(&text[last..found.start()], style));
+        }
+        spans.push(Span::styled(found.as_str(), match_style));
+        last = found.end();
+    }
+    if last < text.len() {
+        spans.push(Span::styled(&text[last..], style));
+    }
+    spans
+}
+
+pub fn output_log_style_builder<'a>(
+    message: &'a ServerMessage,
+    timestamp: Option<String>,
+    search: Option<&Regex>,
+    is_current: bool,
+) -> Line<'a> {
+    let mut spans = Vec::new();
+    if let Some(timestamp) = timestamp {
+        spans.push(Span::styled(
+            format!("{} ", timestamp),
+            Style::new().fg(Color::DarkGray),
+        ));
+    }
+
+    let name_style = Style::new().fg(Color::Blue); // make a server attrbute for this, prob in rgb
+    match message.stream {
+        LogStream::Launcher => {}
+        LogStream::Stdout => {
+            spans.push(Span::from(format!("[{}] ", message.name.as_str())).style(name_style));
+        }
+        LogStream::Stderr => {
+            spans.push(Span::from(format!("[{}] ", message.name.as_str())).style(name_style));
+            spans.push(Span::styled("stderr ", Style::new().fg(Color::Magenta)));
+        }
+    }
+
+    let style = level_style(message);
+    spans.extend(highlight_spans(&message.contents, style, search, is_current));
+
+    Line::from(spans)
+}
+
+fn level_style(message: &ServerMessage) -> Style {
+    match message.level {
+        Some(LogLevel::Error) => Style::new().fg(Color::Red).add_modifier(Modifier::BOLD),
+        Some(LogLevel::Warn) => Style::new().fg(Color::Yellow),
+        Some(LogLevel::Debug) => Style::new().fg(Color::DarkGray),
+        Some(LogLevel::Chat) => Style::new().fg(Color::Green),
+        Some(LogLevel::Info) | None => {
+            if message.stream == LogStream::Launcher {
+                Style::new()
+            } else {
+                Style::new().fg(Color::Blue)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_splits_around_every_match() {
+        let pattern = Regex::new("o").unwrap();
+        let spans = highlight_spans("foo bar", Style::new(), Some(&pattern), false);
+        let pieces: Vec<&str> = spans.iter().map(|span| span.content.as_ref()).collect();
+        assert_eq!(pieces, vec!["f", "o", "o", " bar"]);
+    }
+}
@@ -0,0 +1,159 @@
+use crossterm::event::KeyEvent;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph},
+};
+
+use super::{wrap_index, App, Page, View};
+use crate::auth::{Identity, Permission};
+use crate::keymap::{Action, Hint, KeyPreset};
+use crate::settings::{QuitPolicy, TimestampFormat, LOG_BUFFER_SIZES};
+
+#[derive(Clone, Copy)]
+enum Setting {
+    TimestampFormat,
+    PersistLogs,
+    LogBufferLines,
+    QuitPolicy,
+    KeyPreset,
+}
+
+impl Setting {
+    const ALL: [Setting; 5] = [
+        Setting::TimestampFormat,
+        Setting::PersistLogs,
+        Setting::LogBufferLines,
+        Setting::QuitPolicy,
+        Setting::KeyPreset,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Setting::TimestampFormat => "Log timestamps",
+            Setting::PersistLogs => "Write logs to disk",
+            Setting::LogBufferLines => "Log lines kept",
+            Setting::QuitPolicy => "Servers on quit",
+            Setting::KeyPreset => "Key preset",
+        }
+    }
+}
+
+// Launcher preferences, every change is saved right away
+pub struct SettingsPage {
+    selected: usize,
+}
+
+impl SettingsPage {
+    pub fn new() -> SettingsPage {
+        SettingsPage { selected: 0 }
+    }
+}
+
+fn value(app: &App, setting: Setting) -> String {
+    match setting {
+        Setting::TimestampFormat => app.settings.timestamp_format.name().to_string(),
+        Setting::PersistLogs => if app.settings.persist_logs { "on" } else { "off" }.to_string(),
+        Setting::LogBufferLines => app.settings.log_buffer_lines.to_string(),
+        Setting::QuitPolicy => app.settings.quit_policy.name().to_string(),
+        Setting::KeyPreset => app.settings.key_preset.name().to_string(),
+    }
+}
+
+// Step through the choices of a setting, wrapping around at either end.
+// Settings are the launcher's, not the terminal's, so only admins may change them.
+fn cycle(app: &mut App, who: &Identity, setting: Setting, delta: isize) {
+    if app.authorize(who, Permission::Edit, None).is_err() {
+        return;
+    }
+    match setting {
+        Setting::TimestampFormat => {
+            let all = TimestampFormat::ALL;
+            let index = all
+                .iter()
+                .position(|&format| format == app.settings.timestamp_format)
+                .unwrap_or(0);
+            app.settings.timestamp_format = all[wrap_index(index, all.len() - 1, delta)];
+        }
+        Setting::PersistLogs => app.settings.persist_logs = !app.settings.persist_logs,
+        Setting::LogBufferLines => {
+            let index = LOG_BUFFER_SIZES
+                .iter()
+                .position(|&size| size == app.settings.log_buffer_lines)
+                .unwrap_or(1);
+            app.settings.log_buffer_lines =
+                LOG_BUFFER_SIZES[wrap_index(index, LOG_BUFFER_SIZES.len() - 1, delta)];
+        }
+        Setting::QuitPolicy => {
+            let all = QuitPolicy::ALL;
+            let index = all
+                .iter()
+                .position(|&policy| policy == app.settings.quit_policy)
+                .unwrap_or(0);
+            app.settings.quit_policy = all[wrap_index(index, all.len() - 1, delta)];
+        }
+        Setting::KeyPreset => {
+            let all = KeyPreset::ALL;
+            let index = all
+                .iter()
+                .position(|&preset| preset == app.settings.key_preset)
+                .unwrap_or(0);
+            app.settings.key_preset = all[wrap_index(index, all.len() - 1, delta)];
+        }
+    }
+    app.save_settings();
+}
+
+impl Page for SettingsPage {
+    fn title(&self) -> &'static str {
+        "Settings"
+    }
+
+    fn handle_key(&mut self, app: &mut App, view: &mut View, key: KeyEvent) -> bool {
+        let setting = Setting::ALL[self.selected];
+        match app.keymap.action(&key) {
+            Some(Action::Down) => {
+                self.selected = wrap_index(self.selected, Setting::ALL.len() - 1, 1);
+            }
+            Some(Action::Up) => {
+                self.selected = wrap_index(self.selected, Setting::ALL.len() - 1, -1);
+            }
+            Some(Action::Launch | Action::Right) => cycle(app, &view.identity, setting, 1),
+            Some(Action::Left) => cycle(app, &view.identity, setting, -1),
+            _ => return false,
+        }
+        true
+    }
+
+    fn controls(&self) -> &'static [&'static [Hint]] {
+        &[&[
+            Hint::Actions(&[Action::Down, Action::Up], "Select"),
+            Hint::Actions(&[Action::Launch, Action::Right, Action::Left], "Change"),
+        ]]
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, app: &App, _view: &View) {
+        let lines: Vec<Line> = Setting::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, &setting)| {
+                let style = if i == self.selected {
+                    Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::new()
+                };
+                Line::from(vec![
+                    Span::styled(format!("{:<22}", setting.label()), style),
+                    Span::raw(format!("< {} >", value(app, setting))),
+                ])
+            })
+            .collect();
+
+        let settings = Paragraph::new(lines).block(
+            Block::default()
+                .title("Settings")
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Color::Indexed(33))),
+        );
+        frame.render_widget(settings, area);
+    }
+}
@@ -0,0 +1,198 @@
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Local;
+use crossterm::{
+    cursor::Show,
+    event::DisableMouseCapture,
+    execute,
+    terminal::{disable_raw_mode, LeaveAlternateScreen},
+};
+
+use super::App;
+use crate::servers::{MessageType, ServerMessage};
+
+// Leaving the terminal usable however the launcher goes down. After a panic on the UI or core
+// thread or one of the usual termination signals the quit policy still runs for the servers.
+// Any other thread serves a single session, connection or request, a panic there only ends
+// that one and is noted in the log. Every panic leaves a report in CRASH_DIR.
+
+pub const CRASH_DIR: &str = "crashes";
+
+// How many of the newest log lines go into a crash report
+const REPORT_LOG_LINES: usize = 100;
+
+// How soon a signal is acted on
+const SIGNAL_POLL: Duration = Duration::from_millis(50);
+
+#[cfg(unix)]
+const SIGNALS: [libc::c_int; 3] = [libc::SIGTERM, libc::SIGHUP, libc::SIGINT];
+// There is no SIGHUP on Windows
+#[cfg(not(unix))]
+const SIGNALS: [libc::c_int; 2] = [libc::SIGTERM, libc::SIGINT];
+
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+static PANICKED: AtomicBool = AtomicBool::new(false);
+// A report still missing the state because the lock was held when it was written
+static UNFINISHED_REPORT: Mutex<Option<PathBuf>> = Mutex::new(None);
+// Panics that only took down their own thread, waiting to be shown in the log
+static CONTAINED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // Set on the threads the launcher can't go on without
+    static CRITICAL: Cell<bool> = const { Cell::new(false) };
+}
+
+// A panic on the calling thread brings the launcher down from now on
+pub fn mark_critical() {
+    CRITICAL.with(|critical| critical.set(true));
+}
+
+// Best effort, after a SIGHUP there is no terminal left to restore
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, Show);
+}
+
+extern "C" fn store_signal(signal: libc::c_int) {
+    SIGNAL.store(signal, Ordering::Relaxed);
+}
+
+// Runs `quit` with the signal once SIGTERM, SIGHUP or SIGINT comes in. SIGHUP is what a closed
+// SSH window sends. It runs on a thread of its own because the UI thread can't be counted on:
+// on a terminal that hung up crossterm keeps polling it and never returns to the loop.
+pub fn on_signal(quit: impl FnOnce(i32) + Send + 'static) {
+    for signal in SIGNALS {
+        unsafe {
+            libc::signal(signal, store_signal as *const () as libc::sighandler_t);
+        }
+    }
+    thread::spawn(move || loop {
+        match SIGNAL.load(Ordering::Relaxed) {
+            0 => thread::sleep(SIGNAL_POLL),
+            signal => return quit(signal),
+        }
+    });
+}
+
+pub fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGTERM => "SIGTERM",
+        #[cfg(unix)]
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        _ => "a signal",
+    }
+}
+
+// The core thread may have panicked, the UI loop stops when it sees this
+pub fn panicked() -> bool {
+    PANICKED.load(Ordering::Relaxed)
+}
+
+pub fn install_panic_hook(app: Arc<Mutex<App>>) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let critical = CRITICAL.with(Cell::get);
+        if !critical {
+            // Nothing is printed, the terminal still belongs to the UI
+            let thread = thread::current().name().map_or("A thread".to_string(), |name| format!("Thread {}", name));
+            let note = match write_report(info, &app, critical) {
+                Ok(path) => format!("{} panicked and stopped, report written to {}", thread, path.display()),
+                Err(e) => format!("{} panicked and stopped, the report could not be written: {}", thread, e),
+            };
+            CONTAINED.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(note);
+            return;
+        }
+        PANICKED.store(true, Ordering::Relaxed);
+        restore_terminal();
+        // Printing must not fail here, a panic inside the hook aborts
+        let mut stderr = io::stderr();
+        let _ = match write_report(info, &app, critical) {
+            Ok(path) => writeln!(stderr, "The launcher crashed, report written to {}", path.display()),
+            Err(e) => writeln!(stderr, "The launcher crashed and the report could not be written: {}", e),
+        };
+        previous(info);
+    }));
+}
+
+fn write_report(info: &PanicHookInfo, app: &Mutex<App>, critical: bool) -> io::Result<PathBuf> {
+    let now = Local::now();
+    let mut report = String::new();
+    let _ = writeln!(report, "Server-Launcher {} crashed at {}", env!("CARGO_PKG_VERSION"), now.to_rfc3339());
+    let _ = writeln!(report, "thread: {}", thread::current().name().unwrap_or("unnamed"));
+    if !critical {
+        let _ = writeln!(report, "only this thread stopped, the launcher kept running");
+    }
+    let _ = writeln!(report, "{}", info);
+    let _ = writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture());
+
+    // The panicking thread may hold the lock itself, waiting for it would hang
+    let locked = match app.try_lock() {
+        Ok(app) => {
+            describe(&app, &mut report);
+            false
+        }
+        Err(TryLockError::Poisoned(poisoned)) => {
+            describe(&poisoned.into_inner(), &mut report);
+            false
+        }
+        Err(TryLockError::WouldBlock) => true,
+    };
+
+    fs::create_dir_all(CRASH_DIR)?;
+    let path = Path::new(CRASH_DIR).join(format!("crash-{}.txt", now.format("%Y%m%d-%H%M%S")));
+    fs::write(&path, report)?;
+    if locked {
+        *UNFINISHED_REPORT.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(path.clone());
+    }
+    Ok(path)
+}
+
+// Adds the state to a report that had to leave it out, once the lock is free again
+pub fn finish_report(app: &App) {
+    let Some(path) = UNFINISHED_REPORT.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() else {
+        return;
+    };
+    let mut report = String::new();
+    describe(app, &mut report);
+    if let Ok(mut file) = fs::OpenOptions::new().append(true).open(path) {
+        let _ = file.write_all(report.as_bytes());
+    }
+}
+
+// Puts the panics that didn't bring the launcher down into its log
+pub fn log_contained(app: &mut App) {
+    let notes = std::mem::take(&mut *CONTAINED.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    if notes.is_empty() {
+        return;
+    }
+    for note in notes {
+        app.push_log(ServerMessage::new("", note, MessageType::Err));
+    }
+    finish_report(app);
+}
+
+// Which servers were up and what they said last
+fn describe(app: &App, report: &mut String) {
+    let _ = writeln!(report, "\nrunning servers:");
+    for (name, handle) in app.allocated_servers.iter().filter(|(_, handle)| handle.running) {
+        let pid = handle.pid().map_or("-".to_string(), |pid| pid.to_string());
+        let _ = writeln!(report, "  {} pid {} since {}", name, pid, handle.started_at.to_rfc3339());
+    }
+    let _ = writeln!(report, "\nlast {} log lines:", REPORT_LOG_LINES);
+    let skip = app.logs.len().saturating_sub(REPORT_LOG_LINES);
+    for message in app.logs.iter().skip(skip) {
+        let name = if message.name.is_empty() { "launcher" } else { message.name.as_str() };
+        let _ = writeln!(report, "  {} [{}] {}", message.timestamp.format("%H:%M:%S"), name, message.contents);
+    }
+}
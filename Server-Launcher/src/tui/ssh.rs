@@ -0,0 +1,319 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use russh::keys::ssh_key::{private::Ed25519Keypair, AuthorizedKeys, HashAlg, LineEnding};
+use russh::keys::{PrivateKey, PublicKey};
+use russh::server::{self, Auth, Msg, Session as SshSession};
+use russh::{Channel, ChannelId, CryptoVec, MethodKind, MethodSet, Pty};
+use tokio::runtime::{self, Runtime};
+use tokio::{task, time};
+
+use super::client::{self, Input, WRITE_TIMEOUT};
+use super::{lock, App};
+use crate::auth::{Identity, Source};
+use crate::db;
+
+// The TUI served over SSH. Every connection gets its own dashboard (see client.rs), so each person has their
+// own page, selection, scroll and filters over the servers everyone shares. Logins are by
+// public key only, checked against the authorized_keys file on every attempt so edits to it
+// apply without a restart. When the launcher has users the comment of a key's entry says whose
+// it is, and it only logs in under that name. Otherwise everyone who gets in is an admin like
+// the local terminal, named after their key's comment or fingerprint.
+
+pub const HOST_KEY_PATH: &str = "ssh_host_ed25519_key";
+
+pub fn spawn(address: &str, authorized_keys: &Path, app: Arc<Mutex<App>>) -> io::Result<()> {
+    let address: SocketAddr = address
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "use ip:port"))?;
+    let config = Arc::new(server::Config {
+        keys: vec![host_key(Path::new(HOST_KEY_PATH))?],
+        methods: MethodSet::from(&[MethodKind::PublicKey][..]),
+        auth_rejection_time: Duration::from_secs(1),
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        inactivity_timeout: None,
+        ..Default::default()
+    });
+
+    let runtime = runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("ssh")
+        .enable_io()
+        .enable_time()
+        .build()?;
+    // Bound here so a taken port shows up in the log instead of failing silently later
+    let listener = std::net::TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let listener = {
+        let _entered = runtime.enter();
+        tokio::net::TcpListener::from_std(listener)?
+    };
+
+    let authorized_keys = authorized_keys.to_path_buf();
+    thread::spawn(move || serve(runtime, listener, config, authorized_keys, app));
+    Ok(())
+}
+
+// Made on the first start and kept, so clients only have to trust it once
+fn host_key(path: &Path) -> io::Result<PrivateKey> {
+    if path.exists() {
+        let pem = fs::read_to_string(path)?;
+        return PrivateKey::from_openssh(pem).map_err(io::Error::other);
+    }
+    let mut seed = [0u8; 32];
+    rand::fill(&mut seed);
+    let key = PrivateKey::from(Ed25519Keypair::from_seed(&seed));
+    let pem = key.to_openssh(LineEnding::LF).map_err(io::Error::other)?;
+    // Created 0600 from the start, never readable by anyone else in between
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    file.write_all(pem.as_bytes())?;
+    Ok(key)
+}
+
+fn serve(
+    runtime: Runtime,
+    listener: tokio::net::TcpListener,
+    config: Arc<server::Config>,
+    authorized_keys: PathBuf,
+    app: Arc<Mutex<App>>,
+) {
+    runtime.block_on(async move {
+        loop {
+            let Ok((stream, peer)) = listener.accept().await else {
+                continue;
+            };
+            let client = Client {
+                app: app.clone(),
+                authorized_keys: authorized_keys.clone(),
+                runtime: runtime::Handle::current(),
+                peer,
+                identity: None,
+                size: (80, 24),
+                input: None,
+            };
+            let config = config.clone();
+            tokio::spawn(async move {
+                if let Ok(session) = server::run_stream(config, stream, client).await {
+                    let _ = session.await;
+                }
+            });
+        }
+    });
+}
+
+// One SSH connection
+struct Client {
+    app: Arc<Mutex<App>>,
+    authorized_keys: PathBuf,
+    runtime: runtime::Handle,
+    peer: SocketAddr,
+    identity: Option<Identity>, // set once the key is accepted
+    size: (u16, u16),
+    input: Option<Sender<Input>>, // the running session, if the shell was started
+}
+
+// The comments of the authorized_keys entries holding the key, None when it has none
+fn authorized(authorized_keys: &Path, key: &PublicKey) -> Option<Vec<String>> {
+    let contents = fs::read_to_string(authorized_keys).ok()?;
+    let comments: Vec<String> = AuthorizedKeys::new(&contents)
+        .flatten()
+        .filter(|entry| entry.public_key().key_data() == key.key_data())
+        .map(|entry| entry.public_key().comment().to_string())
+        .collect();
+    (!comments.is_empty()).then_some(comments)
+}
+
+// Reads the file and waits for the App, so it is run off the SSH runtime's threads
+fn identity(app: &Mutex<App>, authorized_keys: &Path, user: &str, key: &PublicKey) -> Option<Identity> {
+    let comments = authorized(authorized_keys, key)?;
+    let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+    let app = lock(app);
+    if !db::has_users(&app.db).ok()? {
+        // The login name is the client's choice, what the audit log shows comes from the key
+        let key_name = comments.into_iter().find(|comment| !comment.is_empty());
+        return Some(Identity {
+            user: key_name.unwrap_or_else(|| fingerprint.clone()),
+            source: Source::Ssh(fingerprint),
+            ..Identity::local()
+        });
+    }
+    // The login name is whatever the client asks for, only the key's owner may use it
+    if !comments.iter().any(|comment| comment == user) {
+        return None;
+    }
+    let user = db::find_user(&app.db, user).ok()??;
+    Some(user.identity(Source::Ssh(fingerprint)))
+}
+
+impl server::Handler for Client {
+    type Error = russh::Error;
+
+    async fn auth_publickey(&mut self, user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        let (app, authorized_keys) = (self.app.clone(), self.authorized_keys.clone());
+        let (user, key) = (user.to_string(), key.clone());
+        let checked = task::spawn_blocking(move || identity(&app, &authorized_keys, &user, &key)).await;
+        match checked.ok().flatten() {
+            Some(identity) => {
+                self.identity = Some(identity);
+                Ok(Auth::Accept)
+            }
+            None => Ok(Auth::reject()),
+        }
+    }
+
+    async fn channel_open_session(&mut self, _channel: Channel<Msg>, _session: &mut SshSession) -> Result<bool, Self::Error> {
+        // One dashboard per connection
+        Ok(self.input.is_none())
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _term: &str,
+        cols: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(Pty, u32)],
+        session: &mut SshSession,
+    ) -> Result<(), Self::Error> {
+        self.size = (cols as u16, rows as u16);
+        session.channel_success(channel)
+    }
+
+    async fn shell_request(&mut self, channel: ChannelId, session: &mut SshSession) -> Result<(), Self::Error> {
+        let Some(identity) = self.identity.clone() else {
+            return session.channel_failure(channel);
+        };
+        let (input, receiver) = mpsc::channel();
+        self.input = Some(input);
+        let writer = ChannelWriter {
+            handle: session.handle(),
+            channel,
+            runtime: self.runtime.clone(),
+            buffer: Vec::new(),
+        };
+        let (app, size, from) = (self.app.clone(), self.size, format!("ssh from {}", self.peer));
+        thread::spawn(move || {
+            client::run(&app, identity, &from, writer.clone(), size, &receiver);
+            writer.runtime.block_on(async move {
+                let _ = writer.handle.exit_status_request(channel, 0).await;
+                let _ = writer.handle.eof(channel).await;
+                let _ = writer.handle.close(channel).await;
+            });
+        });
+        session.channel_success(channel)
+    }
+
+    async fn data(&mut self, _channel: ChannelId, data: &[u8], _session: &mut SshSession) -> Result<(), Self::Error> {
+        if let Some(input) = &self.input {
+            let _ = input.send(Input::Data(data.to_vec()));
+        }
+        Ok(())
+    }
+
+    async fn window_change_request(
+        &mut self,
+        _channel: ChannelId,
+        cols: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _session: &mut SshSession,
+    ) -> Result<(), Self::Error> {
+        if let Some(input) = &self.input {
+            let _ = input.send(Input::Resize(cols as u16, rows as u16));
+        }
+        Ok(())
+    }
+
+    // Dropping the sender ends the session thread
+    async fn channel_eof(&mut self, _channel: ChannelId, _session: &mut SshSession) -> Result<(), Self::Error> {
+        self.input = None;
+        Ok(())
+    }
+
+    async fn channel_close(&mut self, _channel: ChannelId, _session: &mut SshSession) -> Result<(), Self::Error> {
+        self.input = None;
+        Ok(())
+    }
+}
+
+// Collects what ratatui writes and sends it as one channel message per flush
+#[derive(Clone)]
+struct ChannelWriter {
+    handle: server::Handle,
+    channel: ChannelId,
+    runtime: runtime::Handle,
+    buffer: Vec<u8>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let data = CryptoVec::from(std::mem::take(&mut self.buffer));
+        // Waits for the client's window to open, which a stalled client never does
+        match self.runtime.block_on(time::timeout(WRITE_TIMEOUT, self.handle.data(self.channel, data))) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(io::Error::new(io::ErrorKind::BrokenPipe, "the SSH client went away")),
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "the SSH client stopped reading")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    #[cfg(unix)]
+    #[test]
+    fn host_key_is_private_and_kept() {
+        let dir = std::env::temp_dir().join(format!("launcher-host-key-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("host_key");
+        let _ = fs::remove_file(&path);
+
+        let key = host_key(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(host_key(&path).unwrap().public_key(), key.public_key());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn without_users_logins_are_named_after_the_key() {
+        let key = PrivateKey::from(Ed25519Keypair::from_seed(&[7; 32]));
+        let path = std::env::temp_dir().join(format!("launcher-authorized-keys-{}", std::process::id()));
+        let line = key.public_key().to_openssh().unwrap();
+        fs::write(&path, format!("{} alice@laptop\n", line)).unwrap();
+        let app = Mutex::new(App::new(db::connect_db(":memory:").unwrap()));
+
+        let named = identity(&app, &path, "root", key.public_key()).unwrap();
+        assert_eq!(named.user, "alice@laptop");
+        // Without a comment the fingerprint stands in
+        fs::write(&path, format!("{}\n", line)).unwrap();
+        let unnamed = identity(&app, &path, "root", key.public_key()).unwrap();
+        assert_eq!(unnamed.user, key.public_key().fingerprint(HashAlg::Sha256).to_string());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashSet;
+
+use super::confirm::{ConfirmDialog, PendingAction};
+use super::form::ServerForm;
+use super::{App, LogSearch};
+use crate::auth::Identity;
+use crate::classify::LogLevel;
+use crate::db::Server;
+use crate::servers::{LogStream, ServerMessage};
+use crate::settings::{QuitPolicy, TimestampFormat};
+
+// What one terminal looks at. Every terminal on the launcher (the local one, each SSH
+// session) has its own, the servers and the log buffer in App are shared between them.
+pub struct View {
+    pub(super) identity: Identity, // what this terminal's keys act as
+    pub(super) selected_server: usize,
+    pub(super) log_scroll: usize, // lines scrolled up from the bottom, 0 follows the stream
+    pub(super) search: LogSearch,
+    pub(super) hidden_levels: HashSet<LogLevel>,
+    pub(super) hidden_streams: HashSet<LogStream>,
+    timestamps: Option<TimestampFormat>, // None follows the launcher's setting
+    pub(super) form: Option<ServerForm>, // add/edit server popup, takes all keys while open
+    pub(super) confirm: Option<ConfirmDialog>,
+    pub(super) quit: Option<QuitPolicy>, // set once quitting was confirmed
+    cleared_before: u64, // lines before this one were cleared away with C
+    seen_total: u64,     // App::logs_total when this view last caught up
+    seen_dropped: u64,   // App::logs_dropped likewise
+}
+
+impl View {
+    pub fn new(identity: Identity) -> View {
+        View {
+            identity,
+            selected_server: 0,
+            log_scroll: 0,
+            search: LogSearch::default(),
+            hidden_levels: HashSet::new(),
+            hidden_streams: HashSet::new(),
+            timestamps: None,
+            form: None,
+            confirm: None,
+            quit: None,
+            cleared_before: 0,
+            seen_total: 0,
+            seen_dropped: 0,
+        }
+    }
+
+    // Follows lines logged and dropped since the last call, holding the panel still while
+    // scrolled up. Call before drawing or handling keys.
+    pub(super) fn catch_up(&mut self, app: &App) {
+        let dropped = (app.logs_dropped - self.seen_dropped) as usize;
+        self.search.current = self.search.current.and_then(|i| i.checked_sub(dropped));
+        if self.log_scroll > 0 {
+            let added = (app.logs_total() - self.seen_total) as usize;
+            self.log_scroll += app
+                .logs
+                .range(app.logs.len().saturating_sub(added)..)
+                .filter(|message| self.is_visible(app, message))
+                .count();
+        }
+        self.seen_total = app.logs_total();
+        self.seen_dropped = app.logs_dropped;
+        // Another terminal may have deleted servers
+        self.selected_server = self
+            .selected_server
+            .min(app.available_servers.len().saturating_sub(1));
+    }
+
+    pub(super) fn selected<'a>(&self, app: &'a App) -> Option<&'a Server> {
+        app.available_servers.get(self.selected_server)
+    }
+
+    pub(super) fn confirm_delete(&mut self, app: &App) {
+        let Some(server) = self.selected(app) else {
+            return;
+        };
+        let mut lines = vec![format!("Delete server {}?", server.name)];
+        if app.allocated_servers.contains_key(&server.name) {
+            lines.push("It is running and will be stopped first.".to_string());
+        }
+        self.confirm = Some(ConfirmDialog {
+            title: "Delete Server".to_string(),
+            lines,
+            action: PendingAction::DeleteServer {
+                name: server.name.clone(),
+            },
+        });
+    }
+
+    // Lists what is still running before the launcher goes away
+    pub(super) fn confirm_quit(&mut self, app: &App) -> bool {
+        let running = app.running_servers();
+        let policy = app.settings.quit_policy;
+        if running.is_empty() {
+            self.quit = Some(policy);
+            return true;
+        }
+        let question = match policy {
+            QuitPolicy::Ask => "These servers are still running:",
+            QuitPolicy::Stop => "Quit and stop these servers?",
+            QuitPolicy::Leave => "Quit and leave these servers running in the background?",
+        };
+        let mut lines = vec![question.to_string()];
+        lines.extend(running.into_iter().map(|name| format!("  {}", name)));
+        self.confirm = Some(ConfirmDialog {
+            title: "Quit".to_string(),
+            lines,
+            action: PendingAction::Quit(policy),
+        });
+        false
+    }
+
+    pub(super) fn run_pending(&mut self, app: &mut App, action: PendingAction) {
+        match action {
+            PendingAction::DeleteServer { name } => {
+                let _ = app.delete_server(&self.identity, &name);
+            }
+            PendingAction::Quit(policy) => self.quit = Some(policy),
+        }
+    }
+
+    // Only hides what is there now, the other terminals keep their lines
+    pub(super) fn clear_logs(&mut self, app: &App) {
+        self.cleared_before = app.logs_total();
+        self.log_scroll = 0;
+        self.search.current = None;
+    }
+
+    pub(super) fn search_scope<'a>(&self, app: &'a App) -> Option<&'a str> {
+        if self.search.selected_only {
+            self.selected(app).map(|server| server.name.as_str())
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn is_match(&self, app: &App, message: &ServerMessage) -> bool {
+        match &self.search.pattern {
+            Some(pattern) => {
+                self.search_scope(app).is_none_or(|name| message.name == name)
+                    && pattern.is_match(&message.contents)
+            }
+            None => false,
+        }
+    }
+
+    fn is_visible(&self, app: &App, message: &ServerMessage) -> bool {
+        if self.hidden_streams.contains(&message.stream) {
+            return false;
+        }
+        if let Some(level) = message.level {
+            if self.hidden_levels.contains(&level) {
+                return false;
+            }
+        }
+        !(self.search.filter && self.search.pattern.is_some()) || self.is_match(app, message)
+    }
+
+    fn is_cleared(&self, app: &App, index: usize) -> bool {
+        app.logs_dropped + (index as u64) < self.cleared_before
+    }
+
+    pub(super) fn toggle_level(&mut self, level: LogLevel) {
+        if !self.hidden_levels.remove(&level) {
+            self.hidden_levels.insert(level);
+        }
+        self.log_scroll = 0;
+    }
+
+    pub(super) fn toggle_stream(&mut self, stream: LogStream) {
+        if !self.hidden_streams.remove(&stream) {
+            self.hidden_streams.insert(stream);
+        }
+        self.log_scroll = 0;
+    }
+
+    pub(super) fn timestamp_format(&self, app: &App) -> TimestampFormat {
+        self.timestamps.unwrap_or(app.settings.timestamp_format)
+    }
+
+    // Only this terminal's log panel changes, the setting stays what the admin chose
+    pub(super) fn cycle_timestamps(&mut self, app: &App) {
+        self.timestamps = Some(self.timestamp_format(app).next());
+    }
+
+    pub(super) fn clear_filters(&mut self) {
+        self.hidden_levels.clear();
+        self.hidden_streams.clear();
+        self.log_scroll = 0;
+    }
+
+    // Indices into `App::logs` of the lines the log panel shows
+    pub(super) fn visible_logs(&self, app: &App) -> Vec<usize> {
+        (0..app.logs.len())
+            .filter(|&i| !self.is_cleared(app, i) && self.is_visible(app, &app.logs[i]))
+            .collect()
+    }
+
+    pub(super) fn match_indices(&self, app: &App) -> Vec<usize> {
+        (0..app.logs.len())
+            .filter(|&i| !self.is_cleared(app, i) && self.is_match(app, &app.logs[i]))
+            .collect()
+    }
+
+    // Move the focused match and scroll it into the middle of the panel.
+    // With no focused match yet this lands on the most recent one.
+    pub(super) fn jump_to_match(&mut self, app: &App, forward: bool, view_height: usize) {
+        let matches = self.match_indices(app);
+        let next = match self.search.current {
+            Some(current) if forward => matches
+                .iter()
+                .find(|&&i| i > current)
+                .or(matches.first()),
+            Some(current) => matches
+                .iter()
+                .rev()
+                .find(|&&i| i < current)
+                .or(matches.last()),
+            None => matches.last(),
+        }
+        .copied();
+
+        self.search.current = next;
+        if let Some(index) = next {
+            let visible = self.visible_logs(app);
+            if let Some(position) = visible.iter().position(|&i| i == index) {
+                let from_bottom = visible.len() - 1 - position;
+                self.log_scroll = from_bottom.saturating_sub(view_height / 2);
+            }
+        }
+    }
+
+    pub(super) fn scroll_logs(&mut self, app: &App, delta: isize, view_height: usize) {
+        let max_scroll = self.visible_logs(app).len().saturating_sub(view_height);
+        self.log_scroll = self.log_scroll.saturating_add_signed(delta).min(max_scroll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::servers::MessageType;
+
+    fn message(name: &str, contents: &str) -> ServerMessage {
+        ServerMessage::new(name, contents, MessageType::None)
+    }
+
+    fn searching(query: &str, lines: &[(&str, &str)]) -> (App, View) {
+        let mut app = App::new(db::connect_db(":memory:").unwrap());
+        app.logs = lines.iter().map(|(name, contents)| message(name, contents)).collect();
+        let mut view = View::new(Identity::local());
+        view.search.query = query.to_string();
+        view.search.compile();
+        (app, view)
+    }
+
+    #[test]
+    fn filter_hides_lines_that_do_not_match() {
+        let (app, mut view) = searching("warn", &[("Server 1", "WARN low memory"), ("Server 1", "started")]);
+        assert!(app.logs.iter().all(|line| view.is_visible(&app, line)));
+
+        view.search.filter = true;
+        assert_eq!(view.visible_logs(&app), vec![0]);
+        assert_eq!(view.match_indices(&app), vec![0]);
+    }
+
+    #[test]
+    fn selected_only_limits_matches_to_the_selected_server() {
+        let (mut app, mut view) = searching("ready", &[]);
+        let names: Vec<String> =
+            app.available_servers.iter().map(|server| server.name.clone()).collect();
+        app.logs = names.iter().map(|name| message(name, "ready")).collect();
+        assert_eq!(view.match_indices(&app), (0..names.len()).collect::<Vec<_>>());
+
+        view.search.selected_only = true;
+        view.selected_server = 1;
+        assert_eq!(view.match_indices(&app), vec![1]);
+    }
+
+    #[test]
+    fn jumping_between_matches_wraps_around() {
+        let (app, mut view) = searching(
+            "hit",
+            &[("Server 1", "hit"), ("Server 1", "miss"), ("Server 1", "hit")],
+        );
+        view.jump_to_match(&app, true, 10);
+        assert_eq!(view.search.current, Some(2));
+        view.jump_to_match(&app, true, 10);
+        assert_eq!(view.search.current, Some(0));
+        view.jump_to_match(&app, false, 10);
+        assert_eq!(view.search.current, Some(2));
+    }
+
+    #[test]
+    fn timestamps_toggle_per_terminal() {
+        let (mut app, mut view) = searching("", &[]);
+        let other = View::new(Identity::local());
+        view.cycle_timestamps(&app);
+        assert!(view.timestamp_format(&app) == app.settings.timestamp_format.next());
+        assert!(other.timestamp_format(&app) == app.settings.timestamp_format);
+        // Terminals that never toggled follow the setting
+        app.settings.timestamp_format = app.settings.timestamp_format.next();
+        assert!(other.timestamp_format(&app) == app.settings.timestamp_format);
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::procinfo;
+
+// Sampled once per interval, enough samples are kept for the last five minutes
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const HISTORY_LEN: usize = 300;
+
+// CPU and memory history of one run of a server, process tree included
+pub struct UsageHistory {
+    pub run_id: u64,
+    pub cpu: VecDeque<u64>, // percent of one core, can go past 100 on multi threaded servers
+    pub rss_kb: VecDeque<u64>,
+    pub threads: u64,
+    last_ticks: Option<(u64, u64)>, // process and total cpu ticks at the previous sample
+}
+
+impl UsageHistory {
+    pub fn new(run_id: u64) -> UsageHistory {
+        UsageHistory {
+            run_id,
+            cpu: VecDeque::new(),
+            rss_kb: VecDeque::new(),
+            threads: 0,
+            last_ticks: None,
+        }
+    }
+
+    pub fn sample(&mut self, pid: u32) {
+        let Some(usage) = procinfo::process_usage(&procinfo::process_tree(pid)) else {
+            return;
+        };
+        let Some(total) = procinfo::total_cpu_ticks() else {
+            return;
+        };
+        self.record(usage, total, procinfo::cpu_count());
+    }
+
+    fn record(&mut self, usage: procinfo::ProcessUsage, total: u64, cpus: usize) {
+        // The first sample only sets the baseline for the cpu delta
+        if let Some((last_process, last_total)) = self.last_ticks {
+            // Exited children take their ticks with them, so this can go backwards
+            let process = usage.cpu_ticks.saturating_sub(last_process);
+            let elapsed = total.saturating_sub(last_total).max(1);
+            let percent = process * 100 * cpus as u64 / elapsed;
+            push_bounded(&mut self.cpu, percent);
+        }
+        self.last_ticks = Some((usage.cpu_ticks, total));
+        push_bounded(&mut self.rss_kb, usage.rss_kb);
+        self.threads = usage.threads;
+    }
+}
+
+// Lines a server has logged, with the rate over the last sample interval
+#[derive(Default)]
+pub struct LineCount {
+    pub total: u64,
+    pub per_second: f64,
+    at_last_sample: u64,
+}
+
+impl LineCount {
+    pub fn count(&mut self) {
+        self.total += 1;
+    }
+
+    pub fn sample(&mut self, elapsed: Duration) {
+        self.per_second = (self.total - self.at_last_sample) as f64 / elapsed.as_secs_f64().max(0.001);
+        self.at_last_sample = self.total;
+    }
+}
+
+fn push_bounded(history: &mut VecDeque<u64>, value: u64) {
+    history.push_back(value);
+    if history.len() > HISTORY_LEN {
+        history.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use procinfo::ProcessUsage;
+
+    fn usage(cpu_ticks: u64, rss_kb: u64) -> ProcessUsage {
+        ProcessUsage { cpu_ticks, rss_kb, threads: 4 }
+    }
+
+    #[test]
+    fn cpu_is_a_percentage_of_one_core() {
+        let mut history = UsageHistory::new(1);
+        history.record(usage(100, 2048), 1000, 4);
+        // The first sample is only the baseline
+        assert!(history.cpu.is_empty());
+        assert_eq!(history.rss_kb, [2048]);
+
+        // 50 of 400 ticks on four cpus is half of one core
+        history.record(usage(150, 4096), 1400, 4);
+        assert_eq!(history.cpu, [50]);
+        assert_eq!(history.threads, 4);
+
+        // Children exiting can make the ticks go backwards
+        history.record(usage(10, 4096), 1800, 4);
+        assert_eq!(history.cpu, [50, 0]);
+    }
+
+    #[test]
+    fn history_keeps_five_minutes() {
+        let mut history = VecDeque::new();
+        for value in 0..HISTORY_LEN as u64 + 10 {
+            push_bounded(&mut history, value);
+        }
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history.front(), Some(&10));
+    }
+}
@@ -0,0 +1,15 @@
+// Dashboard files, built into the binary so there is nothing to install next to it
+
+const INDEX_HTML: &str = include_str!("../web/index.html");
+const APP_JS: &str = include_str!("../web/app.js");
+const STYLE_CSS: &str = include_str!("../web/style.css");
+
+// Content type and body of the file served at `path`
+pub fn asset(path: &str) -> Option<(&'static str, &'static str)> {
+    match path {
+        "/" | "/index.html" => Some(("text/html; charset=utf-8", INDEX_HTML)),
+        "/app.js" => Some(("text/javascript; charset=utf-8", APP_JS)),
+        "/style.css" => Some(("text/css; charset=utf-8", STYLE_CSS)),
+        _ => None,
+    }
+}
@@ -0,0 +1,280 @@
+// Dashboard served by the launcher, talks to the same API as curl would
+
+const CONSOLE_LINES = 1000;
+const REFRESH_MS = 2000;
+
+const serversBody = document.querySelector("#servers tbody");
+const consoleBox = document.getElementById("console");
+const consoleServer = document.getElementById("console-server");
+const commandForm = document.getElementById("command-form");
+const commandInput = document.getElementById("command");
+const connection = document.getElementById("connection");
+const dashboard = document.getElementById("dashboard");
+const loginForm = document.getElementById("login");
+const whoami = document.getElementById("whoami");
+const logoutButton = document.getElementById("logout");
+
+let servers = [];
+let me = null; // {user, role, servers} once logged in
+let socket = null;
+let timers = [];
+
+function api(method, path, body) {
+  return fetch(path, {
+    method,
+    // The API turns away changes without it, see api.rs
+    headers: method === "GET" ? {} : { "Content-Type": "application/json" },
+    body: body ? JSON.stringify(body) : undefined,
+  }).then(async (response) => {
+    const data = await response.json();
+    if (response.status === 401 && path !== "/api/login") {
+      showLogin();
+    }
+    if (!response.ok) {
+      throw new Error(data.error || response.statusText);
+    }
+    return data;
+  });
+}
+
+function serverPath(name, action) {
+  return `/api/servers/${encodeURIComponent(name)}` + (action ? `/${action}` : "");
+}
+
+function formatBytes(bytes) {
+  const units = ["B", "KiB", "MiB", "GiB", "TiB"];
+  let unit = 0;
+  while (bytes >= 1024 && unit < units.length - 1) {
+    bytes /= 1024;
+    unit += 1;
+  }
+  return `${bytes.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+}
+
+function cell(text) {
+  const td = document.createElement("td");
+  td.textContent = text;
+  return td;
+}
+
+function button(label, enabled, action) {
+  const b = document.createElement("button");
+  b.textContent = label;
+  b.disabled = !enabled;
+  b.addEventListener("click", () => {
+    b.disabled = true;
+    action()
+      .catch((e) => note(e.message, "ERROR"))
+      .finally(refreshServers);
+  });
+  return b;
+}
+
+// Mirrors the launcher's own check, it refuses anything else anyway
+function canControl(server) {
+  if (!me || me.role === "viewer") {
+    return false;
+  }
+  return me.role === "admin" || me.servers.length === 0 || me.servers.includes(server.name);
+}
+
+function renderServers() {
+  serversBody.replaceChildren(
+    ...servers.map((server) => {
+      const tr = document.createElement("tr");
+      tr.append(cell(server.name));
+
+      const state = document.createElement("td");
+      const badge = document.createElement("span");
+      badge.className = `badge ${server.state}`;
+      badge.textContent = server.state + (server.background ? " (background)" : "");
+      state.append(badge);
+      tr.append(state);
+
+      tr.append(cell(server.pid ?? "-"));
+      tr.append(cell(server.started_at ? new Date(server.started_at).toLocaleString() : "-"));
+
+      const allowed = canControl(server);
+      const local = allowed && server.state !== "stopped" && !server.background;
+      const actions = document.createElement("td");
+      actions.append(
+        button("Start", allowed && server.state === "stopped", () => api("POST", serverPath(server.name, "start"))),
+        " ",
+        button("Stop", local, () => api("POST", serverPath(server.name, "stop"))),
+        " ",
+        button("Restart", local, () => api("POST", serverPath(server.name, "restart"))),
+      );
+      tr.append(actions);
+      return tr;
+    }),
+  );
+
+  // Keep the console picker in step with the list
+  const selected = consoleServer.value;
+  const options = [new Option("All servers", "")].concat(
+    servers.map((server) => new Option(server.name, server.name)),
+  );
+  consoleServer.replaceChildren(...options);
+  consoleServer.value = selected;
+}
+
+function refreshServers() {
+  return api("GET", "/api/servers")
+    .then((list) => {
+      servers = list;
+      renderServers();
+    })
+    .catch(() => {});
+}
+
+function refreshHost() {
+  api("GET", "/api/host")
+    .then((host) => {
+      document.getElementById("host-cpu").textContent =
+        `${host.cpu_percent.toFixed(0)}% over ${host.cores.length} cores`;
+      document.getElementById("host-memory").textContent = host.memory
+        ? `${formatBytes(host.memory.total_bytes - host.memory.available_bytes)} / ${formatBytes(host.memory.total_bytes)}`
+        : "-";
+      document.getElementById("host-network").textContent =
+        `down ${formatBytes(host.network.rx_rate)}/s, up ${formatBytes(host.network.tx_rate)}/s`;
+      document.getElementById("host-disks").textContent =
+        host.disks
+          .map((disk) => `${disk.mount} ${formatBytes(disk.available_bytes)} free of ${formatBytes(disk.total_bytes)}`)
+          .join(", ") || "-";
+      document.getElementById("host-temps").textContent =
+        host.temperatures.map((t) => `${t.label} ${t.celsius.toFixed(0)}°C`).join(", ") || "-";
+    })
+    .catch(() => {});
+}
+
+function visible(line) {
+  return !consoleServer.value || line.dataset.server === consoleServer.value;
+}
+
+function append(text, className, server) {
+  const follow = consoleBox.scrollTop + consoleBox.clientHeight >= consoleBox.scrollHeight - 4;
+  const line = document.createElement("div");
+  line.textContent = text;
+  line.className = className;
+  line.dataset.server = server;
+  line.hidden = !visible(line);
+  consoleBox.append(line);
+  while (consoleBox.childElementCount > CONSOLE_LINES) {
+    consoleBox.firstChild.remove();
+  }
+  if (follow) {
+    consoleBox.scrollTop = consoleBox.scrollHeight;
+  }
+}
+
+function note(text, level) {
+  append(`[dashboard] ${text}`, `line-${level}`, "");
+}
+
+function showEvent(event) {
+  const time = new Date(event.timestamp).toLocaleTimeString();
+  if (event.type === "lifecycle") {
+    const code = event.exit_code !== undefined ? ` (exit ${event.exit_code})` : "";
+    append(`${time} [${event.server}] ${event.event}${code}`, "line-lifecycle", event.server);
+    refreshServers();
+  } else {
+    const name = event.server ? `[${event.server}] ` : "";
+    append(`${time} ${name}${event.text}`, `line-${event.level}`, event.server);
+  }
+}
+
+function connect() {
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  socket = new WebSocket(`${scheme}://${location.host}/api/stream?replay=200`);
+  socket.onopen = () => {
+    consoleBox.replaceChildren();
+    connection.textContent = "online";
+    connection.className = "badge running";
+  };
+  socket.onmessage = (message) => showEvent(JSON.parse(message.data));
+  socket.onclose = () => {
+    connection.textContent = "offline";
+    connection.className = "badge stopped";
+    if (!dashboard.hidden) {
+      setTimeout(connect, REFRESH_MS);
+    }
+  };
+}
+
+function showLogin() {
+  if (!loginForm.hidden) {
+    return;
+  }
+  dashboard.hidden = true;
+  loginForm.hidden = false;
+  logoutButton.hidden = true;
+  whoami.textContent = "";
+  timers.forEach(clearInterval);
+  timers = [];
+  if (socket) {
+    socket.close();
+  }
+}
+
+// Without users the launcher lets this machine in as `local`, there is nothing to log out of
+function showDashboard() {
+  return api("GET", "/api/me").then((identity) => {
+    me = identity;
+    loginForm.hidden = true;
+    dashboard.hidden = false;
+    whoami.textContent = `${me.user} (${me.role})`;
+    logoutButton.hidden = me.user === "local";
+    commandInput.disabled = me.role === "viewer";
+    refreshServers();
+    refreshHost();
+    timers = [setInterval(refreshServers, REFRESH_MS), setInterval(refreshHost, REFRESH_MS)];
+    connect();
+  });
+}
+
+loginForm.addEventListener("submit", (e) => {
+  e.preventDefault();
+  const user = document.getElementById("login-user").value;
+  const password = document.getElementById("login-password").value;
+  const error = document.getElementById("login-error");
+  api("POST", "/api/login", { user, password })
+    .then(() => {
+      error.textContent = "";
+      document.getElementById("login-password").value = "";
+      return showDashboard();
+    })
+    .catch((e) => {
+      error.textContent = e.message;
+    });
+});
+
+logoutButton.addEventListener("click", () => {
+  api("POST", "/api/logout").finally(showLogin);
+});
+
+consoleServer.addEventListener("change", () => {
+  for (const line of consoleBox.children) {
+    line.hidden = !visible(line);
+  }
+  consoleBox.scrollTop = consoleBox.scrollHeight;
+});
+
+commandForm.addEventListener("submit", (e) => {
+  e.preventDefault();
+  const name = consoleServer.value;
+  const command = commandInput.value;
+  if (!name) {
+    note("pick a server to send commands to", "WARN");
+    return;
+  }
+  if (!command) {
+    return;
+  }
+  api("POST", serverPath(name, "command"), { command })
+    .then(() => {
+      commandInput.value = "";
+    })
+    .catch((error) => note(error.message, "ERROR"));
+});
+
+showDashboard().catch(() => {});